@@ -64,6 +64,11 @@ rustPlatform.buildRustPackage rec {
     # Install udev rules
     install -Dm644 ${./99-soundblaster-g6.rules} $out/lib/udev/rules.d/99-soundblaster-g6.rules
 
+    # User service for the daemon
+    install -Dm644 ${./linuxblasterd.service} $out/lib/systemd/user/linuxblasterd.service
+    substituteInPlace $out/lib/systemd/user/linuxblasterd.service \
+      --replace-fail "ExecStart=linuxblasterd" "ExecStart=$out/bin/linuxblasterd"
+
     # README and LICENSE
     install -Dm644 ${../README.md} $out/share/doc/${pname}/README.md
     install -Dm644 ${../LICENSE} $out/share/licenses/${pname}/LICENSE
@@ -77,6 +82,8 @@ rustPlatform.buildRustPackage rec {
   postFixup = ''
     # Add runtime dependencies to the binary
     patchelf --add-rpath "${lib.makeLibraryPath [ udev wayland libxkbcommon libGL libglvnd libusb1 ]}" $out/bin/linuxblaster_control
+    patchelf --add-rpath "${lib.makeLibraryPath [ udev libusb1 ]}" $out/bin/linuxblasterd
+    patchelf --add-rpath "${lib.makeLibraryPath [ udev libusb1 ]}" $out/bin/blasterctl
   '';
 
   desktopItems = [
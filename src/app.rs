@@ -1,6 +1,14 @@
 // #![allow(unused)]
 
-use linuxblaster_control::{BlasterXG6, DEFAULT_BASE_PATH, FeatureId, ValueKind};
+use linuxblaster_control::{
+    BlasterXG6, CONNECTION_CHECK_INTERVAL, Config, DEFAULT_BASE_PATH,
+    DeviceDescriptor, DeviceSelector, FeatureChange, FeatureGroup, FeatureId,
+    LibraryEntry, NotificationGroup, Notifier, Profile, ProfileDiff,
+    ProfileLibrary, ProfileScope, ValueKind, Verbosity,
+    dbus::{self, SharedBlaster},
+    eq::{EqFormat, GraphicEq},
+    sbc, services, socket,
+};
 use eframe::egui::{
     self, Button, Color32, RichText, Vec2, Vec2b,
 };
@@ -11,9 +19,13 @@ use egui_plot::{CoordinatesFormatter, Corner, GridInput, GridMark, Line, Plot, P
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use std::cmp::Reverse;
-use std::sync::{LazyLock, Mutex};
+use std::sync::mpsc::Receiver;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
 use tracing::{debug, error, warn};
 
+use crate::tray::{self, BlasterTray};
 use crate::{AUTOEQ_DB, AutoEqDb, HeadphoneResult};
 
 #[macro_use]
@@ -32,17 +44,110 @@ static SEARCH_RESULTS: LazyLock<Mutex<Vec<&'static str>>> =
     LazyLock::new(|| Mutex::new(Vec::new()));
 static PROFILE_NAME: LazyLock<Mutex<String>> =
     LazyLock::new(|| Mutex::new(String::new()));
+static PROFILE_TAGS: LazyLock<Mutex<String>> =
+    LazyLock::new(|| Mutex::new(String::new()));
+/// Groups "Save Current" limits the profile to, all features if empty.
+static PROFILE_SCOPE: LazyLock<Mutex<Vec<FeatureGroup>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+/// Cached `ProfileLibrary::list()`, `None` once it needs to be re-read.
+static LIBRARY: LazyLock<Mutex<Option<Vec<LibraryEntry>>>> =
+    LazyLock::new(|| Mutex::new(None));
+/// The library entry the Delete button is waiting for confirmation on.
+static PENDING_DELETE: LazyLock<Mutex<Option<String>>> =
+    LazyLock::new(|| Mutex::new(None));
+/// A profile waiting for confirmation in the apply preview.
+static PENDING_APPLY: LazyLock<Mutex<Option<(Profile, ProfileDiff)>>> =
+    LazyLock::new(|| Mutex::new(None));
+static CONFIG: LazyLock<Mutex<Config>> = LazyLock::new(|| Mutex::new(Config::load()));
+/// Result and time of the last `ensure_connected()`.
+static CONNECTION: LazyLock<Mutex<(bool, Instant)>> =
+    LazyLock::new(|| Mutex::new((true, Instant::now())));
+static DEVICE_LIST: LazyLock<Mutex<Vec<DeviceDescriptor>>> =
+    LazyLock::new(|| Mutex::new(BlasterXG6::enumerate().unwrap_or_default()));
 
 
-pub struct BlasterApp(pub BlasterXG6);
+pub struct BlasterApp {
+    blaster: SharedBlaster,
+    /// Serve `blaster` on D-Bus and the socket while the window is open,
+    /// so `blasterctl` and scripts work alongside the GUI.
+    bus: Option<zbus::blocking::Connection>,
+    server: Option<socket::Server>,
+    /// Closing the window hides it in the tray, if there is one.
+    tray: Option<ksni::blocking::Handle<BlasterTray>>,
+    changes: Receiver<FeatureChange>,
+    /// Announces changes while the window is hidden in the tray.
+    notifier: Notifier,
+}
+
+impl BlasterApp {
+    pub fn new(blaster: BlasterXG6, ctx: &egui::Context) -> Self {
+        let changes = blaster.subscribe();
+        let notifier = Notifier::new(
+            CONFIG.lock().unwrap().notifications.clone(),
+            &blaster,
+        );
+        let attached = blaster.is_attached();
+        let blaster = Arc::new(Mutex::new(blaster));
+        let (mut bus, mut server) = (None, None);
+        // attached to the daemon, which does all of this already
+        if !attached {
+            bus = dbus::serve(blaster.clone())
+                .inspect_err(|error| warn!("Not serving on D-Bus: {}", error))
+                .ok();
+            server = socket::Server::serve(blaster.clone())
+                .inspect_err(|error| {
+                    warn!("Not serving on a socket: {}", error)
+                })
+                .ok();
+            services::start(blaster.clone(), &CONFIG.lock().unwrap());
+        }
+        let tray = tray::spawn(blaster.clone(), ctx.clone());
+        Self {
+            blaster,
+            bus,
+            server,
+            tray,
+            changes,
+            notifier,
+        }
+    }
+}
 
 impl eframe::App for BlasterApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.blaster.lock().unwrap().save_last_state();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        ctx.request_repaint_after(Duration::from_millis(250));
+        if self.tray.is_some()
+            && ctx.input(|input| input.viewport().close_requested())
+            && !tray::QUITTING.load(Ordering::Relaxed)
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            tray::hide(ctx);
+        }
+        let mut blaster = self.blaster.lock().unwrap();
+
+        let connected = {
+            let mut connection = CONNECTION.lock().unwrap();
+            if connection.1.elapsed() >= CONNECTION_CHECK_INTERVAL {
+                *connection = (blaster.ensure_connected(), Instant::now());
+            }
+            connection.0
+        };
+        // pick up changes made on the device itself (e.g. the SBX button)
+        if connected {
+            blaster.poll_notifications();
+        }
+
         #[cfg(debug_assertions)]
         if ctx.input(|i| i.key_pressed(egui::Key::D)) {
             ctx.set_debug_on_hover(!ctx.debug_on_hover());
         }
 
+        let mut switch_to: Option<DeviceSelector> = None;
+
         egui::TopBottomPanel::top("top_panel")
             .resizable(false)
             .exact_height(56.0)
@@ -50,11 +155,41 @@ impl eframe::App for BlasterApp {
                 ui.horizontal_centered(|ui| {
                     // Reset All Button
                     if ui.button("Reset All").clicked() {
-                        let _ = self.0.reset();
+                        let _ = blaster.reset();
+                    }
+
+                    // Device Picker
+                    switch_to = device_picker(&blaster, ui);
+                    if !connected {
+                        ui.label(RichText::new("Disconnected").color(Color32::RED));
                     }
 
                     // Profile Management 
                     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        if ui.button("Import SBC").clicked() {
+                            let Some(path) = rfd::FileDialog::new()
+                                .add_filter("Sound Blaster Command Profile", &["json", "xml"])
+                                .pick_file()
+                            else {
+                                debug!("No path selected");
+                                return;
+                            };
+
+                            match sbc::import(&path) {
+                                Ok(import) => {
+                                    for entry in &import.unmapped {
+                                        warn!("SBC import: could not map {}", entry);
+                                    }
+                                    preview_apply(&blaster, import.profile);
+                                }
+                                Err(error) => {
+                                    error!("Failed to import SBC profile");
+                                    error!("Path: {}", path.display());
+                                    error!("Error: {}", error);
+                                }
+                            }
+                        }
+
                         if ui.button("Load Profile").clicked() {
                             let Some(path) = rfd::FileDialog::new() 
                                 .add_filter("Profile", &["json"])
@@ -65,10 +200,13 @@ impl eframe::App for BlasterApp {
                                 return;
                             };
 
-                            if let Err(error) = self.0.apply_profile(path.clone()) {
-                                error!("Failed to apply profile from file");
-                                error!("Path: {}", path.display());
-                                error!("Error: {}", error);
+                            match Profile::load(&path) {
+                                Ok(profile) => preview_apply(&blaster, profile),
+                                Err(error) => {
+                                    error!("Failed to load profile from file");
+                                    error!("Path: {}", path.display());
+                                    error!("Error: {}", error);
+                                }
                             }
                         }
                         
@@ -83,7 +221,25 @@ impl eframe::App for BlasterApp {
                                 return;
                             };
 
-                            if let Err(error) = self.0.save_profile(path.clone()) {
+                            if let Err(error) = blaster.save_profile(path.clone()) {
+                                error!("Failed to save profile to file");
+                                error!("Path: {}", path.display());
+                                error!("Error: {}", error);
+                            }
+                        }
+
+                        if ui.button("Save Both Outputs").clicked() {
+                            let Some(path) = rfd::FileDialog::new()
+                                .set_file_name("profile.json")
+                                .add_filter("Profile", &["json"])
+                                .set_directory(DEFAULT_BASE_PATH.join("profiles"))
+                                .save_file()
+                            else {
+                                debug!("No path selected");
+                                return;
+                            };
+
+                            if let Err(error) = blaster.save_profile_all_outputs(path.clone()) {
                                 error!("Failed to save profile to file");
                                 error!("Path: {}", path.display());
                                 error!("Error: {}", error);
@@ -93,23 +249,40 @@ impl eframe::App for BlasterApp {
                 });
             },
         );
+
+        apply_preview(&blaster, ctx);
+
+        if let Some(selector) = switch_to {
+            match BlasterXG6::open(&selector) {
+                Ok(opened) => {
+                    self.changes = opened.subscribe();
+                    *blaster = opened;
+                }
+                Err(error) => {
+                    error!("Failed to open device {:?}", selector);
+                    error!("Error: {}", error);
+                }
+            }
+        }
+
         egui::SidePanel::left("left_panel")
             .resizable(false)
             .show(ctx, |ui| {
-                nav_pane(&self.0, ui, "SBX", Some(FeatureId::SbxMaster), true);
-                nav_pane(&self.0, ui, "Playback", Some(FeatureId::Output), true);
-                nav_pane(&self.0, ui, "Recording", None, true);
-                nav_pane(&self.0, ui, "Scout Mode", Some(FeatureId::ScoutMode), false);
+                nav_pane(&blaster, ui, "SBX", Some(FeatureId::SbxMaster), true);
+                nav_pane(&blaster, ui, "Playback", Some(FeatureId::Output), true);
+                nav_pane(&blaster, ui, "Recording", None, true);
+                nav_pane(&blaster, ui, "Scout Mode", Some(FeatureId::ScoutMode), false);
+                nav_pane(&blaster, ui, "Profiles", None, true);
             });
         egui::CentralPanel::default().show(ctx, |ui| {
             let state = *UI_SELECTED.lock().unwrap();
             match *UI_SELECTED.lock().unwrap() {
                 "SBX" => {
                     if *AUTOEQ_MODAL.lock().unwrap() {
-                        autoeq_pane(&self.0, ui);
+                        autoeq_pane(&blaster, ui);
                     }
                     else {
-                        sbx_pane(&self.0, ui);
+                        sbx_pane(&blaster, ui);
                     }
                 }
                 "Scout Mode" => {
@@ -121,11 +294,34 @@ impl eframe::App for BlasterApp {
                 "Recording" => {
                     todo!();
                 }
+                "Profiles" => {
+                    profiles_pane(&blaster, ui);
+                }
                 _ => {
                     warn!("Unknown UI selected: {}", state);
                 }
             }
         });
+
+        let changes: Vec<FeatureChange> = self.changes.try_iter().collect();
+        if tray::HIDDEN.load(Ordering::Relaxed)
+            && !changes.is_empty()
+            && !blaster.is_attached()
+        {
+            self.notifier.verbosity =
+                CONFIG.lock().unwrap().notifications.clone();
+            self.notifier.update(&blaster, &changes);
+        }
+        for change in changes {
+            if let Some(bus) = &self.bus
+                && let Err(error) = dbus::emit_change(bus, &change)
+            {
+                warn!("Failed to emit change of {}: {}", change.id, error);
+            }
+            if let Some(server) = &self.server {
+                server.broadcast(&change);
+            }
+        }
     }
 }
 
@@ -139,11 +335,13 @@ impl eframe::App for BlasterApp {
 fn nav_pane(
     blaster: &BlasterXG6, 
     ui: &mut egui::Ui, 
-    pane_name: &str,
+    pane_name: &'static str,
     feature_id: Option<FeatureId>, 
     with_selector: bool,
 ) {
-    let feature = feature_id.map(|id| blaster.feature(id));
+    let feature = feature_id
+        .filter(|&id| blaster.supports(id))
+        .map(|id| blaster.feature(id));
 
     ui.vertical_centered_justified(|ui| {
         ui.set_width(160.0);
@@ -176,6 +374,8 @@ fn nav_pane(
                         let mut selected = UI_SELECTED.lock().unwrap();
                         if *selected == pane_name {
                             *selected = "";
+                        } else {
+                            *selected = pane_name;
                         }
                     }
                 });
@@ -187,6 +387,311 @@ fn nav_pane(
     });
 }
 
+/// Lists all connected G6s.
+/// Returns the selector of the newly picked device, if any.
+fn device_picker(
+    blaster: &BlasterXG6,
+    ui: &mut egui::Ui,
+) -> Option<DeviceSelector> {
+    if blaster.is_attached() {
+        ui.label(RichText::new("via linuxblasterd").color(Color32::GRAY));
+        return None;
+    }
+
+    let current = blaster.descriptor();
+    let mut picked = None;
+
+    let response = egui::ComboBox::from_id_salt("device_picker")
+        .selected_text(
+            current
+                .and_then(|device| device.serial.clone())
+                .unwrap_or_else(|| "Device".to_string()),
+        )
+        .show_ui(ui, |ui| {
+            for device in DEVICE_LIST.lock().unwrap().iter() {
+                let selected = current == Some(device);
+                if ui
+                    .selectable_label(selected, device.to_string())
+                    .clicked()
+                    && !selected
+                {
+                    picked = Some(DeviceSelector::for_descriptor(device));
+                }
+            }
+        });
+
+    // re-scan whenever the list is opened, so hotplugged devices show up
+    if response.response.clicked() {
+        *DEVICE_LIST.lock().unwrap() =
+            BlasterXG6::enumerate().unwrap_or_default();
+    }
+
+    picked
+}
+
+/// Shows what applying `profile` would change before it is applied,
+/// see `apply_preview()`.
+fn preview_apply(blaster: &BlasterXG6, profile: Profile) {
+    let diff = blaster.diff(&profile);
+    *PENDING_APPLY.lock().unwrap() = Some((profile, diff));
+}
+
+/// The confirmation dialog for `PENDING_APPLY`.
+fn apply_preview(blaster: &BlasterXG6, ctx: &egui::Context) {
+    let mut pending = PENDING_APPLY.lock().unwrap();
+    let Some((profile, diff)) = pending.as_ref() else {
+        return;
+    };
+
+    let mut apply = false;
+    let mut cancel = false;
+    let modal = egui::Modal::new(egui::Id::new("apply_preview")).show(ctx, |ui| {
+        ui.set_width(480.0);
+        ui.heading(format!("Apply {:?}?", profile.metadata.name));
+        ui.separator();
+
+        if diff.is_empty() {
+            ui.label(RichText::new("Nothing would change").color(Color32::GRAY));
+        }
+        ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+            Grid::new("apply_preview_grid").striped(true).show(ui, |ui| {
+                for change in &diff.changes {
+                    ui.label(
+                        RichText::new(change.output.as_deref().unwrap_or_default())
+                            .color(Color32::GRAY),
+                    );
+                    ui.label(change.id.display_name());
+                    ui.label(change.from_label());
+                    ui.label("➡");
+                    ui.label(RichText::new(change.to_label()).strong());
+                    ui.end_row();
+                }
+            });
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            apply = ui.button("Apply").clicked();
+            cancel = ui.button("Cancel").clicked();
+        });
+    });
+
+    if apply {
+        if let Err(error) = blaster.apply(profile) {
+            error!("Failed to apply profile {:?}", profile.metadata.name);
+            error!("Error: {}", error);
+        }
+        *pending = None;
+    } else if cancel || modal.should_close() {
+        *pending = None;
+    }
+}
+
+/// The profile library: save the current state,
+/// apply, rename, duplicate, tag and delete saved profiles.
+/// Rename, Duplicate and Set Tags take their input from the fields on top.
+fn profiles_pane(blaster: &BlasterXG6, ui: &mut egui::Ui) {
+    let library = ProfileLibrary::default();
+    let mut changed = false;
+
+    ui.vertical_centered_justified(|ui| {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Name").color(Color32::GRAY));
+            ui.text_edit_singleline(&mut *PROFILE_NAME.lock().unwrap());
+            ui.label(RichText::new("Tags").color(Color32::GRAY));
+            ui.text_edit_singleline(&mut *PROFILE_TAGS.lock().unwrap())
+                .on_hover_text("Comma separated");
+
+            if ui.button("Save Current").clicked() {
+                let name = PROFILE_NAME.lock().unwrap().trim().to_string();
+                let mut profile = blaster.to_profile(&name);
+                let scope: Vec<ProfileScope> = PROFILE_SCOPE
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|&group| ProfileScope::Group(group))
+                    .collect();
+                if !scope.is_empty() {
+                    profile = profile.restrict(&scope);
+                }
+                profile.metadata.tags = tags_input();
+                if let Err(error) = library.save(&profile) {
+                    error!("Failed to save profile {:?}", name);
+                    error!("Error: {}", error);
+                }
+                changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut config = CONFIG.lock().unwrap();
+            if ui
+                .checkbox(&mut config.restore_last_state, "Restore last state on connect")
+                .on_hover_text("Re-applies the last known settings when the G6 is plugged in or reset")
+                .changed()
+                && let Err(error) = config.save()
+            {
+                error!("Failed to save config");
+                error!("Error: {}", error);
+            }
+            if ui
+                .checkbox(&mut config.auto_switch_output, "Log headphone jack switches")
+                .on_hover_text("Logs the Output switch the headphone jack would trigger. The jack notification isn't verified yet, so nothing is switched")
+                .changed()
+                && let Err(error) = config.save()
+            {
+                error!("Failed to save config");
+                error!("Error: {}", error);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Notifications").color(Color32::GRAY))
+                .on_hover_text("Desktop notifications for changes from hotkeys, rules and the device, shown by the daemon and while the window is hidden");
+            let mut config = CONFIG.lock().unwrap();
+            let mut changed = false;
+            for &group in NotificationGroup::ALL {
+                let verbosity = config.notifications.entry(group).or_default();
+                egui::ComboBox::from_label(group.display_name())
+                    .selected_text(verbosity.display_name())
+                    .show_ui(ui, |ui| {
+                        for &option in Verbosity::ALL {
+                            changed |= ui
+                                .selectable_value(verbosity, option, option.display_name())
+                                .changed();
+                        }
+                    });
+            }
+            if changed && let Err(error) = config.save() {
+                error!("Failed to save config");
+                error!("Error: {}", error);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Scope").color(Color32::GRAY))
+                .on_hover_text("Only save these features, nothing selected saves everything");
+            let mut scope = PROFILE_SCOPE.lock().unwrap();
+            for &group in FeatureGroup::ALL {
+                let mut selected = scope.contains(&group);
+                if ui.checkbox(&mut selected, group.display_name()).changed() {
+                    scope.retain(|&scoped| scoped != group);
+                    if selected {
+                        scope.push(group);
+                    }
+                }
+            }
+        });
+        ui.separator();
+
+        let mut cached = LIBRARY.lock().unwrap();
+        let entries = cached.get_or_insert_with(|| {
+            library.list().unwrap_or_else(|error| {
+                error!("Failed to list profiles in {:?}", library.root());
+                error!("Error: {}", error);
+                Vec::new()
+            })
+        });
+
+        if entries.is_empty() {
+            ui.label(RichText::new("No saved profiles").color(Color32::GRAY));
+        }
+
+        ScrollArea::vertical().show(ui, |ui| {
+            Grid::new("profiles_grid").striped(true).show(ui, |ui| {
+                for entry in entries.iter() {
+                    let name = entry.name();
+                    let favourite = entry.profile.metadata.favourite;
+
+                    if ui
+                        .button(if favourite { "★" } else { "☆" })
+                        .on_hover_text("Favourite")
+                        .clicked()
+                    {
+                        report(library.set_favourite(name, !favourite), name);
+                        changed = true;
+                    }
+
+                    // the profile the device is currently in
+                    let label = entry.label();
+                    if blaster.matches_profile(&entry.profile) {
+                        ui.label(RichText::new(format!("● {}", label)).strong());
+                    } else {
+                        ui.label(label);
+                    }
+                    let scope = entry
+                        .profile
+                        .scope
+                        .as_ref()
+                        .map(|scope| {
+                            scope
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        })
+                        .unwrap_or_else(|| "Full".to_string());
+                    ui.label(RichText::new(scope).color(Color32::GRAY));
+                    ui.label(
+                        RichText::new(entry.profile.metadata.tags.join(", "))
+                            .color(Color32::GRAY),
+                    );
+
+                    if ui.button("Apply").clicked() {
+                        preview_apply(blaster, entry.profile.clone());
+                    }
+                    if ui.button("Rename").clicked() {
+                        let new_name = PROFILE_NAME.lock().unwrap().trim().to_string();
+                        report(library.rename(name, &new_name), name);
+                        changed = true;
+                    }
+                    if ui.button("Duplicate").clicked() {
+                        let new_name = PROFILE_NAME.lock().unwrap().trim().to_string();
+                        report(library.duplicate(name, &new_name), name);
+                        changed = true;
+                    }
+                    if ui.button("Set Tags").clicked() {
+                        report(library.set_tags(name, &tags_input()), name);
+                        changed = true;
+                    }
+                    let mut pending_delete = PENDING_DELETE.lock().unwrap();
+                    if pending_delete.as_deref() == Some(name) {
+                        if ui.button(RichText::new("Confirm Delete").color(Color32::RED)).clicked() {
+                            report(library.delete(name), name);
+                            *pending_delete = None;
+                            changed = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            *pending_delete = None;
+                        }
+                    } else if ui.button(RichText::new("Delete").color(Color32::RED)).clicked() {
+                        *pending_delete = Some(name.to_string());
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+
+        if changed {
+            *cached = None;
+        }
+    });
+
+    fn tags_input() -> Vec<String> {
+        PROFILE_TAGS
+            .lock()
+            .unwrap()
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+
+    fn report(result: Result<(), Box<dyn std::error::Error>>, name: &str) {
+        if let Err(error) = result {
+            error!("Failed to update profile {:?}", name);
+            error!("Error: {}", error);
+        }
+    }
+}
+
 fn sbx_pane(blaster: &BlasterXG6, ui: &mut egui::Ui) {
     ui.columns(2, |columns| {
         // SBX Features
@@ -211,6 +716,10 @@ fn eq_features(blaster: &BlasterXG6, ui: &mut egui::Ui) {
             if ui.button(RichText::new("Select AutoEq Profile").color(Color32::GRAY)).clicked() {
                 *AUTOEQ_MODAL.lock().unwrap() = true;
             }
+            eq_export_menu(ui, GraphicEq::from_device(blaster), "g6_eq");
+            if ui.button(RichText::new("Import EQ").color(Color32::GRAY)).clicked() {
+                import_eq(blaster);
+            }
         });
 
         ui.separator();
@@ -281,10 +790,13 @@ fn sbx_features(blaster: &BlasterXG6, ui: &mut egui::Ui) {
 
         Grid::new("sbx_grid").show(ui, |ui| {
             for &toggle_id in FeatureId::SBX_TOGGLES {
-                let toggle_feature = blaster.feature(toggle_id);
                 let slider_id = toggle_id
                     .paired_slider()
                     .expect("SBX toggle must have a paired slider");
+                if !blaster.supports(toggle_id) || !blaster.supports(slider_id) {
+                    continue;
+                }
+                let toggle_feature = blaster.feature(toggle_id);
                 let slider_feature = blaster.feature(slider_id);
                 let is_percentage = matches!(slider_id.value_kind(), ValueKind::Percentage);
                 let mut slider_value = if is_percentage {
@@ -495,15 +1007,21 @@ fn autoeq_pane(blaster: &BlasterXG6, ui: &mut egui::Ui) {
 
                                         plot_ui.line(Line::new(format!("eq_curve_{}", name), points).width(2.0));
                                     });
+                                    let result_eq = GraphicEq {
+                                        preamp: result.preamp,
+                                        bands: result.ten_band_eq,
+                                    };
                                     let apply_button = ui.button(RichText::new("Apply Profile"));
                                     if apply_button.clicked() {
-                                        for (index, gain) in result.ten_band_eq.iter().enumerate() {
-                                            if gain.abs() > 0.01 {
-                                                let _ = blaster.set_feature(FeatureId::EQ_BANDS[index], Some(*gain));
-                                            }
+                                        let mut transaction = blaster.transaction();
+                                        for (id, value) in result_eq.writes() {
+                                            transaction.set(id, value);
+                                        }
+                                        if let Err(error) = transaction.commit() {
+                                            error!("Failed to apply AutoEq profile: {}", error);
                                         }
-                                        let _ = blaster.set_feature(FeatureId::EqPreAmp, Some(result.preamp));
                                     }
+                                    eq_export_menu(ui, result_eq, name);
 
                                 });
                             });
@@ -517,6 +1035,64 @@ fn autoeq_pane(blaster: &BlasterXG6, ui: &mut egui::Ui) {
     });
 }
 
+/// "Export EQ" menu with one entry per `EqFormat`.
+fn eq_export_menu(ui: &mut egui::Ui, eq: GraphicEq, file_name: &str) {
+    ui.menu_button(RichText::new("Export EQ").color(Color32::GRAY), |ui| {
+        for &format in EqFormat::ALL {
+            if !ui.button(format.to_string()).clicked() {
+                continue;
+            }
+            ui.close();
+
+            let Some(path) = rfd::FileDialog::new()
+                .set_file_name(format!("{}.{}", file_name, format.extension()))
+                .save_file()
+            else {
+                debug!("No path selected");
+                return;
+            };
+
+            if let Err(error) = eq.export(format, &path) {
+                error!("Failed to export EQ as {}", format);
+                error!("Path: {}", path.display());
+                error!("Error: {}", error);
+            }
+        }
+    });
+}
+
+/// Loads an Equalizer APO / EasyEffects / PipeWire file into the hardware EQ.
+fn import_eq(blaster: &BlasterXG6) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("Equalizer", &["txt", "json", "conf"])
+        .pick_file()
+    else {
+        debug!("No path selected");
+        return;
+    };
+
+    let import = match GraphicEq::import(&path) {
+        Ok(import) => import,
+        Err(error) => {
+            error!("Failed to import EQ");
+            error!("Path: {}", path.display());
+            error!("Error: {}", error);
+            return;
+        }
+    };
+    for filter in &import.skipped {
+        warn!("EQ import: skipped {}", filter);
+    }
+
+    let mut transaction = blaster.transaction();
+    for (id, value) in import.eq.writes() {
+        transaction.set(id, value);
+    }
+    if let Err(error) = transaction.commit() {
+        error!("Failed to apply imported EQ: {}", error);
+    }
+}
+
 fn calculate_peaking_eq_response(freq: f64, center_freq: f64, gain: f64, q: f64) -> f64 {
     let bandwidth = center_freq / q;
     let diff = (freq - center_freq).abs();
@@ -0,0 +1,332 @@
+//! Command line interface for scripting the G6.
+//!
+//! ```text
+//! blasterctl [--device <serial|path>] <command>
+//! ```
+//!
+//! Talks to `linuxblasterd` (or the GUI) if it is running,
+//! over D-Bus or the socket, otherwise opens the device directly.
+
+use std::error::Error;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use linuxblaster_control::dbus::{self, LinuxBlasterProxyBlocking};
+use linuxblaster_control::socket::Client;
+use linuxblaster_control::{
+    BlasterXG6, ChangeSource, DeviceModel, DeviceSelector, FeatureChange,
+    FeatureId, Profile, ProfileDiff, ProfileLibrary, ProfileScope, RuleSet,
+    Schedule,
+};
+use tracing::Level;
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+const USAGE: &str = "\
+Usage: blasterctl [--device <serial|path>] [--scope <scope,...>] <command>
+
+Commands:
+  get <feature>                     Print the value of a feature
+  set <feature> <value>             Set a feature, e.g. set SurroundLevel 0.5
+  toggle <feature>                  Flip a toggle
+  output [<output>]                 Switch to an Output, or to the next one
+  watch                             Print every change until interrupted
+  rules                             List the rules, * marks the matching one
+  schedule                          List the scheduled profiles, * marks
+                                    the one in effect
+  profile apply <layer>...          Apply profiles stacked in order
+  profile diff <profile>            What applying <profile> would change
+  profile diff <profile> <other>    Differences between two profiles
+
+Features are named like SurroundToggle, SmartVolMode or Eq1kHz.
+Toggles are 0 or 1, sliders 0.0 - 1.0, EQ bands in dB, presets the index
+of their label (e.g. SmartVolMode 2 for Night).
+Profiles are file paths or names from the profile library.
+--scope limits the profiles to feature groups (Sbx, SbxToggles, Equalizer,
+EqBands) or single features (e.g. SmartVolMode).
+
+If linuxblasterd or the GUI is running, commands go through it
+(D-Bus, or the socket in $XDG_RUNTIME_DIR) and --device is ignored.";
+
+/// Where commands are sent: the process owning the device,
+/// otherwise the device itself.
+enum Target {
+    Daemon(LinuxBlasterProxyBlocking<'static>),
+    Socket(Client),
+    Device(Box<BlasterXG6>),
+}
+
+impl Target {
+    fn open(selector: &DeviceSelector) -> Result<Self, Box<dyn Error>> {
+        if let Some(proxy) = dbus::connect() {
+            return Ok(Self::Daemon(proxy));
+        }
+        if let Some(client) = Client::connect() {
+            return Ok(Self::Socket(client));
+        }
+        Ok(Self::Device(Box::new(BlasterXG6::open_without_reset(
+            selector,
+        )?)))
+    }
+
+    fn get(&mut self, id: FeatureId) -> Result<f32, Box<dyn Error>> {
+        match self {
+            Self::Daemon(proxy) => {
+                Ok(proxy.get_feature(&format!("{:?}", id))? as f32)
+            }
+            Self::Socket(client) => client.get(id),
+            Self::Device(blaster) => Ok(blaster.feature_value(id)?),
+        }
+    }
+
+    fn set(
+        &mut self,
+        id: FeatureId,
+        value: Option<f32>,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = format!("{:?}", id);
+        match (self, value) {
+            (Self::Daemon(proxy), Some(value)) => {
+                Ok(proxy.set_feature(&name, value as f64)?)
+            }
+            (Self::Daemon(proxy), None) => Ok(proxy.toggle_feature(&name)?),
+            (Self::Socket(client), value) => client.set(id, value),
+            (Self::Device(blaster), value) => blaster.set_feature(id, value),
+        }
+    }
+
+    fn switch_output(
+        &mut self,
+        output: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Daemon(proxy) => {
+                Ok(proxy.switch_output(output.unwrap_or_default())?)
+            }
+            Self::Socket(client) => client.switch_output(output),
+            Self::Device(blaster) => blaster.switch_output(output),
+        }
+    }
+
+    fn apply(&mut self, profile: &Profile) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Daemon(proxy) => {
+                Ok(proxy.apply_profile_json(&profile.to_json()?)?)
+            }
+            Self::Socket(client) => client.apply(profile),
+            Self::Device(blaster) => blaster.apply(profile),
+        }
+    }
+
+    fn diff(&mut self, profile: &Profile) -> Result<String, Box<dyn Error>> {
+        let lines = match self {
+            Self::Daemon(proxy) => {
+                proxy.diff_profile_json(&profile.to_json()?)?
+            }
+            Self::Socket(client) => client.diff(profile)?,
+            Self::Device(blaster) => {
+                return Ok(blaster.diff(profile).to_string());
+            }
+        };
+        if lines.is_empty() {
+            return Ok(ProfileDiff::default().to_string());
+        }
+        Ok(lines.iter().map(|line| format!("{}\n", line)).collect())
+    }
+
+    /// Blocks, printing every change until the connection is lost.
+    fn watch(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Daemon(proxy) => {
+                for signal in proxy.receive_feature_changed()? {
+                    let args = signal.args()?;
+                    if *args.source() == "Refresh" {
+                        continue;
+                    }
+                    let id: FeatureId = args.feature().parse()?;
+                    println!(
+                        "{}: {} ({})",
+                        id.display_name(),
+                        id.value_kind().format(*args.value() as f32),
+                        args.source()
+                    );
+                }
+                Ok(())
+            }
+            Self::Socket(client) => client.watch(print_change),
+            Self::Device(blaster) => {
+                let changes = blaster.subscribe();
+                loop {
+                    blaster.poll_notifications();
+                    changes.try_iter().for_each(print_change);
+                    std::thread::sleep(WATCH_INTERVAL);
+                }
+            }
+        }
+    }
+}
+
+fn print_change(change: FeatureChange) {
+    if change.source == ChangeSource::Refresh {
+        return;
+    }
+    println!(
+        "{}: {} ({:?})",
+        change.id.display_name(),
+        change.id.value_kind().format(change.new),
+        change.source
+    );
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_max_level(Level::WARN)
+        .with_writer(std::io::stderr)
+        .init();
+
+    let mut selector = DeviceSelector::First;
+    let mut scope: Option<Vec<ProfileScope>> = None;
+    let mut command = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--device" => {
+                let Some(value) = args.next() else {
+                    eprintln!("--device requires a serial number or HID path");
+                    return ExitCode::from(2);
+                };
+                selector = value.parse().unwrap();
+            }
+            "--scope" => {
+                let parsed = args.next().map(|value| {
+                    value
+                        .split(',')
+                        .map(|entry| entry.trim().parse())
+                        .collect::<Result<Vec<_>, _>>()
+                });
+                match parsed {
+                    Some(Ok(entries)) => scope = Some(entries),
+                    Some(Err(error)) => {
+                        eprintln!("{}", error);
+                        return ExitCode::from(2);
+                    }
+                    None => {
+                        eprintln!(
+                            "--scope requires a list of groups or features"
+                        );
+                        return ExitCode::from(2);
+                    }
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => command.push(arg),
+        }
+    }
+
+    let command: Vec<&str> = command.iter().map(String::as_str).collect();
+    let load = |name: &str| {
+        ProfileLibrary::default()
+            .resolve(name)
+            .map(|profile| match &scope {
+                Some(scope) => profile.restrict(scope),
+                None => profile,
+            })
+    };
+    let target = || Target::open(&selector);
+
+    let result = match command.as_slice() {
+        ["get", feature] => feature.parse().and_then(|id| {
+            println!("{}", target()?.get(id)?);
+            Ok(())
+        }),
+        ["set", feature, value] => feature.parse().and_then(|id| {
+            let value: f32 = value
+                .parse()
+                .map_err(|_| format!("Invalid value: {}", value))?;
+            target()?.set(id, Some(value))
+        }),
+        ["toggle", feature] => {
+            feature.parse().and_then(|id| target()?.set(id, None))
+        }
+        ["output"] => {
+            target().and_then(|mut target| target.switch_output(None))
+        }
+        ["output", output] => {
+            target().and_then(|mut target| target.switch_output(Some(output)))
+        }
+        ["watch"] => target().and_then(Target::watch),
+        ["rules"] => {
+            let rules = RuleSet::load();
+            let context = rules.detect_context();
+            let matched = rules.matching(&context).map(|rule| &rule.name);
+            for rule in &rules.rules {
+                let marker = if Some(&rule.name) == matched {
+                    "*"
+                } else {
+                    " "
+                };
+                println!(
+                    "{} {}: {} -> {}",
+                    marker, rule.name, rule.trigger, rule.profile
+                );
+            }
+            Ok(())
+        }
+        ["schedule"] => {
+            let schedule = Schedule::load();
+            let current = schedule
+                .current(chrono::Local::now().naive_local())
+                .map(|(entry, _)| &entry.name);
+            for entry in &schedule.entries {
+                let marker = if Some(&entry.name) == current {
+                    "*"
+                } else {
+                    " "
+                };
+                println!(
+                    "{} {}: {} -> {}",
+                    marker, entry.name, entry, entry.profile
+                );
+            }
+            Ok(())
+        }
+        ["profile", "apply", layers @ ..] if !layers.is_empty() => layers
+            .iter()
+            .map(|layer| load(layer))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|layers| {
+                let profile = Profile::stack(&layers).ok_or("No profiles")?;
+                target()?.apply(&profile)
+            }),
+        ["profile", "diff", profile] => load(profile).and_then(|profile| {
+            print!("{}", target()?.diff(&profile)?);
+            Ok(())
+        }),
+        ["profile", "diff", from, to] => load(from).and_then(|from| {
+            // no device involved, take the ranges of the model it was saved from
+            let model = DeviceModel::ALL
+                .iter()
+                .find(|model| {
+                    from.metadata.model.as_deref() == Some(model.name)
+                })
+                .unwrap_or(&DeviceModel::G6);
+            print!("{}", ProfileDiff::between(model, &from, &load(to)?));
+            Ok(())
+        }),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
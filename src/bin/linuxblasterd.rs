@@ -0,0 +1,81 @@
+//! Background service that owns the G6 and serves it on the session bus
+//! and on a Unix socket (see `socket`), so the GUI, `blasterctl`,
+//! widgets and scripts can share it.
+//!
+//! ```text
+//! linuxblasterd [--device <serial|path>]
+//! ```
+
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+
+use linuxblaster_control::{
+    BlasterXG6, Config, DeviceSelector, dbus, services, socket,
+};
+use tracing::{error, info, warn};
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt().init();
+
+    let mut selector = DeviceSelector::First;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--device" => {
+                let Some(value) = args.next() else {
+                    eprintln!("--device requires a serial number or HID path");
+                    return ExitCode::from(2);
+                };
+                selector = value.parse().unwrap();
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                eprintln!("Usage: linuxblasterd [--device <serial|path>]");
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    if dbus::connect().is_some() || socket::Client::connect().is_some() {
+        error!("linuxblasterd is already running");
+        return ExitCode::FAILURE;
+    }
+
+    let blaster = match BlasterXG6::open(&selector) {
+        Ok(blaster) => blaster,
+        Err(error) => {
+            error!("Failed to open device: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    blaster.restore_if_configured();
+    let blaster = Arc::new(Mutex::new(blaster));
+
+    // Either is enough, e.g. there is no session bus in containers.
+    let bus = dbus::serve(blaster.clone())
+        .inspect(|_| {
+            info!("Serving {} at {}", dbus::BUS_NAME, dbus::OBJECT_PATH)
+        })
+        .inspect_err(|error| warn!("Not serving on D-Bus: {}", error))
+        .ok();
+    let server = socket::Server::serve(blaster.clone())
+        .inspect_err(|error| warn!("Not serving on a socket: {}", error))
+        .ok();
+    if bus.is_none() && server.is_none() {
+        error!("Neither D-Bus nor the socket are available");
+        return ExitCode::FAILURE;
+    }
+
+    let config = Config::load();
+    services::start(blaster.clone(), &config);
+    services::run_loop(blaster, &config, |change| {
+        if let Some(bus) = &bus
+            && let Err(error) = dbus::emit_change(bus, change)
+        {
+            warn!("Failed to emit change of {}: {}", change.id, error);
+        }
+        if let Some(server) = &server {
+            server.broadcast(change);
+        }
+    })
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::DEFAULT_BASE_PATH;
+use crate::hotkeys::Hotkey;
+use crate::mqtt::MqttConfig;
+use crate::notify::{NotificationGroup, Verbosity};
+
+/// Application settings shared by the GUI and the headless mode,
+/// stored in `DEFAULT_BASE_PATH/config.json`.
+///
+/// Missing fields fall back to their defaults,
+/// so older config files keep working.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Re-apply the last known state whenever a device is opened,
+    /// e.g. after a reboot, a firmware reset or replugging it.
+    /// See `BlasterXG6::restore_last_state()`.
+    pub restore_last_state: bool,
+    /// Log the switch to Headphones when they are plugged in,
+    /// and back to Speakers when they are removed.
+    /// Log-only until the jack notification is verified.
+    pub auto_switch_output: bool,
+    /// Profile (library name or path) for `auto_switch_output`
+    /// to apply after switching, by Output label, e.g. "Headphones".
+    pub output_profiles: BTreeMap<String, String>,
+    /// Global hotkeys, handled by `linuxblasterd`.
+    pub hotkeys: Vec<Hotkey>,
+    /// Desktop notifications for state changes, see `Notifier`.
+    pub notifications: BTreeMap<NotificationGroup, Verbosity>,
+    /// Where `linuxblasterd` serves OpenMetrics, e.g. "127.0.0.1:9756",
+    /// see `metrics`. Off if unset.
+    pub metrics_address: Option<SocketAddr>,
+    /// The broker `linuxblasterd` bridges to, see `mqtt`. Off if unset.
+    pub mqtt: Option<MqttConfig>,
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        DEFAULT_BASE_PATH.join("config.json")
+    }
+
+    /// Loads the config from `path()`.
+    /// Falls back to the defaults if there is none or it can't be read.
+    pub fn load() -> Self {
+        match Self::load_from(&Self::path()) {
+            Ok(config) => config,
+            Err(error) => {
+                warn!("Failed to load config, using defaults: {}", error);
+                Self::default()
+            }
+        }
+    }
+
+    /// A missing file is not an error, it yields the defaults.
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn Error>> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            Err(error) => Err(Box::new(error)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        self.save_to(&Self::path())
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        info!("Saved config to {:?}", path);
+        Ok(())
+    }
+}
+
+/// Reads a settings file next to `config.json`, like `rules.json`.
+/// A missing file is not an error, it yields `T::default()`.
+pub(crate) fn load_json<T: DeserializeOwned + Default>(
+    path: &Path,
+) -> Result<T, Box<dyn Error>> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(T::default()),
+        Err(error) => Err(Box::new(error)),
+    }
+}
+
+/// `load_json()` for long-running callers: a file that can't be read
+/// is logged as the `what` that failed to load, and yields `T::default()`.
+pub(crate) fn load_json_or_default<T: DeserializeOwned + Default>(
+    path: &Path,
+    what: &str,
+) -> T {
+    load_json(path).unwrap_or_else(|error| {
+        warn!("Failed to load {}: {}", what, error);
+        T::default()
+    })
+}
+
+pub(crate) fn save_json<T: Serialize>(
+    value: &T,
+    path: &Path,
+    what: &str,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(value)?)?;
+    info!("Saved {} to {:?}", what, path);
+    Ok(())
+}
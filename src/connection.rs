@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use hidapi::{DeviceInfo, HidApi, HidDevice, HidError, HidResult};
+use serde::{Deserialize, Serialize};
+
+use crate::DeviceModel;
+use crate::metrics::LinkStats;
+
+/// How many unsolicited packets `Connection::defer()` keeps.
+const MAX_DEFERRED: usize = 64;
+
+/// Identifies one physical device, as found by `BlasterXG6::enumerate()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceDescriptor {
+    /// USB serial number, if the device reports one.
+    pub serial: Option<String>,
+    /// Platform specific HID path.
+    /// With the libusb backend this is `<bus>-<ports>:<config>.<interface>`,
+    /// which stays the same across USB resets as long as the device
+    /// stays plugged into the same port.
+    pub path: String,
+    pub product: Option<String>,
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+impl DeviceDescriptor {
+    pub(crate) fn from_info(info: &DeviceInfo) -> Self {
+        Self {
+            serial: info
+                .serial_number()
+                .filter(|serial| !serial.is_empty())
+                .map(str::to_string),
+            path: info.path().to_string_lossy().into_owned(),
+            product: info.product_string().map(str::to_string),
+            vendor_id: info.vendor_id(),
+            product_id: info.product_id(),
+        }
+    }
+
+    /// `None` if the ids don't belong to any known model.
+    pub fn model(&self) -> Option<&'static DeviceModel> {
+        DeviceModel::from_ids(self.vendor_id, self.product_id)
+    }
+
+    /// The USB port part of a libusb HID path (`1-2.3` of `1-2.3:1.4`).
+    /// `None` for other backends.
+    pub(crate) fn usb_port(&self) -> Option<&str> {
+        self.path
+            .split_once(':')
+            .map(|(port, _)| port)
+            .filter(|port| port.contains('-'))
+    }
+}
+
+impl fmt::Display for DeviceDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} [{}] ({})",
+            self.product
+                .as_deref()
+                .or(self.model().map(|model| model.name))
+                .unwrap_or("Unknown Device"),
+            self.serial.as_deref().unwrap_or("no serial"),
+            self.path
+        )
+    }
+}
+
+/// Which device `BlasterXG6::open()` should pick.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DeviceSelector {
+    /// The first matching device, like before multi-device support.
+    #[default]
+    First,
+    Serial(String),
+    Path(String),
+}
+
+impl DeviceSelector {
+    pub fn matches(&self, descriptor: &DeviceDescriptor) -> bool {
+        match self {
+            Self::First => true,
+            Self::Serial(serial) => {
+                descriptor.serial.as_deref() == Some(serial.as_str())
+            }
+            Self::Path(path) => descriptor.path == *path,
+        }
+    }
+
+    /// The most stable selector for re-finding `descriptor`,
+    /// e.g. after the USB reset re-enumerated it.
+    pub fn for_descriptor(descriptor: &DeviceDescriptor) -> Self {
+        match &descriptor.serial {
+            Some(serial) => Self::Serial(serial.clone()),
+            None => Self::Path(descriptor.path.clone()),
+        }
+    }
+}
+
+/// Parses the `--device` argument:
+/// HID paths contain a `:` or `/`, anything else is a serial number.
+impl FromStr for DeviceSelector {
+    type Err = std::convert::Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.contains(':') || value.contains('/') {
+            Ok(Self::Path(value.to_string()))
+        } else {
+            Ok(Self::Serial(value.to_string()))
+        }
+    }
+}
+
+/// An open HID connection to one device.
+///
+/// Every packet helper in `features` goes through this,
+/// so multiple devices can be driven from the same process.
+pub struct Connection {
+    device: Mutex<Option<HidDevice>>,
+    descriptor: Option<DeviceDescriptor>,
+    model: &'static DeviceModel,
+    stats: Arc<LinkStats>,
+    /// When the last request was sent, until its response arrives.
+    sent: Mutex<Option<Instant>>,
+    /// Whether the device answers batched 0x11 requests,
+    /// see `features::probe_batching()`.
+    batching: AtomicBool,
+    /// Packets the device sent on its own while a response was awaited,
+    /// left for `BlasterXG6::poll_notifications()`.
+    deferred: Mutex<VecDeque<[u8; 64]>>,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Self {
+            device: Mutex::new(None),
+            descriptor: None,
+            model: &DeviceModel::G6,
+            stats: Arc::default(),
+            sent: Mutex::new(None),
+            batching: AtomicBool::new(false),
+            deferred: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl Connection {
+    pub fn open(
+        api: &HidApi,
+        descriptor: &DeviceDescriptor,
+    ) -> HidResult<Self> {
+        let path = CString::new(descriptor.path.clone()).map_err(|_| {
+            HidError::HidApiError {
+                message: format!("Invalid device path: {}", descriptor.path),
+            }
+        })?;
+        let model =
+            descriptor.model().ok_or_else(|| HidError::HidApiError {
+                message: format!("Unknown device model: {}", descriptor),
+            })?;
+        let device = api.open_path(&path)?;
+
+        Ok(Self {
+            device: Mutex::new(Some(device)),
+            descriptor: Some(descriptor.clone()),
+            model,
+            stats: Arc::default(),
+            sent: Mutex::new(None),
+            batching: AtomicBool::new(false),
+            deferred: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// A connection to no device, for a `BlasterXG6` attached to the
+    /// daemon and for tests that must not write anything.
+    pub(crate) fn detached(model: &'static DeviceModel) -> Self {
+        Self {
+            model,
+            ..Self::default()
+        }
+    }
+
+    pub fn descriptor(&self) -> Option<&DeviceDescriptor> {
+        self.descriptor.as_ref()
+    }
+
+    /// The model on the other end, which decides the DSP address map.
+    pub fn model(&self) -> &'static DeviceModel {
+        self.model
+    }
+
+    pub fn stats(&self) -> &LinkStats {
+        &self.stats
+    }
+
+    /// Whether DSP features may be queried in batches.
+    pub(crate) fn batching(&self) -> bool {
+        self.batching.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_batching(&self, batching: bool) {
+        self.batching.store(batching, Ordering::Relaxed);
+    }
+
+    /// Keeps `packet` for the next `take_deferred()`.
+    /// The oldest packet is dropped once `MAX_DEFERRED` are waiting,
+    /// in case nobody polls for notifications.
+    pub(crate) fn defer(&self, packet: [u8; 64]) {
+        let mut deferred = self.deferred.lock().unwrap();
+        if deferred.len() >= MAX_DEFERRED {
+            deferred.pop_front();
+        }
+        deferred.push_back(packet);
+    }
+
+    pub(crate) fn take_deferred(&self) -> Option<[u8; 64]> {
+        self.deferred.lock().unwrap().pop_front()
+    }
+
+    /// Keeps counting into the stats of `previous`, e.g. after a reconnect.
+    pub(crate) fn share_stats(&mut self, previous: &Connection) {
+        self.stats = previous.stats.clone();
+    }
+
+    /// Records the round trip of the last request,
+    /// called once its response or ACK arrived.
+    pub(crate) fn record_round_trip(&self) {
+        if let Some(sent) = self.sent.lock().unwrap().take() {
+            self.stats.round_trip.observe(sent.elapsed());
+        }
+    }
+
+    pub(crate) fn record_ack_failure(&self) {
+        self.stats.ack_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn device(&self) -> MutexGuard<'_, Option<HidDevice>> {
+        self.device.lock().unwrap()
+    }
+
+    pub fn write(&self, payload: &[u8]) -> HidResult<usize> {
+        match self.device().as_ref() {
+            Some(device) => {
+                *self.sent.lock().unwrap() = Some(Instant::now());
+                device.write(payload)
+            }
+            None => Err(not_connected()),
+        }
+    }
+
+    pub fn read_timeout(
+        &self,
+        buffer: &mut [u8],
+        timeout_ms: i32,
+    ) -> HidResult<usize> {
+        match self.device().as_ref() {
+            Some(device) => device.read_timeout(buffer, timeout_ms),
+            None => Err(not_connected()),
+        }
+    }
+
+    pub fn set_blocking_mode(&self, blocking: bool) -> HidResult<()> {
+        match self.device().as_ref() {
+            Some(device) => device.set_blocking_mode(blocking),
+            None => Err(not_connected()),
+        }
+    }
+}
+
+fn not_connected() -> HidError {
+    HidError::HidApiError {
+        message: "Device connection must be initialized".to_string(),
+    }
+}
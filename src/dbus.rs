@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tracing::debug;
+use zbus::zvariant::Value;
+use zbus::{fdo, interface, proxy};
+
+use crate::{BlasterXG6, FeatureChange, FeatureId, Profile, ProfileLibrary};
+
+/// Well-known name `linuxblasterd` owns on the session bus.
+pub const BUS_NAME: &str = "io.github.rizecrime.LinuxBlaster";
+pub const OBJECT_PATH: &str = "/io/github/rizecrime/LinuxBlaster";
+/// Has to match the `#[interface]` and `#[proxy]` attributes below.
+pub const INTERFACE: &str = "io.github.rizecrime.LinuxBlaster";
+
+/// The device, shared between the D-Bus interface and the loop polling it.
+pub type SharedBlaster = Arc<Mutex<BlasterXG6>>;
+
+/// Exposes a `BlasterXG6` on D-Bus:
+/// - one `f64` property per `FeatureId` (e.g. `SurroundToggle`),
+///   with the same values as `Feature::value()`,
+/// - methods for generic access, Output switching and profiles,
+/// - a `FeatureChanged` signal and `PropertiesChanged`
+///   for every change, see `emit_change()`.
+pub struct BlasterInterface {
+    blaster: SharedBlaster,
+}
+
+impl BlasterInterface {
+    pub fn new(blaster: SharedBlaster) -> Self {
+        Self { blaster }
+    }
+
+    fn blaster(&self) -> MutexGuard<'_, BlasterXG6> {
+        self.blaster.lock().unwrap()
+    }
+
+    fn get(&self, id: FeatureId) -> fdo::Result<f64> {
+        let blaster = self.blaster();
+        if !blaster.supports(id) {
+            return Err(not_supported(id, &blaster));
+        }
+        Ok(blaster.feature(id).value() as f64)
+    }
+
+    fn set(&self, id: FeatureId, value: Option<f64>) -> fdo::Result<()> {
+        let blaster = self.blaster();
+        if !blaster.supports(id) {
+            return Err(not_supported(id, &blaster));
+        }
+        check_connected(&blaster)?;
+        blaster
+            .set_feature(id, value.map(|value| value as f32))
+            .map_err(failed)
+    }
+}
+
+/// Writes the interface with one property per `FeatureId`.
+///
+/// `#[interface]` only sees the expanded impl,
+/// so the properties can't be generated by a macro inside of it.
+macro_rules! blaster_interface {
+    ($($getter:ident / $setter:ident => $id:ident,)*) => {
+        #[interface(name = "io.github.rizecrime.LinuxBlaster")]
+        impl BlasterInterface {
+            /// Value of a feature by its name, e.g. "SurroundToggle".
+            fn get_feature(&self, feature: &str) -> fdo::Result<f64> {
+                self.get(parse_feature(feature)?)
+            }
+
+            fn set_feature(
+                &self,
+                feature: &str,
+                value: f64,
+            ) -> fdo::Result<()> {
+                self.set(parse_feature(feature)?, Some(value))
+            }
+
+            /// Flips a toggle between 0 and 1.
+            fn toggle_feature(&self, feature: &str) -> fdo::Result<()> {
+                self.set(parse_feature(feature)?, None)
+            }
+
+            /// Resets every feature this software touches,
+            /// see `BlasterXG6::reset()`.
+            fn reset(&self) -> fdo::Result<()> {
+                let blaster = self.blaster();
+                check_connected(&blaster)?;
+                blaster.reset().map_err(failed)
+            }
+
+            /// Switches to the Output labelled `output` ("Speakers",
+            /// "Headphones"), or to the next one if `output` is empty.
+            fn switch_output(&self, output: &str) -> fdo::Result<()> {
+                let output = (!output.is_empty()).then_some(output);
+                let blaster = self.blaster();
+                check_connected(&blaster)?;
+                blaster.switch_output(output).map_err(failed)
+            }
+
+            /// Applies a profile from the library, or from a file path.
+            fn apply_profile(&self, profile: &str) -> fdo::Result<()> {
+                let profile = ProfileLibrary::default()
+                    .resolve(profile)
+                    .map_err(failed)?;
+                let blaster = self.blaster();
+                check_connected(&blaster)?;
+                blaster.apply(&profile).map_err(failed)
+            }
+
+            /// Applies a serialized `Profile`,
+            /// e.g. stacked layers from `blasterctl`.
+            fn apply_profile_json(&self, json: &str) -> fdo::Result<()> {
+                let profile = Profile::from_json(json).map_err(invalid)?;
+                let blaster = self.blaster();
+                check_connected(&blaster)?;
+                blaster.apply(&profile).map_err(failed)
+            }
+
+            /// What applying a serialized `Profile` would change,
+            /// one line per feature.
+            fn diff_profile_json(
+                &self,
+                json: &str,
+            ) -> fdo::Result<Vec<String>> {
+                let profile = Profile::from_json(json).map_err(invalid)?;
+                let diff = self.blaster().diff(&profile);
+                Ok(diff.changes.iter().map(ToString::to_string).collect())
+            }
+
+            /// Saves the current state to the library.
+            fn save_profile(&self, name: &str) -> fdo::Result<()> {
+                let profile = self.blaster().to_profile(name);
+                ProfileLibrary::default().save(&profile).map_err(failed)?;
+                Ok(())
+            }
+
+            /// Names of every profile in the library, favourites first.
+            fn list_profiles(&self) -> fdo::Result<Vec<String>> {
+                let entries =
+                    ProfileLibrary::default().list().map_err(failed)?;
+                Ok(entries
+                    .iter()
+                    .map(|entry| entry.name().to_string())
+                    .collect())
+            }
+
+            /// The result of the last connection check,
+            /// see `BlasterXG6::ensure_connected()`.
+            #[zbus(property)]
+            fn connected(&self) -> bool {
+                self.blaster().link_stats().connected.load(Ordering::Relaxed)
+            }
+
+            #[zbus(property)]
+            fn model(&self) -> String {
+                self.blaster().model().name.to_string()
+            }
+
+            /// See `ChangeSource` for the possible sources.
+            #[zbus(signal)]
+            async fn feature_changed(
+                emitter: &zbus::object_server::SignalEmitter<'_>,
+                feature: &str,
+                value: f64,
+                source: &str,
+            ) -> zbus::Result<()>;
+
+            $(
+                #[zbus(property)]
+                fn $getter(&self) -> fdo::Result<f64> {
+                    self.get(FeatureId::$id)
+                }
+
+                #[zbus(property)]
+                fn $setter(&self, value: f64) -> zbus::Result<()> {
+                    Ok(self.set(FeatureId::$id, Some(value))?)
+                }
+            )*
+        }
+    };
+}
+
+blaster_interface! {
+    sbx_master / set_sbx_master => SbxMaster,
+    scout_mode / set_scout_mode => ScoutMode,
+    output / set_output => Output,
+    surround_toggle / set_surround_toggle => SurroundToggle,
+    surround_level / set_surround_level => SurroundLevel,
+    dialog_plus_toggle / set_dialog_plus_toggle => DialogPlusToggle,
+    dialog_plus_level / set_dialog_plus_level => DialogPlusLevel,
+    smart_vol_toggle / set_smart_vol_toggle => SmartVolToggle,
+    smart_vol_level / set_smart_vol_level => SmartVolLevel,
+    smart_vol_mode / set_smart_vol_mode => SmartVolMode,
+    crystalizer_toggle / set_crystalizer_toggle => CrystalizerToggle,
+    crystalizer_level / set_crystalizer_level => CrystalizerLevel,
+    bass_toggle / set_bass_toggle => BassToggle,
+    bass_level / set_bass_level => BassLevel,
+    surround_distance / set_surround_distance => SurroundDistance,
+    eq_toggle / set_eq_toggle => EqToggle,
+    eq_pre_amp / set_eq_pre_amp => EqPreAmp,
+    eq31_hz / set_eq31_hz => Eq31Hz,
+    eq62_hz / set_eq62_hz => Eq62Hz,
+    eq125_hz / set_eq125_hz => Eq125Hz,
+    eq250_hz / set_eq250_hz => Eq250Hz,
+    eq500_hz / set_eq500_hz => Eq500Hz,
+    eq1k_hz / set_eq1k_hz => Eq1kHz,
+    eq2k_hz / set_eq2k_hz => Eq2kHz,
+    eq4k_hz / set_eq4k_hz => Eq4kHz,
+    eq8k_hz / set_eq8k_hz => Eq8kHz,
+    eq16k_hz / set_eq16k_hz => Eq16kHz,
+}
+
+/// Client side of `BlasterInterface`, for the GUI, `blasterctl` and scripts.
+/// The per-feature properties are left out, use `get_feature()`.
+#[proxy(
+    interface = "io.github.rizecrime.LinuxBlaster",
+    default_service = "io.github.rizecrime.LinuxBlaster",
+    default_path = "/io/github/rizecrime/LinuxBlaster"
+)]
+pub trait LinuxBlaster {
+    fn get_feature(&self, feature: &str) -> zbus::Result<f64>;
+    fn set_feature(&self, feature: &str, value: f64) -> zbus::Result<()>;
+    fn toggle_feature(&self, feature: &str) -> zbus::Result<()>;
+    fn switch_output(&self, output: &str) -> zbus::Result<()>;
+    fn apply_profile(&self, profile: &str) -> zbus::Result<()>;
+    fn apply_profile_json(&self, json: &str) -> zbus::Result<()>;
+    fn diff_profile_json(&self, json: &str) -> zbus::Result<Vec<String>>;
+    fn save_profile(&self, name: &str) -> zbus::Result<()>;
+    fn list_profiles(&self) -> zbus::Result<Vec<String>>;
+
+    #[zbus(property)]
+    fn connected(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn model(&self) -> zbus::Result<String>;
+
+    #[zbus(signal)]
+    fn feature_changed(
+        &self,
+        feature: &str,
+        value: f64,
+        source: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// Claims `BUS_NAME` on the session bus and serves `blaster` at `OBJECT_PATH`.
+pub fn serve(
+    blaster: SharedBlaster,
+) -> Result<zbus::blocking::Connection, Box<dyn Error>> {
+    let connection = zbus::blocking::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, BlasterInterface::new(blaster))?
+        .build()?;
+    Ok(connection)
+}
+
+/// Connects to a running `linuxblasterd`, if there is one.
+pub fn connect() -> Option<LinuxBlasterProxyBlocking<'static>> {
+    let connection = zbus::blocking::Connection::session().ok()?;
+    let dbus = zbus::blocking::fdo::DBusProxy::new(&connection).ok()?;
+    let name = BUS_NAME.try_into().ok()?;
+    if !dbus.name_has_owner(name).ok()? {
+        return None;
+    }
+    LinuxBlasterProxyBlocking::new(&connection).ok()
+}
+
+/// Announces a change: `FeatureChanged`,
+/// and `PropertiesChanged` for the feature's property.
+pub fn emit_change(
+    connection: &zbus::blocking::Connection,
+    change: &FeatureChange,
+) -> zbus::Result<()> {
+    let feature = format!("{:?}", change.id);
+    let source = format!("{:?}", change.source);
+    let value = change.new as f64;
+    debug!("D-Bus: {} = {} ({})", feature, value, source);
+
+    connection.emit_signal(
+        None::<&str>,
+        OBJECT_PATH,
+        INTERFACE,
+        "FeatureChanged",
+        &(feature.as_str(), value, source.as_str()),
+    )?;
+
+    let changed = HashMap::from([(feature.as_str(), Value::from(value))]);
+    connection.emit_signal(
+        None::<&str>,
+        OBJECT_PATH,
+        "org.freedesktop.DBus.Properties",
+        "PropertiesChanged",
+        &(INTERFACE, changed, Vec::<&str>::new()),
+    )
+}
+
+fn parse_feature(feature: &str) -> fdo::Result<FeatureId> {
+    feature.parse().map_err(invalid)
+}
+
+fn not_supported(id: FeatureId, blaster: &BlasterXG6) -> fdo::Error {
+    fdo::Error::NotSupported(format!(
+        "{} is not supported by the {}",
+        id,
+        blaster.model()
+    ))
+}
+
+/// Fails while the last connection check did,
+/// so callers get an error instead of a write that can't arrive.
+fn check_connected(blaster: &BlasterXG6) -> fdo::Result<()> {
+    if blaster.link_stats().connected.load(Ordering::Relaxed) {
+        Ok(())
+    } else {
+        Err(failed(format!("The {} is disconnected", blaster.model())))
+    }
+}
+
+fn failed(error: impl Display) -> fdo::Error {
+    fdo::Error::Failed(error.to_string())
+}
+
+fn invalid(error: impl Display) -> fdo::Error {
+    fdo::Error::InvalidArgs(error.to_string())
+}
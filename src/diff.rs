@@ -0,0 +1,131 @@
+use std::fmt;
+
+use crate::profile::Profile;
+use crate::{DeviceModel, FeatureId, ValueKind};
+
+/// One feature whose value differs between two states.
+///
+/// `None` means the state has no value for the feature,
+/// e.g. it wasn't saved in the profile, couldn't be read,
+/// or belongs to an Output that isn't active. It is rendered as "?".
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureDiff {
+    /// The Output of the bank the feature belongs to.
+    pub output: Option<String>,
+    pub id: FeatureId,
+    pub from: Option<f32>,
+    pub to: Option<f32>,
+    /// The model's `ValueKind` of the feature, used to render the values.
+    pub kind: ValueKind,
+}
+
+impl FeatureDiff {
+    /// `from` rendered according to the feature's `ValueKind`.
+    pub fn from_label(&self) -> String {
+        self.render(self.from)
+    }
+
+    /// `to` rendered according to the feature's `ValueKind`.
+    pub fn to_label(&self) -> String {
+        self.render(self.to)
+    }
+
+    fn render(&self, value: Option<f32>) -> String {
+        value
+            .map(|value| self.kind.format(value))
+            .unwrap_or_else(|| "?".to_string())
+    }
+}
+
+impl fmt::Display for FeatureDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(output) = &self.output {
+            write!(f, "[{}] ", output)?;
+        }
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.id.display_name(),
+            self.from_label(),
+            self.to_label()
+        )
+    }
+}
+
+/// The feature by feature difference between two profiles,
+/// see `ProfileDiff::between()` and `BlasterXG6::diff()`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileDiff {
+    /// Grouped by Output, in `FeatureId::ALL` order within each Output.
+    pub changes: Vec<FeatureDiff>,
+}
+
+impl ProfileDiff {
+    /// Compares `from` with `to`, bank by bank,
+    /// using the value ranges of `model`.
+    ///
+    /// Banks are paired by their Output. A bank without an Output
+    /// stands in for every Output the other profile has a bank for.
+    /// Only features in the scope of both profiles are compared.
+    /// Values are equal if they match within `ValueKind::tolerance()`.
+    pub fn between(model: &DeviceModel, from: &Profile, to: &Profile) -> Self {
+        let mut outputs: Vec<Option<&str>> = Vec::new();
+        for bank in from.banks.iter().chain(&to.banks) {
+            let output = bank.output.as_deref();
+            if output.is_some() && !outputs.contains(&output) {
+                outputs.push(output);
+            }
+        }
+        if outputs.is_empty() {
+            outputs.push(None);
+        }
+
+        let mut changes = Vec::new();
+        for output in outputs {
+            let from_bank = from.bank_for(output);
+            let to_bank = to.bank_for(output);
+
+            for &id in FeatureId::ALL {
+                if !from.in_scope(id) || !to.in_scope(id) {
+                    continue;
+                }
+                let kind = model.value_kind(id);
+                let from_value = from_bank.and_then(|bank| bank.value(id));
+                let to_value = to_bank.and_then(|bank| bank.value(id));
+
+                let equal = match (from_value, to_value) {
+                    (Some(a), Some(b)) => kind.matches(a, b),
+                    (None, None) => true,
+                    _ => false,
+                };
+                if !equal {
+                    changes.push(FeatureDiff {
+                        output: output.map(str::to_string),
+                        id,
+                        from: from_value,
+                        to: to_value,
+                        kind,
+                    });
+                }
+            }
+        }
+
+        Self { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for ProfileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
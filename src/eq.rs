@@ -0,0 +1,470 @@
+//! Conversion of the 10-band EQ to and from software equalizers:
+//! Equalizer APO, EasyEffects and PipeWire filter-chain.
+//!
+//! Every band is exported as a peaking filter at its ISO center frequency.
+//! On import, peaking filters are assigned to the nearest band;
+//! filters that don't fit any band are reported back.
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use serde_json::{Map, Value, json};
+
+use crate::{BlasterXG6, FeatureId, ValueKind};
+
+/// Center frequencies of `FeatureId::EQ_BANDS`.
+pub const BAND_FREQUENCIES: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Q of one octave, which is what the bands of the G6 are spaced at.
+pub const BAND_Q: f32 = 1.41;
+
+/// Filters further away from a band than this (in octaves) are not imported.
+const MAX_BAND_DISTANCE: f32 = 0.5;
+
+/// The state of the hardware EQ: pre-amp + 10 bands, all in dB.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GraphicEq {
+    pub preamp: f32,
+    pub bands: [f32; 10],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EqFormat {
+    /// `config.txt`
+    EqualizerApo,
+    /// Output preset `.json`
+    EasyEffects,
+    /// filter-chain module `.conf`
+    PipeWire,
+}
+
+impl EqFormat {
+    pub const ALL: &[EqFormat] =
+        &[Self::EqualizerApo, Self::EasyEffects, Self::PipeWire];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::EqualizerApo => "txt",
+            Self::EasyEffects => "json",
+            Self::PipeWire => "conf",
+        }
+    }
+}
+
+impl fmt::Display for EqFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EqualizerApo => write!(f, "Equalizer APO"),
+            Self::EasyEffects => write!(f, "EasyEffects"),
+            Self::PipeWire => write!(f, "PipeWire"),
+        }
+    }
+}
+
+/// The result of importing an EQ file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EqImport {
+    pub eq: GraphicEq,
+    /// Filters that couldn't be mapped onto a band.
+    pub skipped: Vec<String>,
+}
+
+impl GraphicEq {
+    /// The cached EQ values of `blaster`.
+    pub fn from_device(blaster: &BlasterXG6) -> Self {
+        Self {
+            preamp: blaster.feature(FeatureId::EqPreAmp).value(),
+            bands: std::array::from_fn(|index| {
+                blaster.feature(FeatureId::EQ_BANDS[index]).value()
+            }),
+        }
+    }
+
+    /// `(FeatureId, value)` pairs for a `Transaction`,
+    /// clamped to what the hardware accepts.
+    pub fn writes(&self) -> Vec<(FeatureId, f32)> {
+        std::iter::once((FeatureId::EqPreAmp, self.preamp))
+            .chain(FeatureId::EQ_BANDS.iter().copied().zip(self.bands))
+            .map(|(id, value)| match id.value_kind() {
+                ValueKind::Ranged { min, max, .. } => {
+                    (id, value.clamp(min, max))
+                }
+                _ => (id, value),
+            })
+            .collect()
+    }
+
+    pub fn export(
+        &self,
+        format: EqFormat,
+        path: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        let content = match format {
+            EqFormat::EqualizerApo => self.to_apo(),
+            EqFormat::EasyEffects => self.to_easyeffects(),
+            EqFormat::PipeWire => self.to_pipewire(),
+        };
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Imports any of the `EqFormat`s, detected from the content.
+    pub fn import(path: &Path) -> Result<EqImport, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let trimmed = content.trim_start();
+
+        if trimmed.starts_with('{') {
+            Self::from_easyeffects(&content)
+        } else if content.contains("filter-chain") || content.contains("bq_") {
+            Self::from_pipewire(&content)
+        } else {
+            Self::from_apo(&content)
+        }
+    }
+
+    // ─── Equalizer APO ──────────────────────────────────────────────────────
+
+    pub fn to_apo(&self) -> String {
+        let mut config = format!("Preamp: {:.1} dB\n", self.preamp);
+        for (index, (frequency, gain)) in
+            BAND_FREQUENCIES.iter().zip(self.bands).enumerate()
+        {
+            config.push_str(&format!(
+                "Filter {}: ON PK Fc {} Hz Gain {:.1} dB Q {:.2}\n",
+                index + 1,
+                frequency,
+                gain,
+                BAND_Q
+            ));
+        }
+        config
+    }
+
+    /// Reads `Preamp:`, `Filter N: ON PK ...` and `GraphicEQ:` lines.
+    /// AutoEq's `GraphicEQ.txt` works as well.
+    pub fn from_apo(config: &str) -> Result<EqImport, Box<dyn Error>> {
+        let mut builder = Builder::default();
+
+        for line in config.lines().map(str::trim) {
+            let Some((command, arguments)) = line.split_once(':') else {
+                continue;
+            };
+            let tokens: Vec<&str> = arguments.split_whitespace().collect();
+
+            match command.trim() {
+                "Preamp" => {
+                    builder.preamp += tokens
+                        .first()
+                        .and_then(|gain| gain.parse::<f32>().ok())
+                        .ok_or_else(|| format!("Invalid line: {}", line))?;
+                }
+                command if command.starts_with("Filter") => {
+                    let value_after = |key: &str| {
+                        tokens
+                            .iter()
+                            .position(|&token| token == key)
+                            .and_then(|index| tokens.get(index + 1))
+                            .and_then(|value| value.parse::<f32>().ok())
+                    };
+                    let is_peaking = tokens.first() == Some(&"ON")
+                        && tokens.get(1).is_some_and(|filter| {
+                            matches!(*filter, "PK" | "PEQ" | "Modal")
+                        });
+
+                    match (is_peaking, value_after("Fc"), value_after("Gain")) {
+                        (true, Some(frequency), Some(gain)) => {
+                            builder.add(frequency, gain, line)
+                        }
+                        _ if tokens.first() == Some(&"OFF") => {}
+                        _ => builder.skipped.push(line.to_string()),
+                    }
+                }
+                "GraphicEQ" => {
+                    for point in arguments.split(';') {
+                        let mut values = point
+                            .split_whitespace()
+                            .filter_map(|value| value.parse::<f32>().ok());
+                        if let (Some(frequency), Some(gain)) =
+                            (values.next(), values.next())
+                        {
+                            builder.set(frequency, gain);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(builder.finish())
+    }
+
+    // ─── EasyEffects ────────────────────────────────────────────────────────
+
+    pub fn to_easyeffects(&self) -> String {
+        let bands: Map<String, Value> = BAND_FREQUENCIES
+            .iter()
+            .zip(self.bands)
+            .enumerate()
+            .map(|(index, (frequency, gain))| {
+                (
+                    format!("band{}", index),
+                    json!({
+                        "frequency": frequency,
+                        "gain": gain,
+                        "mode": "RLC (BT)",
+                        "mute": false,
+                        "q": BAND_Q,
+                        "slope": "x1",
+                        "solo": false,
+                        "type": "Bell",
+                    }),
+                )
+            })
+            .collect();
+
+        let preset = json!({
+            "output": {
+                "blocklist": [],
+                "equalizer#0": {
+                    "balance": 0.0,
+                    "bypass": false,
+                    "input-gain": self.preamp,
+                    "output-gain": 0.0,
+                    "left": bands,
+                    "right": bands,
+                    "mode": "IIR",
+                    "num-bands": self.bands.len(),
+                    "split-channels": false,
+                },
+                "plugins_order": ["equalizer#0"],
+            }
+        });
+        serde_json::to_string_pretty(&preset).expect("EQ preset is valid JSON")
+    }
+
+    /// Reads the first equalizer of an EasyEffects output preset
+    /// (left channel if the channels are split).
+    pub fn from_easyeffects(json: &str) -> Result<EqImport, Box<dyn Error>> {
+        let preset: Value = serde_json::from_str(json)?;
+        let plugins = preset
+            .get("output")
+            .and_then(Value::as_object)
+            .ok_or("Not an EasyEffects output preset")?;
+        let equalizer = plugins
+            .iter()
+            .find(|(name, _)| name.starts_with("equalizer"))
+            .map(|(_, equalizer)| equalizer)
+            .ok_or("Preset has no equalizer")?;
+
+        let mut builder = Builder::default();
+        builder.preamp += equalizer
+            .get("input-gain")
+            .and_then(Value::as_f64)
+            .unwrap_or_default() as f32;
+        builder.preamp += equalizer
+            .get("output-gain")
+            .and_then(Value::as_f64)
+            .unwrap_or_default() as f32;
+
+        let bands = equalizer
+            .get("left")
+            .and_then(Value::as_object)
+            .ok_or("Equalizer has no bands")?;
+        for (name, band) in bands {
+            let field = |key: &str| band.get(key).and_then(Value::as_f64);
+            let is_bell =
+                band.get("type").and_then(Value::as_str) == Some("Bell");
+            let muted = band.get("mute").and_then(Value::as_bool) == Some(true);
+
+            match (is_bell, field("frequency"), field("gain")) {
+                _ if muted => {}
+                (true, Some(frequency), Some(gain)) => {
+                    builder.add(frequency as f32, gain as f32, name)
+                }
+                _ => builder.skipped.push(format!("{}: {}", name, band)),
+            }
+        }
+
+        Ok(builder.finish())
+    }
+
+    // ─── PipeWire filter-chain ──────────────────────────────────────────────
+
+    /// A filter-chain sink, to be placed in `~/.config/pipewire/pipewire.conf.d/`.
+    /// The pre-amp is a high shelf at 0 Hz, i.e. a plain gain stage.
+    pub fn to_pipewire(&self) -> String {
+        let mut nodes = vec![format!(
+            "{{ type = builtin name = preamp label = bq_highshelf control = {{ \"Freq\" = 0.0 \"Q\" = 1.0 \"Gain\" = {:.1} }} }}",
+            self.preamp
+        )];
+        let mut links = Vec::new();
+        let mut previous = "preamp".to_string();
+        for (index, (frequency, gain)) in
+            BAND_FREQUENCIES.iter().zip(self.bands).enumerate()
+        {
+            let name = format!("band{}", index + 1);
+            nodes.push(format!(
+                "{{ type = builtin name = {} label = bq_peaking control = {{ \"Freq\" = {:.1} \"Q\" = {:.2} \"Gain\" = {:.1} }} }}",
+                name, frequency, BAND_Q, gain
+            ));
+            links.push(format!(
+                "{{ output = \"{}:Out\" input = \"{}:In\" }}",
+                previous, name
+            ));
+            previous = name;
+        }
+
+        let indent = |lines: Vec<String>| {
+            lines
+                .iter()
+                .map(|line| format!("                    {}", line))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        format!(
+            r#"context.modules = [
+    {{ name = libpipewire-module-filter-chain
+        args = {{
+            node.description = "G6 Equalizer"
+            media.name       = "G6 Equalizer"
+            filter.graph = {{
+                nodes = [
+{}
+                ]
+                links = [
+{}
+                ]
+            }}
+            audio.channels = 2
+            audio.position = [ FL FR ]
+            capture.props = {{
+                node.name   = "effect_input.g6_eq"
+                media.class = Audio/Sink
+            }}
+            playback.props = {{
+                node.name    = "effect_output.g6_eq"
+                node.passive = true
+            }}
+        }}
+    }}
+]
+"#,
+            indent(nodes),
+            indent(links)
+        )
+    }
+
+    /// Reads the `bq_peaking` nodes of a filter-chain config.
+    /// `bq_highshelf` nodes at 0 Hz count as pre-amp.
+    pub fn from_pipewire(config: &str) -> Result<EqImport, Box<dyn Error>> {
+        let mut builder = Builder::default();
+
+        for node in config.split("label").skip(1) {
+            let node = node.trim_start_matches([' ', '=']);
+            let label = node.split_whitespace().next().unwrap_or_default();
+            let Some(control) = node
+                .split_once("control")
+                .and_then(|(_, control)| control.split_once('{'))
+                .and_then(|(_, control)| control.split_once('}'))
+                .map(|(control, _)| control)
+            else {
+                continue;
+            };
+
+            // "Freq" = 31.0 "Q" = 1.41 "Gain" = 4.0
+            let tokens: Vec<&str> = control
+                .split(|char: char| char.is_whitespace() || char == '=')
+                .filter(|token| !token.is_empty())
+                .collect();
+            let value = |key: &str| {
+                tokens
+                    .iter()
+                    .position(|token| token.trim_matches('"') == key)
+                    .and_then(|index| tokens.get(index + 1))
+                    .and_then(|value| value.parse::<f32>().ok())
+            };
+
+            match (label, value("Freq"), value("Gain")) {
+                ("bq_highshelf", Some(0.0), Some(gain)) => {
+                    builder.preamp += gain
+                }
+                ("bq_peaking", Some(frequency), Some(gain)) => {
+                    builder.add(frequency, gain, label)
+                }
+                _ => builder.skipped.push(format!(
+                    "{} {{{}}}",
+                    label,
+                    control.trim()
+                )),
+            }
+        }
+
+        Ok(builder.finish())
+    }
+}
+
+/// Collects filters into bands.
+#[derive(Default)]
+struct Builder {
+    preamp: f32,
+    bands: [f32; 10],
+    /// The GraphicEQ point closest to each band, as `(distance, gain)`.
+    points: [Option<(f32, f32)>; 10],
+    skipped: Vec<String>,
+}
+
+impl Builder {
+    /// Adds a peaking filter to the nearest band.
+    fn add(&mut self, frequency: f32, gain: f32, source: &str) {
+        match nearest_band(frequency) {
+            Some(index) => self.bands[index] += gain,
+            None => self.skipped.push(source.to_string()),
+        }
+    }
+
+    /// Adds a point of a GraphicEQ curve.
+    /// Each band takes the gain of the point closest to it.
+    fn set(&mut self, frequency: f32, gain: f32) {
+        let Some(index) = nearest_band(frequency) else {
+            return;
+        };
+        let distance = octaves(frequency, BAND_FREQUENCIES[index]);
+        if self.points[index].is_none_or(|(closest, _)| distance < closest) {
+            self.points[index] = Some((distance, gain));
+        }
+    }
+
+    fn finish(mut self) -> EqImport {
+        for (band, point) in self.bands.iter_mut().zip(self.points) {
+            if let Some((_, gain)) = point {
+                *band += gain;
+            }
+        }
+
+        EqImport {
+            eq: GraphicEq {
+                preamp: self.preamp,
+                bands: self.bands,
+            },
+            skipped: self.skipped,
+        }
+    }
+}
+
+fn octaves(frequency: f32, band: f32) -> f32 {
+    (frequency / band).log2().abs()
+}
+
+fn nearest_band(frequency: f32) -> Option<usize> {
+    BAND_FREQUENCIES
+        .iter()
+        .map(|&band| octaves(frequency, band))
+        .enumerate()
+        .filter(|&(_, distance)| distance <= MAX_BAND_DISTANCE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+}
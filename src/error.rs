@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::FeatureId;
+
+/// A write was sent, but the value read back from the device
+/// doesn't match the requested one (within `ValueKind::tolerance()`).
+///
+/// Usually means the device clamped or rejected the value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MismatchError {
+    pub id: FeatureId,
+    pub requested: f32,
+    pub actual: f32,
+    /// How many times the value was written before giving up.
+    pub attempts: usize,
+}
+
+impl fmt::Display for MismatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: requested {}, device reports {} (after {} attempts)",
+            self.id, self.requested, self.actual, self.attempts
+        )
+    }
+}
+
+impl Error for MismatchError {}
+
+/// A `Transaction` could not be verified and was rolled back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionError {
+    pub mismatches: Vec<MismatchError>,
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Transaction rolled back, could not verify: ")?;
+        for (index, mismatch) in self.mismatches.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for TransactionError {}
+
+/// The connected model doesn't have the requested feature.
+/// See `DeviceModel::features`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnsupportedFeatureError {
+    pub id: FeatureId,
+    pub model: &'static str,
+}
+
+impl fmt::Display for UnsupportedFeatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not supported by the {}", self.id, self.model)
+    }
+}
+
+impl Error for UnsupportedFeatureError {}
+
+/// The connected model is unverified (see `DeviceModel::verified`),
+/// so it was opened read-only, see `BlasterXG6::is_read_only()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadOnlyError {
+    pub model: &'static str,
+}
+
+impl fmt::Display for ReadOnlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The {} is unverified and opened read-only, set {}=1 to write to it",
+            self.model,
+            crate::UNVERIFIED_WRITES_VAR
+        )
+    }
+}
+
+impl Error for ReadOnlyError {}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::FeatureId;
+
+/// What caused a `FeatureChange`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChangeSource {
+    /// A write from this process (`set_feature()`, transactions, profiles).
+    Local,
+    /// An unsolicited packet from the device,
+    /// e.g. after pressing the physical SBX button.
+    Device,
+    /// An explicit re-read via `read_state_from_device()`.
+    Refresh,
+}
+
+/// Emitted to every subscriber whenever a cached feature value changes.
+/// See `BlasterXG6::subscribe()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeatureChange {
+    pub id: FeatureId,
+    pub old: f32,
+    pub new: f32,
+    pub source: ChangeSource,
+}
@@ -1,11 +1,14 @@
 use std::fmt;
+use std::str::FromStr;
 use std::sync::Mutex;
 
+use hidapi::HidResult;
 use serde::de::Error;
 use serde::{Deserialize, Serialize, ser::Serializer};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::DEVICE_CONNECTION;
+use crate::DeviceModel;
+use crate::connection::Connection;
 
 // ─── FeatureId ───────────────────────────────────────────────────────────────
 
@@ -62,6 +65,16 @@ impl fmt::Display for FeatureId {
     }
 }
 
+/// Parses the serialized name, e.g. "SurroundToggle".
+impl FromStr for FeatureId {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(name.to_string()))
+            .map_err(|_| format!("Unknown feature: {}", name).into())
+    }
+}
+
 impl FeatureId {
     pub const ALL: &[FeatureId] = &[
         Self::SbxMaster,
@@ -182,6 +195,8 @@ impl FeatureId {
             Self::EqPreAmp => ValueKind::Ranged {
                 min: -6.0,
                 max: 6.0,
+                step: 0.5,
+                unit: "dB",
             },
 
             Self::Eq31Hz
@@ -196,11 +211,15 @@ impl FeatureId {
             | Self::Eq16kHz => ValueKind::Ranged {
                 min: -12.0,
                 max: 12.0,
+                step: 0.5,
+                unit: "dB",
             },
 
             Self::SurroundDistance => ValueKind::Ranged {
                 min: 10.0,
                 max: 300.0,
+                step: 1.0,
+                unit: "cm",
             },
 
             Self::SmartVolMode => {
@@ -243,6 +262,14 @@ impl FeatureId {
         }
     }
 
+    /// Reverse lookup of `dsp_address()`.
+    pub fn from_dsp_address(address: (u8, u8)) -> Option<FeatureId> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|id| id.dsp_address() == Some(address))
+    }
+
     /// Features that must be ON for this feature to work.
     pub fn dependencies(&self) -> &'static [FeatureId] {
         match self {
@@ -358,16 +385,179 @@ impl FeatureId {
     }
 }
 
+// ─── FeatureGroup ────────────────────────────────────────────────────────────
+
+/// Named sets of features, used to scope a `Profile`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FeatureGroup {
+    /// SBX and every SBX effect, including their levels and modes.
+    Sbx,
+    /// `FeatureId::SBX_TOGGLES`
+    SbxToggles,
+    /// The EQ toggle and `FeatureId::EQ_ALL`.
+    Equalizer,
+    /// `FeatureId::EQ_BANDS`
+    EqBands,
+}
+
+impl FeatureGroup {
+    pub const ALL: &[FeatureGroup] =
+        &[Self::Sbx, Self::SbxToggles, Self::Equalizer, Self::EqBands];
+
+    pub fn features(&self) -> &'static [FeatureId] {
+        match self {
+            Self::Sbx => &[
+                FeatureId::SbxMaster,
+                FeatureId::SurroundToggle,
+                FeatureId::SurroundLevel,
+                FeatureId::DialogPlusToggle,
+                FeatureId::DialogPlusLevel,
+                FeatureId::SmartVolToggle,
+                FeatureId::SmartVolLevel,
+                FeatureId::SmartVolMode,
+                FeatureId::CrystalizerToggle,
+                FeatureId::CrystalizerLevel,
+                FeatureId::BassToggle,
+                FeatureId::BassLevel,
+                FeatureId::SurroundDistance,
+            ],
+            Self::SbxToggles => FeatureId::SBX_TOGGLES,
+            Self::Equalizer => &[
+                FeatureId::EqToggle,
+                FeatureId::EqPreAmp,
+                FeatureId::Eq31Hz,
+                FeatureId::Eq62Hz,
+                FeatureId::Eq125Hz,
+                FeatureId::Eq250Hz,
+                FeatureId::Eq500Hz,
+                FeatureId::Eq1kHz,
+                FeatureId::Eq2kHz,
+                FeatureId::Eq4kHz,
+                FeatureId::Eq8kHz,
+                FeatureId::Eq16kHz,
+            ],
+            Self::EqBands => FeatureId::EQ_BANDS,
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Sbx => "SBX",
+            Self::SbxToggles => "SBX Toggles",
+            Self::Equalizer => "Equalizer",
+            Self::EqBands => "EQ Bands",
+        }
+    }
+}
+
 // ─── ValueKind ───────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ValueKind {
     Toggle,
     Percentage,
-    Ranged { min: f32, max: f32 },
+    /// `step` is the resolution sliders offer,
+    /// `unit` is shown after the value, e.g. "dB".
+    Ranged {
+        min: f32,
+        max: f32,
+        step: f32,
+        unit: &'static str,
+    },
     Preset(&'static [&'static str]),
 }
 
+impl ValueKind {
+    /// Maximum difference between a written and a read-back value
+    /// for the write to count as successful.
+    ///
+    /// Toggles and Presets are discrete and have to match exactly.
+    pub fn tolerance(&self) -> f32 {
+        match self {
+            Self::Toggle | Self::Preset(_) => 0.0,
+            Self::Percentage => 0.005,
+            Self::Ranged { .. } => 0.05,
+        }
+    }
+
+    /// Whether `actual` is within `tolerance()` of `requested`.
+    pub fn matches(&self, requested: f32, actual: f32) -> bool {
+        (requested - actual).abs() <= self.tolerance()
+    }
+
+    /// Whether the device accepts `value`: 0 or 1 for Toggles,
+    /// 0.0 - 1.0 for Percentages and the index of a label for Presets.
+    pub fn contains(&self, value: f32) -> bool {
+        match *self {
+            Self::Toggle => value == 0.0 || value == 1.0,
+            Self::Percentage => (0.0..=1.0).contains(&value),
+            Self::Ranged { min, max, .. } => (min..=max).contains(&value),
+            Self::Preset(labels) => {
+                value.fract() == 0.0
+                    && (0.0..labels.len() as f32).contains(&value)
+            }
+        }
+    }
+
+    /// The values `contains()` accepts, for error messages,
+    /// e.g. "0.0 - 1.0" or "0 (Speakers), 1 (Headphones)".
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Toggle => "0 or 1".to_string(),
+            Self::Percentage => "0.0 - 1.0".to_string(),
+            Self::Ranged { min, max, unit, .. } => {
+                format!("{} - {} {}", min, max, unit)
+            }
+            Self::Preset(labels) => labels
+                .iter()
+                .enumerate()
+                .map(|(index, label)| format!("{} ({})", index, label))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    /// The label of a Preset value, e.g. "Headphones" for an Output of 1.0.
+    pub fn label(&self, value: f32) -> Option<&'static str> {
+        match self {
+            Self::Preset(labels) if value >= 0.0 => {
+                labels.get(value as usize).copied()
+            }
+            _ => None,
+        }
+    }
+
+    /// Renders a value for humans, e.g. "On", "80%", "+2.5 dB", "Headphones".
+    /// `NaN` (never read) is rendered as "?".
+    pub fn format(&self, value: f32) -> String {
+        if value.is_nan() {
+            return "?".to_string();
+        }
+        match self {
+            Self::Toggle if value != 0.0 => "On".to_string(),
+            Self::Toggle => "Off".to_string(),
+            Self::Percentage => format!("{:.0}%", value * 100.0),
+            Self::Ranged { unit: "dB", .. } => format!("{:+.1} dB", value),
+            Self::Ranged { unit, .. } => format!("{:.0} {}", value, unit),
+            Self::Preset(_) => self
+                .label(value)
+                .map(str::to_string)
+                .unwrap_or_else(|| value.to_string()),
+        }
+    }
+
+    /// Reverse of `label()`.
+    pub fn value_of(&self, label: &str) -> Option<f32> {
+        match self {
+            Self::Preset(labels) => labels
+                .iter()
+                .position(|&candidate| candidate == label)
+                .map(|index| index as f32),
+            _ => None,
+        }
+    }
+}
+
 // ─── Feature ─────────────────────────────────────────────────────────────────
 
 fn serialize_mutex_f32<S>(
@@ -391,9 +581,9 @@ pub struct Feature {
     // Setter and Getter function are stored as members,
     // because it provides comfortable flexibility while reverse Engineering.
     #[serde(skip)]
-    getter: fn(&Feature) -> f32,
+    getter: fn(&Feature, &Connection) -> HidResult<f32>,
     #[serde(skip)]
-    setter: fn(&Feature, f32),
+    setter: fn(&Feature, &Connection, f32) -> HidResult<()>,
 }
 
 impl Clone for Feature {
@@ -467,22 +657,42 @@ impl Feature {
         }
     }
 
+    /// Overwrites the cached value without any hardware I/O,
+    /// for values the device reported on its own.
+    pub(crate) fn set_cached(&self, value: f32) {
+        *self.value.lock().unwrap() = value;
+    }
+
     /// Queries hardware for the current value, updates the cache, returns the fresh value.
-    pub fn read_from_device(&self) -> f32 {
-        (self.getter)(self)
+    /// Fails if the query can't be sent.
+    pub fn read_from_device(&self, connection: &Connection) -> HidResult<f32> {
+        (self.getter)(self, connection)
     }
 
     /// sends one write packet to hardware and reads the ACK.
     /// logs an error if the ACK is missing or invalid.
     /// does NOT update the value cache.
     /// you'll have to call `read_from_device()` afterwards.
-    pub fn write_to_device(&self, value: f32) {
-        (self.setter)(self, value)
+    /// fails if the packet can't be sent.
+    pub fn write_to_device(
+        &self,
+        connection: &Connection,
+        value: f32,
+    ) -> HidResult<()> {
+        (self.setter)(self, connection, value)
     }
 }
 
 // ─── Feature Registration ────────────────────────────────────────────────────
 
+/// The features of `all_features()` that `model` has.
+pub fn features_for(model: &DeviceModel) -> Vec<Feature> {
+    all_features()
+        .into_iter()
+        .filter(|feature| model.supports(feature.id))
+        .collect()
+}
+
 pub fn all_features() -> Vec<Feature> {
     vec![
         Feature::dsp(FeatureId::SurroundToggle),
@@ -536,15 +746,25 @@ pub fn all_features() -> Vec<Feature> {
 const MAX_READ_ATTEMPTS: usize = 30;
 const READ_TIMEOUT_MS: i32 = 500;
 
-fn read_packet() -> Option<[u8; 64]> {
+/// Maximum number of features per batched 0x11 request,
+/// each entry takes 6 bytes of the 64 byte response frame.
+pub const MAX_BATCH_SIZE: usize = 8;
+
+/// How long a batched 0x11 request waits for more of its response.
+/// No capture has a batched request, so a device that doesn't
+/// support them must not stall the refresh for long.
+const BATCH_SILENCE_MS: i32 = 300;
+
+fn read_packet(connection: &Connection) -> Option<[u8; 64]> {
+    read_packet_within(connection, READ_TIMEOUT_MS)
+}
+
+fn read_packet_within(
+    connection: &Connection,
+    timeout_ms: i32,
+) -> Option<[u8; 64]> {
     let mut buffer = [0u8; 64];
-    match DEVICE_CONNECTION
-        .get()
-        .expect("Device connection must be initialized")
-        .lock()
-        .unwrap()
-        .read_timeout(&mut buffer, READ_TIMEOUT_MS)
-    {
+    match connection.read_timeout(&mut buffer, timeout_ms) {
         Ok(0) => None,
         Ok(_) => Some(buffer),
         Err(error) => {
@@ -554,9 +774,94 @@ fn read_packet() -> Option<[u8; 64]> {
     }
 }
 
-fn read_ack() {
+/// Non-blocking variant of `read_packet()`,
+/// returns `None` right away if the device has nothing to say.
+///
+/// Packets deferred while waiting for a response come first.
+pub(crate) fn try_read_packet(connection: &Connection) -> Option<[u8; 64]> {
+    if let Some(packet) = connection.take_deferred() {
+        return Some(packet);
+    }
+    let mut buffer = [0u8; 64];
+    match connection.read_timeout(&mut buffer, 0) {
+        Ok(0) => None,
+        Ok(_) => Some(buffer),
+        Err(error) => {
+            error!("Failed to read packet: {:?}", error);
+            None
+        }
+    }
+}
+
+/// Extracts feature values from a packet the device sent on its own:
+/// - `0x11` status pushes (single or multi-feature)
+/// - `0x26` global profile bitmask (SBX, Scout Mode, EQ)
+/// - `0x2c` output mode
+///
+/// Anything else (ACKs, unknown commands) yields nothing,
+/// and so do features `model` doesn't have.
+pub(crate) fn decode_notification(
+    model: &DeviceModel,
+    packet: &[u8; 64],
+) -> Vec<(FeatureId, f32)> {
+    if packet[0] != 0x5a {
+        return Vec::new();
+    }
+
+    let as_value = |is_on: bool| if is_on { 1.0 } else { 0.0 };
+
+    let values = match packet[1] {
+        0x11 => parse_status_response(packet)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(family, feature_id, value)| {
+                model.feature_at((family, feature_id)).map(|id| (id, value))
+            })
+            .collect(),
+
+        // 5a 26 0b 08 ff ff [bitmask] ...
+        0x26 if packet[3] == 0x08 => {
+            let bitmask = packet[6];
+            vec![
+                (FeatureId::SbxMaster, as_value(bitmask & 0x01 != 0)),
+                (FeatureId::ScoutMode, as_value(bitmask & 0x02 != 0)),
+                (FeatureId::EqToggle, as_value(bitmask & 0x04 != 0)),
+            ]
+        }
+
+        // 5a 2c 05 01 [mode] ...
+        0x2c if packet[3] == 0x01 => match packet[4] {
+            0x02 => vec![(FeatureId::Output, 0.0)],
+            0x04 => vec![(FeatureId::Output, 1.0)],
+            _ => Vec::new(),
+        },
+
+        _ => Vec::new(),
+    };
+
+    values
+        .into_iter()
+        .filter(|&(id, _)| model.supports(id))
+        .collect()
+}
+
+/// Decodes a `0x6e` hardware notification into the headphone jack state,
+/// `true` if headphones are plugged in.
+///
+/// `5a 6e 02 01 [state]`, with `state` assumed to be `0x00` for unplugged.
+/// The semantics of `0x6e` are not fully known (see UsbProtocol.md),
+/// so every one is logged to help verify this.
+pub(crate) fn decode_jack(packet: &[u8; 64]) -> Option<bool> {
+    if packet[0] != 0x5a || packet[1] != 0x6e || packet[3] != 0x01 {
+        return None;
+    }
+    info!("Jack notification: {:02x?}", &packet[..8]);
+    Some(packet[4] != 0x00)
+}
+
+fn read_ack(connection: &Connection) {
     for attempt in 0..MAX_READ_ATTEMPTS {
-        let Some(packet) = read_packet() else {
+        let Some(packet) = read_packet(connection) else {
             error!(
                 "Expected ACK but no response on attempt {}/{}",
                 attempt + 1,
@@ -567,6 +872,7 @@ fn read_ack() {
 
         if packet[0] == 0x5a && packet[1] == 0x02 {
             debug!("ACK received: {:02x?}", &packet[..12]);
+            connection.record_round_trip();
             return;
         }
 
@@ -578,14 +884,196 @@ fn read_ack() {
     }
 
     error!("No ACK received after {} attempts", MAX_READ_ATTEMPTS);
+    connection.record_ack_failure();
+}
+
+/// Builds a 0x11 StatusRequest for one or more `(family, feature_id)` pairs.
+///
+/// `5a 11 [len] [count] [family] [id] [family] [id] ...`
+pub(crate) fn status_request_packet(addresses: &[(u8, u8)]) -> [u8; 65] {
+    assert!(
+        !addresses.is_empty() && addresses.len() <= MAX_BATCH_SIZE,
+        "StatusRequest must contain 1..={} features",
+        MAX_BATCH_SIZE
+    );
+
+    let mut payload = [0u8; 65];
+    payload[1] = 0x5a;
+    payload[2] = 0x11;
+    payload[3] = 0x01 + 2 * addresses.len() as u8;
+    payload[4] = addresses.len() as u8;
+    for (index, &(family, feature_id)) in addresses.iter().enumerate() {
+        payload[5 + 2 * index] = family;
+        payload[6 + 2 * index] = feature_id;
+    }
+    payload
+}
+
+/// Decodes a (single- or multi-feature) 0x11 StatusResponse.
+/// Returns `None` if the packet is not a StatusResponse.
+///
+/// `5a 11 [len] [count] 00 [family] [id] [f32 LE × 4] [family] [id] ...`
+pub(crate) fn parse_status_response(
+    packet: &[u8; 64],
+) -> Option<Vec<(u8, u8, f32)>> {
+    if packet[0] != 0x5a || packet[1] != 0x11 {
+        return None;
+    }
+
+    let count = packet[3] as usize;
+    let entries = packet[5..]
+        .chunks_exact(6)
+        .take(count)
+        .map(|entry| {
+            let value = f32::from_le_bytes(
+                entry[2..6]
+                    .try_into()
+                    .expect("Failed to parse f32 from response bytes"),
+            );
+            (entry[0], entry[1], value)
+        })
+        .collect();
+
+    Some(entries)
 }
 
 // ─── DSP Getter/Setter (0x96 family via 0x11/0x12) ──────────────────────────
 
-fn dsp_get(feature: &Feature) -> f32 {
-    let (family, feature_id) = feature
-        .id
-        .dsp_address()
+/// Sends one batched 0x11 request for two DSP features of the model,
+/// and allows `dsp_get_many()` to batch if both are answered.
+///
+/// The batched format is not confirmed by a capture,
+/// a device that ignores it is queried one feature at a time.
+pub(crate) fn probe_batching(connection: &Connection) {
+    let model = connection.model();
+    let addresses: Vec<(u8, u8)> = FeatureId::ALL
+        .iter()
+        .filter(|&&id| model.supports(id))
+        .filter_map(|&id| model.dsp_address(id))
+        .take(2)
+        .collect();
+    if addresses.len() < 2 {
+        return;
+    }
+
+    let batching = match query_batch(connection, &addresses) {
+        Ok(values) => values.iter().all(Option::is_some),
+        Err(error) => {
+            warn!("Failed to probe batched status requests: {}", error);
+            false
+        }
+    };
+    if batching {
+        info!("Batched status requests are supported");
+    } else {
+        info!(
+            "Batched status requests are unsupported, querying one at a time"
+        );
+    }
+    connection.set_batching(batching);
+}
+
+/// Sends a batched 0x11 request and collects the values of `addresses`,
+/// until all arrived or the device stayed silent for `BATCH_SILENCE_MS`.
+fn query_batch(
+    connection: &Connection,
+    addresses: &[(u8, u8)],
+) -> HidResult<Vec<Option<f32>>> {
+    let mut values = vec![None; addresses.len()];
+
+    connection.write(&status_request_packet(addresses))?;
+
+    for attempt in 0..MAX_READ_ATTEMPTS {
+        if values.iter().all(Option::is_some) {
+            break;
+        }
+        let Some(response) = read_packet_within(connection, BATCH_SILENCE_MS)
+        else {
+            debug!("No further response to batched query");
+            break;
+        };
+
+        let Some(entries) = parse_status_response(&response) else {
+            debug!(
+                "Discarded stale packet on attempt {}: {:02x?}",
+                attempt + 1,
+                &response[..12]
+            );
+            continue;
+        };
+        connection.record_round_trip();
+
+        for (family, feature_id, value) in entries {
+            if let Some(index) = addresses
+                .iter()
+                .position(|&address| address == (family, feature_id))
+            {
+                values[index] = Some(value);
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Queries many DSP features with as few 0x11 round-trips as possible,
+/// `MAX_BATCH_SIZE` features per request if `probe_batching()` found
+/// the device to answer batches, one feature per request otherwise.
+/// Updates the cache of every feature a value was received for.
+///
+/// Features missing from a batched response are retried individually,
+/// a batch that went entirely unanswered turns batching off.
+pub fn dsp_get_many(
+    connection: &Connection,
+    features: &[&Feature],
+) -> HidResult<()> {
+    for chunk in features.chunks(MAX_BATCH_SIZE) {
+        if !connection.batching() {
+            for feature in chunk {
+                feature.read_from_device(connection)?;
+            }
+            continue;
+        }
+
+        let addresses: Vec<(u8, u8)> = chunk
+            .iter()
+            .map(|feature| {
+                connection
+                    .model()
+                    .dsp_address(feature.id)
+                    .expect("dsp_get_many called on non-DSP feature")
+            })
+            .collect();
+
+        debug!("Querying {} DSP features in one batch", chunk.len());
+        let values = query_batch(connection, &addresses)?;
+        if values.iter().all(Option::is_none) {
+            warn!("Batched query went unanswered, querying one at a time");
+            connection.set_batching(false);
+        }
+
+        for (feature, value) in chunk.iter().zip(values) {
+            match value {
+                Some(value) => {
+                    debug!("Read {} = {}", feature.id, value);
+                    *feature.value.lock().unwrap() = value;
+                }
+                None => {
+                    error!(
+                        "{} missing from batched response, querying individually",
+                        feature.id
+                    );
+                    feature.read_from_device(connection)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn dsp_get(feature: &Feature, connection: &Connection) -> HidResult<f32> {
+    let (family, feature_id) = connection
+        .model()
+        .dsp_address(feature.id)
         .expect("dsp_get called on non-DSP feature");
 
     debug!(
@@ -593,24 +1081,12 @@ fn dsp_get(feature: &Feature) -> f32 {
         feature.id, family, feature_id
     );
 
-    let mut payload = [0u8; 65];
-    payload[1] = 0x5a;
-    payload[2] = 0x11;
-    payload[3] = 0x03;
-    payload[4] = 0x01;
-    payload[5] = family;
-    payload[6] = feature_id;
+    let payload = status_request_packet(&[(family, feature_id)]);
 
-    DEVICE_CONNECTION
-        .get()
-        .expect("Device Connection must be initialized")
-        .lock()
-        .unwrap()
-        .write(&payload)
-        .expect("Failed to send Query to Device");
+    connection.write(&payload)?;
 
     for attempt in 0..MAX_READ_ATTEMPTS {
-        let Some(response) = read_packet() else {
+        let Some(response) = read_packet(connection) else {
             error!(
                 "No response on attempt {}/{} for {}",
                 attempt + 1,
@@ -632,8 +1108,9 @@ fn dsp_get(feature: &Feature) -> f32 {
                     .expect("Failed to parse f32 from response bytes"),
             );
             debug!("Read {} = {}", feature.id, value);
+            connection.record_round_trip();
             *feature.value.lock().unwrap() = value;
-            return value;
+            return Ok(value);
         }
 
         debug!(
@@ -647,13 +1124,17 @@ fn dsp_get(feature: &Feature) -> f32 {
         "No matching response after {} attempts for {}",
         MAX_READ_ATTEMPTS, feature.id
     );
-    0.0
+    Ok(0.0)
 }
 
-fn dsp_set(feature: &Feature, value: f32) {
-    let (family, feature_id) = feature
-        .id
-        .dsp_address()
+fn dsp_set(
+    feature: &Feature,
+    connection: &Connection,
+    value: f32,
+) -> HidResult<()> {
+    let (family, feature_id) = connection
+        .model()
+        .dsp_address(feature.id)
         .expect("dsp_set called on non-DSP feature");
 
     debug!(
@@ -672,20 +1153,18 @@ fn dsp_set(feature: &Feature, value: f32) {
     payload[6] = feature_id;
     payload[7..11].copy_from_slice(&value_bytes);
 
-    DEVICE_CONNECTION
-        .get()
-        .expect("Device Connection must be initialized")
-        .lock()
-        .unwrap()
-        .write(&payload)
-        .expect("Failed to send Write to Device");
+    connection.write(&payload)?;
 
-    read_ack();
+    read_ack(connection);
+    Ok(())
 }
 
 // ─── GlobalProfile Getter/Setter (0x26) ──────────────────────────────────────
 
-fn global_profile_get(feature: &Feature) -> f32 {
+fn global_profile_get(
+    feature: &Feature,
+    connection: &Connection,
+) -> HidResult<f32> {
     let bitmask = match feature.id {
         FeatureId::SbxMaster => 0x01u8,
         FeatureId::ScoutMode => 0x02u8,
@@ -705,16 +1184,10 @@ fn global_profile_get(feature: &Feature) -> f32 {
     payload[5] = 0xff;
     payload[6] = 0xff;
 
-    DEVICE_CONNECTION
-        .get()
-        .expect("Device connection must be initialized")
-        .lock()
-        .unwrap()
-        .write(&payload)
-        .expect("Failed to send query to device");
+    connection.write(&payload)?;
 
     for attempt in 0..MAX_READ_ATTEMPTS {
-        let Some(response) = read_packet() else {
+        let Some(response) = read_packet(connection) else {
             error!(
                 "No response on attempt {}/{} for {}",
                 attempt + 1,
@@ -733,8 +1206,9 @@ fn global_profile_get(feature: &Feature) -> f32 {
                 "Read {} = {} (device bitmask: 0x{:02x})",
                 feature.id, value, device_bitmask
             );
+            connection.record_round_trip();
             *feature.value.lock().unwrap() = value;
-            return value;
+            return Ok(value);
         }
 
         debug!(
@@ -748,10 +1222,14 @@ fn global_profile_get(feature: &Feature) -> f32 {
         "No matching response after {} attempts for {}",
         MAX_READ_ATTEMPTS, feature.id
     );
-    0.0
+    Ok(0.0)
 }
 
-fn global_profile_set(feature: &Feature, value: f32) {
+fn global_profile_set(
+    feature: &Feature,
+    connection: &Connection,
+    value: f32,
+) -> HidResult<()> {
     let profile_id = match feature.id {
         FeatureId::SbxMaster => 0x01u8,
         FeatureId::ScoutMode => 0x02u8,
@@ -775,20 +1253,15 @@ fn global_profile_set(feature: &Feature, value: f32) {
     payload[7] = state;
     payload[8] = 0x00;
 
-    DEVICE_CONNECTION
-        .get()
-        .expect("Device connection must be initialized")
-        .lock()
-        .unwrap()
-        .write(&payload)
-        .expect("Failed to send write to device");
+    connection.write(&payload)?;
 
-    read_ack();
+    read_ack(connection);
+    Ok(())
 }
 
 // ─── Output Getter/Setter (0x2c) ────────────────────────────────────────────
 
-fn output_get(feature: &Feature) -> f32 {
+fn output_get(feature: &Feature, connection: &Connection) -> HidResult<f32> {
     debug!("Querying output device");
 
     let mut payload = [0u8; 65];
@@ -797,16 +1270,10 @@ fn output_get(feature: &Feature) -> f32 {
     payload[3] = 0x01;
     payload[4] = 0x01;
 
-    DEVICE_CONNECTION
-        .get()
-        .expect("Device connection must be initialized")
-        .lock()
-        .unwrap()
-        .write(&payload)
-        .expect("Failed to send Query to Device");
+    connection.write(&payload)?;
 
     for attempt in 0..MAX_READ_ATTEMPTS {
-        let Some(response) = read_packet() else {
+        let Some(response) = read_packet(connection) else {
             error!(
                 "No response on attempt {}/{} for Output",
                 attempt + 1,
@@ -831,8 +1298,9 @@ fn output_get(feature: &Feature) -> f32 {
                     f32::NAN
                 }
             };
+            connection.record_round_trip();
             *feature.value.lock().unwrap() = value;
-            return value;
+            return Ok(value);
         }
 
         debug!(
@@ -846,10 +1314,14 @@ fn output_get(feature: &Feature) -> f32 {
         "No matching response after {} attempts for Output",
         MAX_READ_ATTEMPTS
     );
-    0.0
+    Ok(0.0)
 }
 
-fn output_set(feature: &Feature, value: f32) {
+fn output_set(
+    feature: &Feature,
+    connection: &Connection,
+    value: f32,
+) -> HidResult<()> {
     let mode = if value > 0.0 { 0x04u8 } else { 0x02u8 };
 
     debug!("Setting output: mode 0x{:02x}", mode);
@@ -864,13 +1336,80 @@ fn output_set(feature: &Feature, value: f32) {
     payload[7] = 0x00;
     payload[8] = 0x00;
 
-    DEVICE_CONNECTION
-        .get()
-        .expect("Device connection must be initialized")
-        .lock()
-        .unwrap()
-        .write(&payload)
-        .expect("Failed to send write to device");
+    connection.write(&payload)?;
+
+    read_ack(connection);
+    Ok(())
+}
+
+// ─── Device Info (0x07) ─────────────────────────────────────────────────────
 
-    read_ack();
+/// `5a 07 01 02`, answered with the firmware version string.
+fn firmware_request() -> [u8; 65] {
+    let mut payload = [0u8; 65];
+    payload[1] = 0x5a;
+    payload[2] = 0x07;
+    payload[3] = 0x01;
+    payload[4] = 0x02;
+    payload
+}
+
+/// Whether the device answers a harmless request.
+/// The response is read here, so it isn't left for whoever reads next.
+pub(crate) fn ping(connection: &Connection) -> bool {
+    connection.write(&firmware_request()).is_ok()
+        && read_firmware_response(connection).is_some()
+}
+
+/// Queries the firmware version string, e.g. `"2.1.250903.1324"`.
+///
+/// `5a 07 01 02` → `5a 07 [len] [ASCII...]`
+pub fn firmware_version(connection: &Connection) -> Option<String> {
+    if let Err(error) = connection.write(&firmware_request()) {
+        error!("Failed to query firmware version: {}", error);
+        return None;
+    }
+
+    let version = read_firmware_response(connection);
+    match &version {
+        Some(version) => debug!("Firmware version: {}", version),
+        None => {
+            error!("No firmware version after {} attempts", MAX_READ_ATTEMPTS)
+        }
+    }
+    version
+}
+
+/// Waits for the answer to `firmware_request()`.
+/// Anything else that arrives first is deferred,
+/// so notifications still reach `poll_notifications()`.
+fn read_firmware_response(connection: &Connection) -> Option<String> {
+    for attempt in 0..MAX_READ_ATTEMPTS {
+        let Some(response) = read_packet(connection) else {
+            continue;
+        };
+
+        if let Some(version) = parse_firmware_response(&response) {
+            connection.record_round_trip();
+            return Some(version);
+        }
+
+        debug!(
+            "Deferred packet on attempt {}: {:02x?}",
+            attempt + 1,
+            &response[..12]
+        );
+        connection.defer(response);
+    }
+    None
+}
+
+pub(crate) fn parse_firmware_response(packet: &[u8; 64]) -> Option<String> {
+    if packet[0] != 0x5a || packet[1] != 0x07 {
+        return None;
+    }
+    let length = (packet[2] as usize).min(packet.len() - 3);
+    let bytes = &packet[3..3 + length];
+    let bytes = bytes.split(|&byte| byte == 0).next().unwrap_or_default();
+    Some(String::from_utf8_lossy(bytes).into_owned())
 }
@@ -0,0 +1,460 @@
+//! Global hotkeys for `linuxblasterd`, through the XDG GlobalShortcuts
+//! portal, or by reading keyboards via evdev where there is no portal.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::mem::offset_of;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use crate::{BlasterXG6, FeatureId, ProfileLibrary};
+
+/// What a hotkey does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Flips a toggle, e.g. `SbxMaster` or `ScoutMode`.
+    Toggle {
+        feature: FeatureId,
+    },
+    NextOutput,
+    /// Cycles through the `ProfileLibrary`, in `list()` order.
+    NextProfile,
+    PreviousProfile,
+}
+
+impl Action {
+    /// Stable id the portal remembers the user's trigger by.
+    pub fn id(&self) -> String {
+        match self {
+            Self::Toggle { feature } => format!("toggle-{:?}", feature),
+            Self::NextOutput => "next-output".to_string(),
+            Self::NextProfile => "next-profile".to_string(),
+            Self::PreviousProfile => "previous-profile".to_string(),
+        }
+    }
+
+    /// Runs the action, returning what to show in the notification,
+    /// e.g. "Scout Mode: On".
+    pub fn run(&self, blaster: &BlasterXG6) -> Result<String, Box<dyn Error>> {
+        match *self {
+            Self::Toggle { feature } => {
+                blaster.set_feature(feature, None)?;
+                let value = blaster.feature(feature).value();
+                Ok(format!(
+                    "{}: {}",
+                    feature.display_name(),
+                    feature.value_kind().format(value)
+                ))
+            }
+            Self::NextOutput => {
+                blaster.switch_output(None)?;
+                Ok(format!("Output: {}", blaster.output_label().unwrap_or("?")))
+            }
+            Self::NextProfile => cycle_profile(blaster, 1),
+            Self::PreviousProfile => cycle_profile(blaster, -1),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Toggle { feature } => {
+                write!(f, "Toggle {}", feature.display_name())
+            }
+            Self::NextOutput => write!(f, "Next Output"),
+            Self::NextProfile => write!(f, "Next profile"),
+            Self::PreviousProfile => write!(f, "Previous profile"),
+        }
+    }
+}
+
+/// Applies the profile `step` entries away from the active one,
+/// or the first (last) one if no profile is active.
+fn cycle_profile(
+    blaster: &BlasterXG6,
+    step: isize,
+) -> Result<String, Box<dyn Error>> {
+    let entries = ProfileLibrary::default().list()?;
+    if entries.is_empty() {
+        return Err("The profile library is empty".into());
+    }
+    let count = entries.len() as isize;
+    let index = match entries
+        .iter()
+        .position(|entry| blaster.matches_profile(&entry.profile))
+    {
+        Some(active) => (active as isize + step).rem_euclid(count),
+        None if step > 0 => 0,
+        None => count - 1,
+    };
+    let entry = &entries[index as usize];
+    blaster.apply(&entry.profile)?;
+    Ok(format!("Profile: {}", entry.label()))
+}
+
+/// An `Action` bound to a key combination.
+///
+/// ```json
+/// { "action": "toggle", "feature": "ScoutMode", "trigger": "CTRL+ALT+S" }
+/// ```
+///
+/// Triggers use the portal's format: modifiers (`CTRL`, `ALT`, `SHIFT`,
+/// `LOGO`) and a key joined by `+`, e.g. "LOGO+F9" or "XF86AudioPlay".
+/// With the portal, they are only a suggestion the desktop may change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hotkey {
+    #[serde(flatten)]
+    pub action: Action,
+    pub trigger: String,
+}
+
+/// Binds `hotkeys` and sends their `Action` whenever one is pressed.
+///
+/// Tries the GlobalShortcuts portal first, and falls back to evdev,
+/// which needs read access to `/dev/input` (e.g. the `input` group).
+pub fn listen(hotkeys: &[Hotkey]) -> Receiver<Action> {
+    let (sender, receiver) = channel();
+    if hotkeys.is_empty() {
+        return receiver;
+    }
+
+    let hotkeys = hotkeys.to_vec();
+    std::thread::spawn(move || {
+        let error = match listen_portal(&hotkeys, &sender) {
+            Ok(()) => return,
+            Err(error) => error,
+        };
+        info!(
+            "GlobalShortcuts portal unavailable ({}), using evdev",
+            error
+        );
+        if let Err(error) = listen_evdev(&hotkeys, sender) {
+            warn!("Hotkeys are disabled: {}", error);
+        }
+    });
+    receiver
+}
+
+const PORTAL: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SHORTCUTS: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+/// Blocks while the portal session lasts.
+fn listen_portal(
+    hotkeys: &[Hotkey],
+    sender: &Sender<Action>,
+) -> Result<(), Box<dyn Error>> {
+    let connection = zbus::blocking::Connection::session()?;
+    let portal = zbus::blocking::Proxy::new(
+        &connection,
+        PORTAL,
+        PORTAL_PATH,
+        SHORTCUTS,
+    )?;
+    // fails on desktops without GlobalShortcuts
+    portal.get_property::<u32>("version")?;
+
+    let options = HashMap::from([
+        ("handle_token", Value::from("linuxblaster_session")),
+        ("session_handle_token", Value::from("linuxblaster")),
+    ]);
+    let results =
+        portal_request(&connection, &portal, "CreateSession", &(options,))?;
+    // a string since version 2 of the portal, an object path before
+    let session = match results.get("session_handle").map(|value| &**value) {
+        Some(Value::Str(path)) => OwnedObjectPath::try_from(path.as_str())?,
+        Some(Value::ObjectPath(path)) => path.to_owned().into(),
+        _ => return Err("No session handle".into()),
+    };
+
+    let shortcuts: Vec<(String, HashMap<&str, Value>)> = hotkeys
+        .iter()
+        .map(|hotkey| {
+            let options = HashMap::from([
+                ("description", Value::from(hotkey.action.to_string())),
+                ("preferred_trigger", Value::from(hotkey.trigger.as_str())),
+            ]);
+            (hotkey.action.id(), options)
+        })
+        .collect();
+    let options =
+        HashMap::from([("handle_token", Value::from("linuxblaster_bind"))]);
+    portal_request(
+        &connection,
+        &portal,
+        "BindShortcuts",
+        &(&session, shortcuts, "", options),
+    )?;
+    info!("Bound {} hotkeys through the portal", hotkeys.len());
+
+    for message in portal.receive_signal("Activated")? {
+        let (_, id, _, _): (
+            OwnedObjectPath,
+            String,
+            u64,
+            HashMap<String, OwnedValue>,
+        ) = message.body().deserialize()?;
+        if let Some(hotkey) =
+            hotkeys.iter().find(|hotkey| hotkey.action.id() == id)
+        {
+            debug!("Hotkey {} pressed", id);
+            if sender.send(hotkey.action).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Calls a portal method and waits for the `Response` of its request.
+fn portal_request<B>(
+    connection: &zbus::blocking::Connection,
+    portal: &zbus::blocking::Proxy,
+    method: &str,
+    body: &B,
+) -> Result<HashMap<String, OwnedValue>, Box<dyn Error>>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    // subscribe before calling, the response may arrive right away
+    let rule = zbus::MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface("org.freedesktop.portal.Request")?
+        .member("Response")?
+        .build();
+    let mut responses = zbus::blocking::MessageIterator::for_match_rule(
+        rule, connection, None,
+    )?;
+
+    let request: OwnedObjectPath = portal.call(method, body)?;
+    for message in &mut responses {
+        let message = message?;
+        if message.header().path().map(|path| path.as_str())
+            != Some(request.as_str())
+        {
+            continue;
+        }
+        let (response, results): (u32, HashMap<String, OwnedValue>) =
+            message.body().deserialize()?;
+        if response != 0 {
+            return Err(format!("{} was denied ({})", method, response).into());
+        }
+        return Ok(results);
+    }
+    Err(format!("No response to {}", method).into())
+}
+
+/// A trigger parsed into evdev key codes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct KeyCombo {
+    pub modifiers: Vec<Modifier>,
+    pub key: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Modifier {
+    Ctrl,
+    Alt,
+    Shift,
+    Logo,
+}
+
+impl Modifier {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "CTRL" | "CONTROL" => Some(Self::Ctrl),
+            "ALT" => Some(Self::Alt),
+            "SHIFT" => Some(Self::Shift),
+            "LOGO" | "SUPER" | "META" => Some(Self::Logo),
+            _ => None,
+        }
+    }
+
+    /// The modifier a key code is, left or right.
+    fn from_code(code: u16) -> Option<Self> {
+        match code {
+            29 | 97 => Some(Self::Ctrl),
+            56 | 100 => Some(Self::Alt),
+            42 | 54 => Some(Self::Shift),
+            125 | 126 => Some(Self::Logo),
+            _ => None,
+        }
+    }
+}
+
+impl KeyCombo {
+    pub(crate) fn parse(trigger: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = trigger.split('+').map(str::trim).collect();
+        let key = key_code(parts.pop()?)?;
+        let modifiers = parts
+            .into_iter()
+            .map(Modifier::from_name)
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { modifiers, key })
+    }
+
+    fn matches(&self, key: u16, held: &HashSet<Modifier>) -> bool {
+        self.key == key
+            && held.len() == self.modifiers.len()
+            && self
+                .modifiers
+                .iter()
+                .all(|modifier| held.contains(modifier))
+    }
+}
+
+/// Linux input event codes (`linux/input-event-codes.h`)
+/// for the keys a trigger may end with.
+fn key_code(name: &str) -> Option<u16> {
+    const ROWS: [(&str, u16); 4] = [
+        ("1234567890", 2),
+        ("QWERTYUIOP", 16),
+        ("ASDFGHJKL", 30),
+        ("ZXCVBNM", 44),
+    ];
+    let upper = name.to_ascii_uppercase();
+    if upper.len() == 1 {
+        let char = upper.chars().next()?;
+        return ROWS.iter().find_map(|(row, first)| {
+            row.find(char).map(|index| first + index as u16)
+        });
+    }
+    if let Some(number) = upper.strip_prefix('F')
+        && let Ok(number) = number.parse::<u16>()
+    {
+        return match number {
+            1..=10 => Some(58 + number),
+            11 | 12 => Some(76 + number),
+            13..=24 => Some(170 + number),
+            _ => None,
+        };
+    }
+    let code = match upper.as_str() {
+        "ESCAPE" => 1,
+        "TAB" => 15,
+        "RETURN" | "ENTER" => 28,
+        "SPACE" => 57,
+        "PAUSE" => 119,
+        "INSERT" => 110,
+        "DELETE" => 111,
+        "HOME" => 102,
+        "END" => 107,
+        "PAGE_UP" | "PRIOR" => 104,
+        "PAGE_DOWN" | "NEXT" => 109,
+        "XF86AUDIOMUTE" => 113,
+        "XF86AUDIOLOWERVOLUME" => 114,
+        "XF86AUDIORAISEVOLUME" => 115,
+        "XF86AUDIONEXT" => 163,
+        "XF86AUDIOPLAY" => 164,
+        "XF86AUDIOPREV" => 165,
+        "XF86AUDIOSTOP" => 166,
+        "XF86AUDIOMEDIA" => 226,
+        _ => return None,
+    };
+    Some(code)
+}
+
+const EV_KEY: u16 = 1;
+/// `struct input_event`: a timestamp whose size depends on the platform,
+/// then type (u16), code (u16) and value (i32).
+const EVENT_SIZE: usize = size_of::<libc::input_event>();
+const TYPE_OFFSET: usize = offset_of!(libc::input_event, type_);
+const CODE_OFFSET: usize = offset_of!(libc::input_event, code);
+const VALUE_OFFSET: usize = offset_of!(libc::input_event, value);
+
+/// Reads every readable `/dev/input/event*` device, one thread each.
+/// Keys still reach the desktop, they are not grabbed.
+fn listen_evdev(
+    hotkeys: &[Hotkey],
+    sender: Sender<Action>,
+) -> Result<(), Box<dyn Error>> {
+    let combos: Vec<(KeyCombo, Action)> = hotkeys
+        .iter()
+        .filter_map(|hotkey| match KeyCombo::parse(&hotkey.trigger) {
+            Some(combo) => Some((combo, hotkey.action)),
+            None => {
+                warn!("Unknown hotkey trigger: {}", hotkey.trigger);
+                None
+            }
+        })
+        .collect();
+    let combos = Arc::new(combos);
+
+    let mut devices = Vec::new();
+    for entry in std::fs::read_dir("/dev/input")? {
+        let path = entry?.path();
+        let is_event = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("event"));
+        if is_event && let Ok(file) = File::open(&path) {
+            devices.push(file);
+        }
+    }
+    if devices.is_empty() {
+        return Err(
+            "No readable input devices (is the user in the input group?)"
+                .into(),
+        );
+    }
+    info!(
+        "Listening for {} hotkeys on {} input devices",
+        combos.len(),
+        devices.len()
+    );
+
+    // modifiers held on any keyboard
+    let held: Arc<Mutex<HashSet<Modifier>>> = Arc::default();
+    for mut device in devices {
+        let combos = combos.clone();
+        let held = held.clone();
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            let mut event = [0u8; EVENT_SIZE];
+            while device.read_exact(&mut event).is_ok() {
+                let kind = u16::from_ne_bytes(
+                    event[TYPE_OFFSET..TYPE_OFFSET + 2].try_into().unwrap(),
+                );
+                let code = u16::from_ne_bytes(
+                    event[CODE_OFFSET..CODE_OFFSET + 2].try_into().unwrap(),
+                );
+                let value = i32::from_ne_bytes(
+                    event[VALUE_OFFSET..VALUE_OFFSET + 4].try_into().unwrap(),
+                );
+                if kind != EV_KEY {
+                    continue;
+                }
+
+                let mut held = held.lock().unwrap();
+                if let Some(modifier) = Modifier::from_code(code) {
+                    match value {
+                        0 => held.remove(&modifier),
+                        _ => held.insert(modifier),
+                    };
+                    continue;
+                }
+                // 1 is a press, 2 a repeat
+                if value != 1 {
+                    continue;
+                }
+                for (combo, action) in combos.iter() {
+                    if combo.matches(code, &held)
+                        && sender.send(*action).is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+    Ok(())
+}
@@ -1,21 +1,65 @@
 #![allow(unused)]
 
 use hidapi::{DeviceInfo, HidApi, HidDevice};
-use rusb;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::error::Error;
 use std::io::ErrorKind;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
 #[cfg(test)]
 mod tests;
 
+pub mod config;
+pub mod connection;
+pub mod dbus;
+pub mod diff;
+pub mod eq;
+pub mod error;
+pub mod events;
 pub mod features;
-pub use features::{Feature, FeatureId, ValueKind};
+pub mod hotkeys;
+pub mod library;
+pub mod metrics;
+pub mod model;
+pub mod mqtt;
+pub mod notify;
+pub mod profile;
+mod remote;
+pub mod rules;
+pub mod sbc;
+pub mod schedule;
+pub mod services;
+pub mod snapshot;
+pub mod socket;
+pub mod transaction;
+pub use config::Config;
+pub use connection::{Connection, DeviceDescriptor, DeviceSelector};
+pub use diff::{FeatureDiff, ProfileDiff};
+pub use error::{
+    MismatchError, ReadOnlyError, TransactionError, UnsupportedFeatureError,
+};
+pub use events::{ChangeSource, FeatureChange};
+pub use features::{Feature, FeatureGroup, FeatureId, ValueKind};
+pub use library::{CachedLibrary, LibraryEntry, ProfileLibrary};
+pub use metrics::LinkStats;
+pub use model::DeviceModel;
+pub use notify::{NotificationGroup, Notifier, Verbosity};
+pub use profile::{
+    Profile, ProfileBank, ProfileMetadata, ProfileScope, ProfileValue,
+};
+pub use rules::{Rule, RuleEngine, RuleSet, Trigger};
+pub use schedule::{Schedule, ScheduledProfile, Scheduler};
+pub use transaction::Transaction;
 
+use remote::Remote;
+
+// ids of the G6, see `DeviceModel` for the other models
 pub const VENDOR_ID: u16 = 0x041e;
 pub const PRODUCT_ID: u16 = 0x3256;
 pub const INTERFACE: i32 = 4;
@@ -43,51 +87,347 @@ pub static DEFAULT_BASE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
     }
 });
 
-pub static DEVICE_CONNECTION: OnceLock<Mutex<HidDevice>> = OnceLock::new();
+/// How often a write is repeated when the read-back value doesn't match.
+pub const DEFAULT_WRITE_RETRIES: usize = 2;
+
+/// How often long-running frontends should call `ensure_connected()`.
+pub const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Environment variable that opts in to resetting and writing to models
+/// whose feature map is unverified, which are read-only otherwise.
+pub const UNVERIFIED_WRITES_VAR: &str = "LINUXBLASTER_UNVERIFIED_WRITES";
+
+/// How long the state has to stay unchanged before it is saved
+/// as the last known state, so dragging a slider writes the file once.
+const PERSIST_DELAY: Duration = Duration::from_secs(2);
+
+/// How long after a reconnect values reported by the device are not
+/// saved as the last known state, they may be what a reset left behind.
+const RECONNECT_SETTLE: Duration = Duration::from_secs(5);
+
+/// How long after the Output changed `0x6e` notifications are ignored,
+/// the device sends one while switching (see UsbProtocol.md).
+const OUTPUT_SETTLE: Duration = Duration::from_secs(1);
 
 #[derive(Serialize, Deserialize)]
 pub struct BlasterXG6 {
     pub features: Vec<Feature>,
+
+    /// Number of retries for writes that fail verification.
+    /// See `set_feature()` and `Transaction::commit()`.
+    #[serde(skip)]
+    pub write_retries: usize,
+
+    #[serde(skip)]
+    subscribers: Mutex<Vec<Sender<FeatureChange>>>,
+
+    /// Last reported headphone jack state, see `headphones_plugged()`.
+    #[serde(skip)]
+    headphones: Mutex<Option<bool>>,
+
+    /// When the last change not yet saved as the last known state happened.
+    #[serde(skip)]
+    unsaved_since: Mutex<Option<Instant>>,
+
+    #[serde(skip)]
+    reconnected_at: Mutex<Option<Instant>>,
+
+    #[serde(skip)]
+    output_changed_at: Mutex<Option<Instant>>,
+
+    /// Set for unverified models, see `is_read_only()`.
+    #[serde(skip)]
+    read_only: bool,
+
+    /// Set if the device is owned by `linuxblasterd`, see `attach()`.
+    #[serde(skip)]
+    remote: Option<Remote>,
+
+    #[serde(skip)]
+    connection: Connection,
 }
 
 impl BlasterXG6 {
+    /// Opens the first supported device found.
+    ///
+    /// Panics if there is none, see `open()` for the fallible version.
     pub fn init() -> Self {
-        let device_connection = DEVICE_CONNECTION.get_or_init(|| {
-            Self::reset_usb();
-
-            let api = HidApi::new().expect("Failed to create HID API");
-            let device =
-                Self::find_device(&api).expect("Failed to find device");
-            let connection = device
-                .open_device(&api)
-                .expect("Failed to open device connection");
-            Mutex::new(connection)
+        Self::open(&DeviceSelector::First).expect("Failed to open device")
+    }
+
+    /// Lists every connected device of a known `DeviceModel`.
+    pub fn enumerate() -> Result<Vec<DeviceDescriptor>, Box<dyn Error>> {
+        let api = HidApi::new()?;
+        Ok(Self::matching_devices(&api)
+            .map(DeviceDescriptor::from_info)
+            .collect())
+    }
+
+    /// Opens the device matching `selector`,
+    /// resets it and reads its current state.
+    ///
+    /// Every `BlasterXG6` owns its own connection,
+    /// so multiple devices can be opened side by side.
+    pub fn open(selector: &DeviceSelector) -> Result<Self, Box<dyn Error>> {
+        Self::open_with(selector, true)
+    }
+
+    /// Like `open()`, but leaves the USB connection alone.
+    ///
+    /// For short-lived callers like `blasterctl`, where resetting the device
+    /// on every invocation would interrupt playback for nothing.
+    pub fn open_without_reset(
+        selector: &DeviceSelector,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::open_with(selector, false)
+    }
+
+    fn open_with(
+        selector: &DeviceSelector,
+        reset: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let api = HidApi::new()?;
+        let descriptor =
+            DeviceDescriptor::from_info(&Self::find_device(&api, selector)?);
+
+        // models without a confirmed feature map are only read
+        let read_only = descriptor.model().is_some_and(|model| {
+            !model.verified && std::env::var_os(UNVERIFIED_WRITES_VAR).is_none()
         });
+        if reset && !read_only {
+            Self::reset_usb(&descriptor)?;
+        }
 
-        let features = features::all_features();
+        // the reset re-enumerates the device, so look it up again
+        let mut api = api;
+        api.refresh_devices()?;
+        let reselect = DeviceSelector::for_descriptor(&descriptor);
+        let descriptor =
+            DeviceDescriptor::from_info(&Self::find_device(&api, &reselect)?);
+        info!("Opening {}", descriptor);
 
-        if device_connection
-            .lock()
-            .unwrap()
-            .set_blocking_mode(false)
-            .is_err()
-        {
+        let connection = Connection::open(&api, &descriptor)?;
+        let model = connection.model();
+        if read_only {
+            warn!(
+                "Support for the {} is unverified, opening it read-only",
+                model
+            );
+        } else if !model.verified {
+            warn!(
+                "Support for the {} is unverified, expect missing or wrong values",
+                model
+            );
+        }
+
+        if connection.set_blocking_mode(false).is_err() {
             warn!("Failed to set blocking mode to false");
             warn!("Continuing with blocking mode in unknown state...");
         }
+        features::probe_batching(&connection);
 
-        let blaster = Self { features };
+        let blaster = Self {
+            features: features::features_for(model),
+            write_retries: DEFAULT_WRITE_RETRIES,
+            subscribers: Mutex::new(Vec::new()),
+            headphones: Mutex::new(None),
+            unsaved_since: Mutex::new(None),
+            reconnected_at: Mutex::new(None),
+            output_changed_at: Mutex::new(None),
+            read_only,
+            remote: None,
+            connection,
+        };
         blaster.read_state_from_device();
-        blaster
+
+        Ok(blaster)
     }
 
-    pub fn reset_usb() {
-        let Some(handle) =
-            rusb::open_device_with_vid_pid(VENDOR_ID, PRODUCT_ID)
-        else {
-            error!("Failed to open device for USB reset");
+    /// Attaches to the device `linuxblasterd` owns, through its socket
+    /// (see `socket::path()`), since it can't be opened while the daemon runs.
+    ///
+    /// Reads and writes go through the daemon, `poll_notifications()`
+    /// follows the changes it reports. Restoring and saving the last known
+    /// state is left to the daemon.
+    pub fn attach() -> Result<Self, Box<dyn Error>> {
+        Self::attach_at(&socket::path())
+    }
+
+    pub(crate) fn attach_at(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let (remote, model) = Remote::connect(path)?;
+        info!("Attached to the {} owned by linuxblasterd", model);
+
+        let blaster = Self {
+            features: features::features_for(model),
+            write_retries: DEFAULT_WRITE_RETRIES,
+            subscribers: Mutex::new(Vec::new()),
+            headphones: Mutex::new(None),
+            unsaved_since: Mutex::new(None),
+            reconnected_at: Mutex::new(None),
+            output_changed_at: Mutex::new(None),
+            read_only: false,
+            remote: Some(remote),
+            connection: Connection::detached(model),
+        };
+        blaster.read_state_from_device();
+        Ok(blaster)
+    }
+
+    /// Whether this instance is attached to the daemon, see `attach()`.
+    pub fn is_attached(&self) -> bool {
+        self.remote.is_some()
+    }
+
+    /// Calls `restore_last_state()` if `Config::restore_last_state` is on.
+    ///
+    /// Frontends that own the device call this right after `open()`,
+    /// `ensure_connected()` calls it after reconnecting.
+    pub fn restore_if_configured(&self) {
+        // a USB or firmware reset may have clobbered the state
+        if !Config::load().restore_last_state {
             return;
+        }
+        match self.restore_last_state() {
+            Ok(true) => info!("Restored the last known state"),
+            Ok(false) => debug!("No last known state to restore"),
+            Err(error) => warn!("Failed to restore last state: {}", error),
+        }
+    }
+
+    /// Re-opens the device after it went away, e.g. after replugging it
+    /// or a firmware reset, see `open()`.
+    /// Attached instances attach again, e.g. after the daemon restarted.
+    ///
+    /// Subscribers are kept and receive the re-read values
+    /// as `ChangeSource::Refresh` changes.
+    pub fn reconnect(&mut self) -> Result<(), Box<dyn Error>> {
+        let selector = self
+            .descriptor()
+            .map(DeviceSelector::for_descriptor)
+            .unwrap_or(DeviceSelector::First);
+        let reopened = match &self.remote {
+            Some(remote) => Self::attach_at(remote.path())?,
+            None => Self::open(&selector)?,
+        };
+
+        let same_model = reopened.model() == self.model();
+        let mut connection = reopened.connection;
+        connection.share_stats(&self.connection);
+        connection
+            .stats()
+            .reconnects
+            .fetch_add(1, Ordering::Relaxed);
+        self.connection = connection;
+        self.remote = reopened.remote;
+        *self.reconnected_at.lock().unwrap() = Some(Instant::now());
+        if !same_model {
+            self.features = reopened.features;
+            return Ok(());
+        }
+
+        self.observe(ChangeSource::Refresh, || {
+            for (feature, fresh) in self.features.iter().zip(&reopened.features)
+            {
+                feature.set_cached(fresh.value());
+            }
+        });
+        Ok(())
+    }
+
+    /// Calls `reconnect()` if `is_connected()` fails,
+    /// and `restore_if_configured()` once it succeeded.
+    /// Returns whether the device is connected afterwards.
+    ///
+    /// Meant to be called every few seconds,
+    /// the GUI and the headless mode both do.
+    pub fn ensure_connected(&mut self) -> bool {
+        let connected = self.is_connected()
+            || match self.reconnect() {
+                Ok(()) => {
+                    info!("Reconnected to {}", self.model());
+                    self.restore_if_configured();
+                    true
+                }
+                Err(error) => {
+                    debug!("Reconnect failed: {}", error);
+                    false
+                }
+            };
+        self.link_stats()
+            .connected
+            .store(connected, Ordering::Relaxed);
+        connected
+    }
+
+    /// ACK failures, reconnects and HID latencies, see `metrics`.
+    pub fn link_stats(&self) -> &LinkStats {
+        self.connection.stats()
+    }
+
+    /// The device this instance is connected to.
+    pub fn descriptor(&self) -> Option<&DeviceDescriptor> {
+        self.connection.descriptor()
+    }
+
+    pub fn model(&self) -> &'static DeviceModel {
+        self.connection.model()
+    }
+
+    /// Whether the connected model has the feature.
+    /// Unsupported features are not registered, so `feature()` panics for them.
+    pub fn supports(&self, id: FeatureId) -> bool {
+        self.model().supports(id)
+    }
+
+    /// Whether writes are refused with a `ReadOnlyError`:
+    /// the model is unverified and `UNVERIFIED_WRITES_VAR` wasn't set.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub(crate) fn check_writable(&self) -> Result<(), ReadOnlyError> {
+        if self.read_only {
+            Err(ReadOnlyError {
+                model: self.model().name,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn check_supported(
+        &self,
+        id: FeatureId,
+    ) -> Result<(), UnsupportedFeatureError> {
+        if self.supports(id) {
+            Ok(())
+        } else {
+            Err(UnsupportedFeatureError {
+                id,
+                model: self.model().name,
+            })
+        }
+    }
+
+    /// Resets the USB device behind `descriptor`.
+    ///
+    /// The device is matched by its USB port, then by its serial number.
+    /// Only if neither works and it is the only device with its VID/PID
+    /// is that one reset, so another attached unit is never reset instead.
+    pub fn reset_usb(
+        descriptor: &DeviceDescriptor,
+    ) -> Result<(), Box<dyn Error>> {
+        let device = match descriptor
+            .usb_port()
+            .and_then(|port| Self::usb_device_at(descriptor, port))
+        {
+            Some(device) => device,
+            None => {
+                warn!("Could not locate {} by USB port", descriptor);
+                Self::usb_device_like(descriptor)?
+            }
         };
+        let handle = device.open()?;
 
         info!("Resetting device...");
         let _ = handle.reset();
@@ -96,13 +436,122 @@ impl BlasterXG6 {
         // and the device sends some initial data we don't care about
         std::thread::sleep(std::time::Duration::from_secs(2));
         info!("Device reset complete.");
+        Ok(())
+    }
+
+    /// Finds the `rusb` device by serial number,
+    /// or the only one with the VID/PID of `target`.
+    fn usb_device_like(
+        target: &DeviceDescriptor,
+    ) -> Result<rusb::Device<rusb::GlobalContext>, Box<dyn Error>> {
+        let candidates: Vec<_> = rusb::devices()?
+            .iter()
+            .filter(|device| {
+                device.device_descriptor().is_ok_and(|descriptor| {
+                    descriptor.vendor_id() == target.vendor_id
+                        && descriptor.product_id() == target.product_id
+                })
+            })
+            .collect();
+
+        if let Some(serial) = &target.serial {
+            let matching = candidates.iter().find(|device| {
+                let (Ok(handle), Ok(descriptor)) =
+                    (device.open(), device.device_descriptor())
+                else {
+                    return false;
+                };
+                handle
+                    .read_serial_number_string_ascii(&descriptor)
+                    .is_ok_and(|found| &found == serial)
+            });
+            if let Some(device) = matching {
+                return Ok(device.clone());
+            }
+        }
+
+        match candidates.as_slice() {
+            [device] => Ok(device.clone()),
+            [] => Err(format!("{} is not on the USB bus", target).into()),
+            _ => Err(format!(
+                "Can't tell which of {} devices is {}, not resetting any",
+                candidates.len(),
+                target
+            )
+            .into()),
+        }
+    }
+
+    /// Finds the `rusb` device at `port` (`<bus>-<port>.<port>...`).
+    fn usb_device_at(
+        target: &DeviceDescriptor,
+        port: &str,
+    ) -> Option<rusb::Device<rusb::GlobalContext>> {
+        rusb::devices().ok()?.iter().find(|device| {
+            let Ok(descriptor) = device.device_descriptor() else {
+                return false;
+            };
+            let Ok(ports) = device.port_numbers() else {
+                return false;
+            };
+            let ports: Vec<String> =
+                ports.iter().map(|port| port.to_string()).collect();
+
+            descriptor.vendor_id() == target.vendor_id
+                && descriptor.product_id() == target.product_id
+                && format!("{}-{}", device.bus_number(), ports.join("."))
+                    == port
+        })
     }
 
-    /// Queries every feature from hardware individually.
+    /// Queries every feature from hardware.
     /// This will update the internal state of the features with the current hardware values.
+    ///
+    /// DSP features are read in batches (see `features::dsp_get_many()`),
+    /// everything else is queried individually.
     pub fn read_state_from_device(&self) {
-        for feature in &self.features {
-            feature.read_from_device();
+        self.observe(ChangeSource::Refresh, || self.refresh_state());
+    }
+
+    /// `read_state_from_device()` without emitting `FeatureChange`s,
+    /// for use inside already observed operations.
+    pub(crate) fn refresh_state(&self) {
+        let features: Vec<&Feature> = self.features.iter().collect();
+        self.read_features_from_device(&features);
+    }
+
+    /// Queries the given features from hardware,
+    /// batching DSP features into as few round-trips as possible.
+    pub(crate) fn read_features_from_device(&self, features: &[&Feature]) {
+        if let Some(remote) = &self.remote {
+            match remote.state() {
+                Ok(state) => {
+                    for feature in features {
+                        if let Some(&(_, value)) =
+                            state.iter().find(|(id, _)| *id == feature.id)
+                        {
+                            feature.set_cached(value);
+                        }
+                    }
+                }
+                Err(error) => warn!("Failed to read state: {}", error),
+            }
+            return;
+        }
+
+        let (dsp, other): (Vec<&Feature>, Vec<&Feature>) =
+            features.iter().partition(|feature| {
+                self.model().dsp_address(feature.id).is_some()
+            });
+
+        let read =
+            features::dsp_get_many(&self.connection, &dsp).and_then(|()| {
+                other.iter().try_for_each(|feature| {
+                    feature.read_from_device(&self.connection).map(drop)
+                })
+            });
+        if let Err(error) = read {
+            warn!("Failed to read state: {}", error);
         }
     }
 
@@ -114,17 +563,45 @@ impl BlasterXG6 {
             .unwrap_or_else(|| panic!("Feature {:?} not registered", id))
     }
 
+    /// The cached value of `id`.
+    /// Unlike `feature()`, returns an error for unsupported features.
+    pub fn feature_value(
+        &self,
+        id: FeatureId,
+    ) -> Result<f32, UnsupportedFeatureError> {
+        self.check_supported(id)?;
+        Ok(self.feature(id).value())
+    }
+
     /// Managed Write:
     /// enable dependencies, write, confirm, re-query dependents.
     ///
+    /// The value read back from the device is compared to the requested one
+    /// (see `ValueKind::tolerance()`). On mismatch, the write is repeated
+    /// up to `write_retries` times before a `MismatchError` is returned.
+    ///
     /// Automatically calls `read_state_from_device()` after switching the Output.
     ///
     /// Pass `None` for value to toggle (flip between 0.0 and 1.0).
+    ///
+    /// Returns an `UnsupportedFeatureError` if the model doesn't have `id`,
+    /// a `ReadOnlyError` for unverified models,
+    /// and an error for values outside of its `ValueKind`.
     pub fn set_feature(
         &self,
         id: FeatureId,
         value: Option<f32>,
     ) -> Result<(), Box<dyn Error>> {
+        self.observe(ChangeSource::Local, || self.write_feature(id, value))
+    }
+
+    fn write_feature(
+        &self,
+        id: FeatureId,
+        value: Option<f32>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_supported(id)?;
+        self.check_writable()?;
         let feature = self.feature(id);
 
         let actual_value = match value {
@@ -140,52 +617,364 @@ impl BlasterXG6 {
 
         debug!("set_feature: {} = {}", id, actual_value);
 
+        let value_kind = self.model().value_kind(id);
+        if !value_kind.contains(actual_value) {
+            return Err(format!(
+                "{} is out of range for {}, expected {}",
+                actual_value,
+                id,
+                value_kind.describe()
+            )
+            .into());
+        }
+
+        if let Some(remote) = &self.remote {
+            remote.set(id, actual_value)?;
+            // dependencies, or everything after switching the Output
+            self.refresh_state();
+            return Ok(());
+        }
+
         for &dependency_id in id.dependencies() {
             let dependency = self.feature(dependency_id);
             if dependency.value() != 1.0 {
                 debug!("Enabling dependency: {} for {}", dependency_id, id);
-                self.set_feature(dependency_id, Some(1.0))?;
+                self.write_feature(dependency_id, Some(1.0))?;
             }
         }
 
-        feature.write_to_device(actual_value);
-        feature.read_from_device();
-
-        for &dependent_id in id.dependents() {
-            self.feature(dependent_id).read_from_device();
+        let mut attempts = 0;
+        let mut read_back = f32::NAN;
+        while attempts <= self.write_retries {
+            attempts += 1;
+            feature.write_to_device(&self.connection, actual_value)?;
+            read_back = feature.read_from_device(&self.connection)?;
+            if value_kind.matches(actual_value, read_back) {
+                break;
+            }
+            warn!(
+                "Write verification failed for {} (requested {}, got {}), attempt {}/{}",
+                id,
+                actual_value,
+                read_back,
+                attempts,
+                self.write_retries + 1
+            );
         }
 
+        let dependents: Vec<&Feature> = id
+            .dependents()
+            .iter()
+            .filter(|&&dependent_id| self.supports(dependent_id))
+            .map(|&dependent_id| self.feature(dependent_id))
+            .collect();
+        self.read_features_from_device(&dependents);
+
         // changing the output changes the internal settings profile
         if feature.id == FeatureId::Output {
-            self.read_state_from_device();
+            self.refresh_state();
+        }
+
+        if !value_kind.matches(actual_value, read_back) {
+            return Err(Box::new(MismatchError {
+                id,
+                requested: actual_value,
+                actual: read_back,
+                attempts,
+            }));
         }
 
         Ok(())
     }
 
-    pub fn find_device(api: &HidApi) -> Result<DeviceInfo, Box<dyn Error>> {
-        let device = api
-            .device_list()
+    /// Returns a channel that receives a `FeatureChange` for every
+    /// cached value that changes from now on, no matter the `ChangeSource`.
+    ///
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<FeatureChange> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Runs `operation` and notifies subscribers about
+    /// every cached value it changed.
+    pub(crate) fn observe<T>(
+        &self,
+        source: ChangeSource,
+        operation: impl FnOnce() -> T,
+    ) -> T {
+        let before: Vec<f32> =
+            self.features.iter().map(Feature::value).collect();
+
+        let result = operation();
+
+        let changes: Vec<FeatureChange> = self
+            .features
+            .iter()
+            .zip(before)
+            .filter_map(|(feature, old)| {
+                let new = feature.value();
+                let changed = old != new && !(old.is_nan() && new.is_nan());
+                changed.then_some(FeatureChange {
+                    id: feature.id,
+                    old,
+                    new,
+                    source,
+                })
+            })
+            .collect();
+
+        if changes.iter().any(|change| change.id == FeatureId::Output) {
+            *self.output_changed_at.lock().unwrap() = Some(Instant::now());
+        }
+        if !changes.is_empty() {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            for change in changes {
+                debug!("{:?}", change);
+                subscribers
+                    .retain(|subscriber| subscriber.send(change).is_ok());
+            }
+            drop(subscribers);
+
+            // re-reads don't count as known good, and neither does what
+            // the device reports right after coming back: after a firmware
+            // reset they'd overwrite what's worth restoring.
+            // Attached instances leave this to the daemon.
+            let settling = self
+                .reconnected_at
+                .lock()
+                .unwrap()
+                .is_some_and(|at| at.elapsed() < RECONNECT_SETTLE);
+            let known_good = match source {
+                ChangeSource::Local => true,
+                ChangeSource::Device => !settling,
+                ChangeSource::Refresh => false,
+            };
+            if known_good && self.remote.is_none() {
+                *self.unsaved_since.lock().unwrap() = Some(Instant::now());
+            }
+        }
+
+        result
+    }
+
+    /// Saves pending changes as the last known state right away,
+    /// e.g. before exiting. Otherwise `poll_notifications()` saves them
+    /// once the state didn't change for a moment.
+    pub fn save_last_state(&self) {
+        if self.unsaved_since.lock().unwrap().take().is_some() {
+            self.persist_last_state();
+        }
+    }
+
+    /// Updates the last known state of the active Output,
+    /// see `snapshot::path()`.
+    fn persist_last_state(&self) {
+        let Some(descriptor) = self.descriptor() else {
+            return;
+        };
+        let path = snapshot::path(descriptor);
+
+        let metadata = ProfileMetadata {
+            name: "Last state".to_string(),
+            created: profile::now(),
+            model: Some(self.model().name.to_string()),
+            output: self.output_label().map(str::to_string),
+            ..Default::default()
+        };
+        let previous =
+            path.exists().then(|| Profile::load(&path).ok()).flatten();
+        let snapshot = snapshot::merge(previous, metadata, self.current_bank());
+
+        let result = snapshot.to_json().and_then(|json| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, json)?;
+            Ok(())
+        });
+        match result {
+            Ok(()) => debug!("Saved last state to {:?}", path),
+            Err(error) => warn!("Failed to save last state: {}", error),
+        }
+    }
+
+    /// The last known state of this device, if one was saved.
+    pub fn last_state(&self) -> Option<Profile> {
+        let path = snapshot::path(self.descriptor()?);
+        if !path.exists() {
+            return None;
+        }
+        Profile::load(&path)
+            .inspect_err(|error| warn!("Failed to load last state: {}", error))
+            .ok()
+    }
+
+    /// Re-applies `last_state()`, including the Output that was active.
+    /// Returns `false` if there is none.
+    pub fn restore_last_state(&self) -> Result<bool, Box<dyn Error>> {
+        let Some(last_state) = self.last_state() else {
+            return Ok(false);
+        };
+
+        // switch first, so `apply()` ends up on this Output
+        let output = last_state.metadata.output.as_deref().and_then(|label| {
+            self.model().value_kind(FeatureId::Output).value_of(label)
+        });
+        if let Some(output) = output
+            && self.supports(FeatureId::Output)
+        {
+            self.set_feature(FeatureId::Output, Some(output))?;
+        }
+
+        self.apply(&last_state)?;
+        Ok(true)
+    }
+
+    /// Processes packets the device sent on its own,
+    /// e.g. after the physical SBX button was pressed.
+    ///
+    /// Does not block; call this regularly (the GUI does so every frame).
+    pub fn poll_notifications(&self) {
+        if let Some(remote) = &self.remote {
+            for change in remote.changes() {
+                if self.supports(change.id) {
+                    self.observe(change.source, || {
+                        self.feature(change.id).set_cached(change.new)
+                    });
+                }
+            }
+            return;
+        }
+
+        let mut jack = None;
+        self.observe(ChangeSource::Device, || {
+            let mut output_changed = false;
+
+            while let Some(packet) = features::try_read_packet(&self.connection)
+            {
+                for (id, value) in
+                    features::decode_notification(self.model(), &packet)
+                {
+                    let feature = self.feature(id);
+                    if id == FeatureId::Output && feature.value() != value {
+                        output_changed = true;
+                    }
+                    feature.set_cached(value);
+                }
+                if let Some(plugged) = features::decode_jack(&packet) {
+                    jack = Some(plugged);
+                }
+            }
+
+            // the other settings profile is active now
+            if output_changed {
+                self.refresh_state();
+            }
+        });
+
+        let switching = self
+            .output_changed_at
+            .lock()
+            .unwrap()
+            .is_some_and(|at| at.elapsed() < OUTPUT_SETTLE);
+        match jack {
+            Some(_) if switching => {
+                debug!("Ignoring jack notification after an Output change")
+            }
+            Some(plugged) => {
+                *self.headphones.lock().unwrap() = Some(plugged);
+                self.follow_jack(plugged);
+            }
+            None => {}
+        }
+
+        let idle = self
+            .unsaved_since
+            .lock()
+            .unwrap()
+            .is_some_and(|since| since.elapsed() >= PERSIST_DELAY);
+        if idle {
+            self.save_last_state();
+        }
+    }
+
+    /// Whether headphones are plugged in,
+    /// `None` until the device reported a change.
+    pub fn headphones_plugged(&self) -> Option<bool> {
+        *self.headphones.lock().unwrap()
+    }
+
+    /// With `Config::auto_switch_output`, logs the Output matching the jack
+    /// and the profile from `Config::output_profiles` it would switch to.
+    ///
+    /// Log-only: `decode_jack()` is a guess until the `0x6e` semantics
+    /// are confirmed from captures, and switching on a wrong guess
+    /// would fight the user's own Output switches.
+    fn follow_jack(&self, plugged: bool) {
+        let config = Config::load();
+        if !config.auto_switch_output {
+            return;
+        }
+        let output = if plugged { "Headphones" } else { "Speakers" };
+        if self.output_label() == Some(output) {
+            return;
+        }
+
+        let jack = if plugged { "plugged in" } else { "removed" };
+        match config.output_profiles.get(output) {
+            Some(name) => info!(
+                "Headphones {}, would switch to {} and apply {:?}",
+                jack, output, name
+            ),
+            None => info!("Headphones {}, would switch to {}", jack, output),
+        }
+    }
+
+    /// Starts a `Transaction` for applying multiple values at once,
+    /// with a single dependency resolution, verification and rollback.
+    pub fn transaction(&self) -> Transaction<'_> {
+        Transaction::new(self)
+    }
+
+    /// The control interface of every known model found by `api`.
+    fn matching_devices(api: &HidApi) -> impl Iterator<Item = &DeviceInfo> {
+        api.device_list().filter(|device| {
+            debug!("Checking device: {:04x?}", device);
+            DeviceModel::from_ids(device.vendor_id(), device.product_id())
+                .is_some_and(|model| {
+                    device.interface_number() == model.interface
+                })
+        })
+    }
+
+    pub fn find_device(
+        api: &HidApi,
+        selector: &DeviceSelector,
+    ) -> Result<DeviceInfo, Box<dyn Error>> {
+        let device = Self::matching_devices(api)
             .find(|device| {
-                debug!("Checking device: {:04x?}", device);
-                device.vendor_id() == VENDOR_ID
-                    && device.product_id() == PRODUCT_ID
-                    && device.interface_number() == INTERFACE
+                selector.matches(&DeviceDescriptor::from_info(device))
             })
             .ok_or_else(|| {
                 Box::new(std::io::Error::new(
                     ErrorKind::NotFound,
-                    "Device not found",
+                    format!("Device not found ({:?})", selector),
                 ))
             })
             .cloned()?;
         Ok(device)
     }
 
+    /// Whether the device still answers.
+    ///
+    /// Sends a firmware version request and reads its response.
     pub fn is_connected(&self) -> bool {
-        // check if device connection is healthy
-
-        todo!()
+        match &self.remote {
+            Some(remote) => remote.is_connected(),
+            None => features::ping(&self.connection),
+        }
     }
 
     /// ### Important
@@ -202,55 +991,309 @@ impl BlasterXG6 {
     /// but I figured it'd be worth noting down.
     pub fn reset(&self) -> Result<(), Box<dyn Error>> {
         // yeah the return type might be stupid, I'll fix it soon™️
-        let features: Vec<Feature> = features::all_features();
-        features.iter().for_each(|feature| {
-            if feature.id == FeatureId::Output {
-                return;
-            }
-            feature.write_to_device(0.0);
-        });
+        if let Some(remote) = &self.remote {
+            remote.reset()?;
+            self.observe(ChangeSource::Local, || self.refresh_state());
+            return Ok(());
+        }
+        self.check_writable()?;
+        self.observe(ChangeSource::Local, || {
+            let features: Vec<Feature> = features::features_for(self.model());
+            let written = features
+                .iter()
+                .filter(|feature| feature.id != FeatureId::Output)
+                .try_for_each(|feature| {
+                    feature.write_to_device(&self.connection, 0.0)
+                });
 
-        self.read_state_from_device();
+            self.refresh_state();
+            written
+        })?;
 
         Ok(())
     }
 
-    pub fn save_profile(&self, path: PathBuf) -> Result<(), Box<dyn Error>> {
-        // save the current state of the features to a profile
+    /// Queries the firmware version string from the device.
+    pub fn firmware_version(&self) -> Option<String> {
+        match &self.remote {
+            Some(remote) => remote.firmware(),
+            None => features::firmware_version(&self.connection),
+        }
+    }
 
-        let json = serde_json::to_string_pretty(&self)?;
-        std::fs::write(&path, json)?;
-        info!("Saved Profile to {:?}", path);
+    /// The label of the active Output ("Speakers", "Headphones"),
+    /// `None` for models without Output switching.
+    pub fn output_label(&self) -> Option<&'static str> {
+        if !self.supports(FeatureId::Output) {
+            return None;
+        }
+        self.model()
+            .value_kind(FeatureId::Output)
+            .label(self.feature(FeatureId::Output).value())
+    }
 
-        Ok(())
+    /// Switches to the Output labelled `output` ("Speakers", "Headphones"),
+    /// or to the next one if `output` is `None`.
+    pub fn switch_output(
+        &self,
+        output: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_supported(FeatureId::Output)?;
+        let ValueKind::Preset(labels) =
+            self.model().value_kind(FeatureId::Output)
+        else {
+            return Err("Output is not a preset".into());
+        };
+
+        let value = match output {
+            Some(label) => labels
+                .iter()
+                .position(|&candidate| candidate == label)
+                .ok_or_else(|| format!("Unknown Output: {}", label))?
+                as f32,
+            None => {
+                let current = self.feature(FeatureId::Output).value();
+                (current + 1.0) % labels.len() as f32
+            }
+        };
+        self.set_feature(FeatureId::Output, Some(value))
     }
 
-    pub fn apply_profile(&self, path: PathBuf) -> Result<(), Box<dyn Error>> {
-        // apply a profile to the features
+    /// The cached values of the active Output's settings bank.
+    /// Features that couldn't be read are left out.
+    fn current_bank(&self) -> ProfileBank {
+        ProfileBank {
+            output: self.output_label().map(str::to_string),
+            values: self
+                .features
+                .iter()
+                .filter(|feature| {
+                    feature.id != FeatureId::Output && !feature.value().is_nan()
+                })
+                .map(|feature| ProfileValue {
+                    id: feature.id,
+                    value: feature.value(),
+                })
+                .collect(),
+        }
+    }
 
-        let json = std::fs::read_to_string(&path)?;
-        let saved: BlasterXG6 = serde_json::from_str(&json)?;
+    fn profile_metadata(&self, name: &str) -> ProfileMetadata {
+        ProfileMetadata {
+            name: name.to_string(),
+            created: profile::now(),
+            firmware: self.firmware_version(),
+            model: Some(self.model().name.to_string()),
+            output: self.output_label().map(str::to_string),
+            ..Default::default()
+        }
+    }
 
-        for feature in &saved.features {
-            // don't write features that haven't been changed from defautl
-            if feature.value() == 0.0 {
-                continue;
+    /// Captures the cached state of the active Output as a `Profile`.
+    pub fn to_profile(&self, name: &str) -> Profile {
+        Profile::new(self.profile_metadata(name), vec![self.current_bank()])
+    }
+
+    /// Captures the settings of every Output as a `Profile`.
+    ///
+    /// Switches through the Outputs to read their banks,
+    /// and switches back to the active one afterwards.
+    pub fn to_profile_all_outputs(
+        &self,
+        name: &str,
+    ) -> Result<Profile, Box<dyn Error>> {
+        let metadata = self.profile_metadata(name);
+        let mut banks = vec![self.current_bank()];
+
+        if !self.supports(FeatureId::Output) {
+            return Ok(Profile::new(metadata, banks));
+        }
+        let output_count = match self.model().value_kind(FeatureId::Output) {
+            ValueKind::Preset(labels) => labels.len(),
+            _ => 1,
+        };
+
+        let active = self.feature(FeatureId::Output).value();
+        let result = (0..output_count)
+            .map(|index| index as f32)
+            .filter(|&output| output != active)
+            .try_for_each(|output| {
+                self.set_feature(FeatureId::Output, Some(output))?;
+                banks.push(self.current_bank());
+                Ok::<(), Box<dyn Error>>(())
+            });
+        self.restore_output(active)?;
+        result?;
+
+        Ok(Profile::new(metadata, banks))
+    }
+
+    /// Whether the cached state equals `profile` (within tolerance).
+    ///
+    /// Only the bank of the active Output is compared,
+    /// profiles without a bank for it never match.
+    pub fn matches_profile(&self, profile: &Profile) -> bool {
+        let bank = profile.bank_for(self.output_label());
+
+        bank.is_some_and(|bank| {
+            bank.values.iter().all(|&ProfileValue { id, value }| {
+                !self.supports(id)
+                    || self
+                        .model()
+                        .value_kind(id)
+                        .matches(value, self.feature(id).value())
+            })
+        })
+    }
+
+    /// What `apply(profile)` would change, feature by feature.
+    ///
+    /// Only the cached state of the active Output is known, so banks of
+    /// inactive Outputs are reported with an unknown current value
+    /// (`FeatureDiff::from` is `None`) instead of switching to them.
+    /// Features the model doesn't have, and features the profile
+    /// has no value for, are left out.
+    pub fn diff(&self, profile: &Profile) -> ProfileDiff {
+        let current =
+            Profile::new(ProfileMetadata::default(), vec![self.current_bank()]);
+
+        // features the profile has no value for are left alone
+        let mut diff = ProfileDiff::between(self.model(), &current, profile);
+        diff.changes
+            .retain(|change| change.to.is_some() && self.supports(change.id));
+        diff
+    }
+
+    /// Brings the device into exactly the state saved in `profile`,
+    /// no matter what state it was in before:
+    /// toggles are switched off, sliders behind disabled toggles are
+    /// still written, EQ bands are zeroed, and so on.
+    ///
+    /// Each bank is written in one `Transaction` on its own Output,
+    /// so only values that differ from the current state are sent,
+    /// and a bank that can't be reached is rolled back.
+    /// The Output that was active before is restored afterwards.
+    ///
+    /// Scoped profiles only touch the features in their scope,
+    /// see `Profile::stack()` for applying several layers at once.
+    /// Features the model doesn't have are skipped with a warning.
+    pub fn apply(&self, profile: &Profile) -> Result<(), Box<dyn Error>> {
+        // a hand-edited profile may have values outside its scope
+        let restricted;
+        let profile = match &profile.scope {
+            Some(scope) => {
+                restricted = profile.clone().restrict(scope);
+                &restricted
             }
+            None => profile,
+        };
 
-            // don't write sliders if their toggle is off
-            if matches!(feature.id.value_kind(), ValueKind::Ranged { .. }) {
-                if let Some(toggle_id) = feature.id.paired_toggle() {
-                    if self.feature(toggle_id).value() == 0.0 {
-                        continue;
-                    }
-                };
+        if let Some(remote) = &self.remote {
+            remote.apply(profile)?;
+            self.observe(ChangeSource::Local, || self.refresh_state());
+            return Ok(());
+        }
+
+        if !self.supports(FeatureId::Output) {
+            if profile.banks.len() > 1 {
+                warn!(
+                    "The {} has no Output switching, only applying the first bank",
+                    self.model()
+                );
+            }
+            return match profile.banks.first() {
+                Some(bank) => self.apply_bank(bank, None),
+                None => Ok(()),
+            };
+        }
+
+        let active = self.feature(FeatureId::Output).value();
+        let output_kind = self.model().value_kind(FeatureId::Output);
+
+        // the active Output's bank goes last, so restoring is a no-op
+        let mut banks: Vec<(&ProfileBank, Option<f32>)> = profile
+            .banks
+            .iter()
+            .map(|bank| {
+                let output = bank
+                    .output
+                    .as_deref()
+                    .and_then(|label| output_kind.value_of(label));
+                (bank, output)
+            })
+            .collect();
+        banks.sort_by_key(|&(_, output)| {
+            output.is_none_or(|output| output == active)
+        });
+
+        let result = banks
+            .into_iter()
+            .try_for_each(|(bank, output)| self.apply_bank(bank, output));
+        self.restore_output(active)?;
+        result
+    }
+
+    /// Writes one bank, on `output` if given, on the active Output otherwise.
+    fn apply_bank(
+        &self,
+        bank: &ProfileBank,
+        output: Option<f32>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut transaction = self.transaction();
+        if let Some(output) = output {
+            transaction.set(FeatureId::Output, output);
+        } else if let Some(label) = &bank.output {
+            warn!("Unknown Output {:?}, applying to the active one", label);
+        }
+
+        for &ProfileValue { id, value } in &bank.values {
+            if !self.supports(id) {
+                warn!("Skipping {}, not supported by the {}", id, self.model());
+                continue;
             }
+            transaction.set(id, value);
+        }
+        transaction.commit()
+    }
 
-            self.set_feature(feature.id, Some(feature.value()))?;
+    fn restore_output(&self, output: f32) -> Result<(), Box<dyn Error>> {
+        if self.feature(FeatureId::Output).value() != output {
+            self.set_feature(FeatureId::Output, Some(output))?;
         }
+        Ok(())
+    }
+
+    /// Saves the current state to `path`,
+    /// named after the file (without extension).
+    pub fn save_profile(&self, path: PathBuf) -> Result<(), Box<dyn Error>> {
+        self.to_profile(&profile_name(&path)).save(&path)
+    }
+
+    /// Like `save_profile()`, but with the banks of all Outputs,
+    /// see `to_profile_all_outputs()`.
+    pub fn save_profile_all_outputs(
+        &self,
+        path: PathBuf,
+    ) -> Result<(), Box<dyn Error>> {
+        self.to_profile_all_outputs(&profile_name(&path))?
+            .save(&path)
+    }
+
+    /// Applies a profile saved by any version of this application,
+    /// see `Profile::from_json()` and `apply()`.
+    pub fn apply_profile(&self, path: PathBuf) -> Result<(), Box<dyn Error>> {
+        let saved = Profile::load(&path)?;
+        self.apply(&saved)?;
 
         info!("Applied Profile ({:?})", path);
 
         Ok(())
     }
 }
+
+/// Profiles are named after their file (without extension).
+fn profile_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
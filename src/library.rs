@@ -0,0 +1,293 @@
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use tracing::{info, warn};
+
+use crate::DEFAULT_BASE_PATH;
+use crate::profile::{Profile, ProfileScope};
+
+/// A profile stored in the `ProfileLibrary`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    /// The file stem, which is what the library addresses the profile by.
+    pub name: String,
+    pub profile: Profile,
+}
+
+impl LibraryEntry {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `ProfileMetadata::name` for display,
+    /// the entry name if the profile doesn't have one.
+    pub fn label(&self) -> &str {
+        match self.profile.metadata.name.trim() {
+            "" => &self.name,
+            label => label,
+        }
+    }
+}
+
+/// The collection of profiles in one directory,
+/// one `<name>.json` file per profile.
+///
+/// Profiles are addressed by name, which is the file name without `.json`.
+/// Profiles saved here get their `ProfileMetadata::name` as the file name,
+/// files copied in by hand may carry a different one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileLibrary {
+    root: PathBuf,
+}
+
+impl Default for ProfileLibrary {
+    /// `DEFAULT_BASE_PATH/profiles`
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_PATH.join("profiles"))
+    }
+}
+
+impl ProfileLibrary {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The file a profile called `name` is stored in.
+    pub fn path_of(&self, name: &str) -> PathBuf {
+        let file_name: String = name
+            .chars()
+            .map(|char| match char {
+                '/' | '\\' | ':' | '\0' => '_',
+                char => char,
+            })
+            .collect();
+        self.root.join(format!("{}.json", file_name))
+    }
+
+    /// Every readable profile, favourites first, then by name.
+    /// Files that can't be parsed are skipped with a warning.
+    pub fn list(&self) -> Result<Vec<LibraryEntry>, Box<dyn Error>> {
+        let directory = match std::fs::read_dir(&self.root) {
+            Ok(directory) => directory,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                return Ok(Vec::new());
+            }
+            Err(error) => return Err(Box::new(error)),
+        };
+
+        let mut entries = Vec::new();
+        for file in directory {
+            let path = file?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            match Profile::load(&path) {
+                Ok(profile) => entries.push(LibraryEntry {
+                    path,
+                    name,
+                    profile,
+                }),
+                Err(error) => warn!("Skipping profile {:?}: {}", path, error),
+            }
+        }
+
+        entries.sort_by(|a, b| {
+            b.profile
+                .metadata
+                .favourite
+                .cmp(&a.profile.metadata.favourite)
+                .then_with(|| {
+                    a.label().to_lowercase().cmp(&b.label().to_lowercase())
+                })
+        });
+        Ok(entries)
+    }
+
+    /// The profile stored as `name`.
+    /// Its `ProfileMetadata::name` is filled in with `name` if it is empty.
+    pub fn load(&self, name: &str) -> Result<Profile, Box<dyn Error>> {
+        let mut profile = Profile::load(&self.path_of(name))?;
+        if profile.metadata.name.trim().is_empty() {
+            profile.metadata.name = name.to_string();
+        }
+        Ok(profile)
+    }
+
+    /// Loads `profile` from a file if it is a path to one,
+    /// from the library otherwise.
+    pub fn resolve(&self, profile: &str) -> Result<Profile, Box<dyn Error>> {
+        let path = Path::new(profile);
+        if path.is_file() {
+            return Profile::load(path);
+        }
+        self.load(profile)
+    }
+
+    /// `resolve()`, narrowed down to `scope` if there is one,
+    /// for settings that name a profile, like rules and schedules.
+    pub fn resolve_scoped(
+        &self,
+        profile: &str,
+        scope: Option<&[ProfileScope]>,
+    ) -> Result<Profile, Box<dyn Error>> {
+        let profile = self.resolve(profile)?;
+        Ok(match scope {
+            Some(scope) => profile.restrict(scope),
+            None => profile,
+        })
+    }
+
+    /// Stores `profile` under its name, replacing an existing one.
+    pub fn save(&self, profile: &Profile) -> Result<PathBuf, Box<dyn Error>> {
+        if profile.metadata.name.trim().is_empty() {
+            return Err("Profile name must not be empty".into());
+        }
+        std::fs::create_dir_all(&self.root)?;
+        let path = self.path_of(&profile.metadata.name);
+        profile.save(&path)?;
+        Ok(path)
+    }
+
+    pub fn rename(
+        &self,
+        name: &str,
+        new_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut profile = self.load(name)?;
+        profile.metadata.name = new_name.to_string();
+        self.create(&profile)?;
+        std::fs::remove_file(self.path_of(name))?;
+        info!("Renamed profile {:?} to {:?}", name, new_name);
+        Ok(())
+    }
+
+    pub fn duplicate(
+        &self,
+        name: &str,
+        new_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut profile = self.load(name)?;
+        profile.metadata.name = new_name.to_string();
+        profile.metadata.favourite = false;
+        self.create(&profile)?;
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        std::fs::remove_file(self.path_of(name))?;
+        info!("Deleted profile {:?}", name);
+        Ok(())
+    }
+
+    /// Replaces the tags of a profile.
+    /// Tags are trimmed, empty and duplicate tags are dropped.
+    pub fn set_tags(
+        &self,
+        name: &str,
+        tags: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut profile = self.load(name)?;
+        profile.metadata.tags.clear();
+        for tag in tags.iter().map(|tag| tag.trim()) {
+            if !tag.is_empty()
+                && !profile.metadata.tags.iter().any(|existing| existing == tag)
+            {
+                profile.metadata.tags.push(tag.to_string());
+            }
+        }
+        profile.save(&self.path_of(name))?;
+        Ok(())
+    }
+
+    pub fn set_favourite(
+        &self,
+        name: &str,
+        favourite: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut profile = self.load(name)?;
+        profile.metadata.favourite = favourite;
+        profile.save(&self.path_of(name))?;
+        Ok(())
+    }
+
+    /// Like `save()`, but fails instead of replacing an existing profile.
+    fn create(&self, profile: &Profile) -> Result<PathBuf, Box<dyn Error>> {
+        if self.path_of(&profile.metadata.name).exists() {
+            return Err(Box::new(std::io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("Profile {:?} already exists", profile.metadata.name),
+            )));
+        }
+        self.save(profile)
+    }
+}
+
+/// `ProfileLibrary::list()` for frontends that need it on every change,
+/// read again only once a profile file was added, removed or modified.
+#[derive(Debug, Clone)]
+pub struct CachedLibrary {
+    library: ProfileLibrary,
+    /// Every profile file and when it was modified, as of the last read.
+    modified: Option<Vec<(PathBuf, SystemTime)>>,
+    entries: Vec<LibraryEntry>,
+}
+
+impl Default for CachedLibrary {
+    fn default() -> Self {
+        Self::new(ProfileLibrary::default())
+    }
+}
+
+impl CachedLibrary {
+    pub fn new(library: ProfileLibrary) -> Self {
+        Self {
+            library,
+            modified: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// The entries of `list()`, empty if the library can't be read.
+    pub fn entries(&mut self) -> &[LibraryEntry] {
+        let modified = self.modified();
+        if modified.is_none() || modified != self.modified {
+            self.entries = self.library.list().unwrap_or_else(|error| {
+                warn!("Failed to list profiles: {}", error);
+                Vec::new()
+            });
+            self.modified = modified;
+        }
+        &self.entries
+    }
+
+    /// `None` if the directory can't be read, so it is listed every time.
+    fn modified(&self) -> Option<Vec<(PathBuf, SystemTime)>> {
+        let directory = match std::fs::read_dir(self.library.root()) {
+            Ok(directory) => directory,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                return Some(Vec::new());
+            }
+            Err(_) => return None,
+        };
+        let mut modified = directory
+            .map(|file| {
+                let file = file.ok()?;
+                Some((file.path(), file.metadata().ok()?.modified().ok()?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        modified.sort();
+        Some(modified)
+    }
+}
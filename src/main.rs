@@ -1,12 +1,15 @@
 #![allow(unused)]
 
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 
 use eframe::egui::{self, Vec2, ahash::HashMap, mutex::Mutex};
-use linuxblaster_control::BlasterXG6;
-use tracing::Level;
+use linuxblaster_control::{
+    BlasterXG6, Config, DeviceSelector, dbus, services, socket,
+};
+use tracing::{Level, warn};
 
 mod app;
+mod tray;
 use app::BlasterApp;
 
 fn main() -> eframe::Result<()> {
@@ -14,9 +17,63 @@ fn main() -> eframe::Result<()> {
         .with_max_level(Level::DEBUG)
         .init();
 
-    let device = BlasterXG6::init();
-    let app = BlasterApp(device);
+    let mut selector = DeviceSelector::First;
+    let mut headless = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list-devices" => {
+                for device in BlasterXG6::enumerate().unwrap_or_default() {
+                    println!("{}", device);
+                }
+                return Ok(());
+            }
+            "--headless" => headless = true,
+            "--device" => {
+                let Some(value) = args.next() else {
+                    eprintln!("--device requires a serial number or HID path");
+                    std::process::exit(2);
+                };
+                selector = value.parse().unwrap();
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                eprintln!("Usage: linuxblaster_control [--list-devices] [--headless] [--device <serial|path>]");
+                std::process::exit(2);
+            }
+        }
+    }
 
+    let daemon_running =
+        dbus::connect().is_some() || socket::Client::connect().is_some();
+    let device = if daemon_running {
+        if headless {
+            eprintln!("linuxblasterd is running and owns the device already.");
+            std::process::exit(1);
+        }
+        // the daemon runs rules, schedules and so on, the GUI only shows it
+        match BlasterXG6::attach() {
+            Ok(device) => device,
+            Err(error) => {
+                eprintln!("linuxblasterd is running and owns the device,");
+                eprintln!("but attaching to its socket failed: {}", error);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        let device = match BlasterXG6::open(&selector) {
+            Ok(device) => device,
+            Err(error) => {
+                eprintln!("Failed to open device: {}", error);
+                std::process::exit(1);
+            }
+        };
+        device.restore_if_configured();
+        device
+    };
+    if headless {
+        run_headless(device);
+    }
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_resizable(true)
@@ -38,11 +95,22 @@ fn main() -> eframe::Result<()> {
                 cc.egui_ctx.set_debug_on_hover(false);
             }
 
-            Ok(Box::new(app))
+            Ok(Box::new(BlasterApp::new(device, &cc.egui_ctx)))
         }),
     )
 }
 
+/// Keeps the device connected without a window:
+/// follows changes made on the device (so the last known state stays current)
+/// and reconnects, restoring that state if configured, when it reappears.
+/// Runs the same `services` as `linuxblasterd`.
+fn run_headless(device: BlasterXG6) -> ! {
+    let config = Config::load();
+    let device = Arc::new(std::sync::Mutex::new(device));
+    services::start(device.clone(), &config);
+    services::run_loop(device, &config, |_| {})
+}
+
 /// Results from "<headphone_name> FixedBandEQ.txt"
 /// mapped to ten bands (31Hz, 62Hz, 125Hz, 250Hz, 500Hz, 1kHz, 2kHz, 4kHz, 8kHz, 16kHz)
 #[derive(Debug, Clone)]
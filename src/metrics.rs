@@ -0,0 +1,251 @@
+//! OpenMetrics exporter for `linuxblasterd`, e.g. for Prometheus:
+//! every feature value, the Output, connection health,
+//! ACK failures and HID round-trip latencies.
+//!
+//! ```text
+//! curl http://127.0.0.1:9756/metrics
+//! ```
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use tracing::{debug, info, warn};
+
+use crate::dbus::SharedBlaster;
+use crate::{BlasterXG6, FeatureId, ValueKind};
+
+const CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Upper bounds of the round-trip histogram buckets, in seconds.
+pub const ROUND_TRIP_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// A latency histogram with the bounds from `ROUND_TRIP_BUCKETS`.
+#[derive(Debug, Default)]
+pub struct Histogram {
+    /// Per bucket, not cumulative, the last one is `+Inf`.
+    buckets: [AtomicU64; ROUND_TRIP_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let index = ROUND_TRIP_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(ROUND_TRIP_BUCKETS.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Cumulative counts per bucket, the last one is the total.
+    pub fn cumulative(&self) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .scan(0, |total, bucket| {
+                *total += bucket.load(Ordering::Relaxed);
+                Some(*total)
+            })
+            .collect()
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(self.sum_micros.load(Ordering::Relaxed))
+    }
+}
+
+/// Health of the HID link, kept across reconnects.
+#[derive(Debug)]
+pub struct LinkStats {
+    /// Writes the device didn't acknowledge.
+    pub ack_failures: AtomicU64,
+    pub reconnects: AtomicU64,
+    /// Result of the last `ensure_connected()`.
+    pub connected: AtomicBool,
+    /// From sending a request to its matching response or ACK.
+    pub round_trip: Histogram,
+}
+
+impl Default for LinkStats {
+    fn default() -> Self {
+        Self {
+            ack_failures: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            connected: AtomicBool::new(true),
+            round_trip: Histogram::default(),
+        }
+    }
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The metrics of `blaster` in the OpenMetrics text format.
+///
+/// Only cached values are read, so this doesn't talk to the device.
+pub fn render(blaster: &BlasterXG6) -> String {
+    let mut out = String::new();
+    let stats = blaster.link_stats();
+
+    let model = blaster.model().name;
+    let serial = blaster
+        .descriptor()
+        .and_then(|descriptor| descriptor.serial.as_deref())
+        .unwrap_or("");
+    out += "# TYPE linuxblaster_device info\n";
+    out += "# HELP linuxblaster_device The device being served.\n";
+    let _ = writeln!(
+        out,
+        "linuxblaster_device_info{{model=\"{}\",serial=\"{}\"}} 1",
+        escape(model),
+        escape(serial)
+    );
+
+    out += "# TYPE linuxblaster_connected gauge\n";
+    out += "# HELP linuxblaster_connected Whether the last connection check succeeded.\n";
+    let connected = stats.connected.load(Ordering::Relaxed);
+    let _ = writeln!(out, "linuxblaster_connected {}", u8::from(connected));
+
+    out += "# TYPE linuxblaster_feature_value gauge\n";
+    out += "# HELP linuxblaster_feature_value Cached value of every feature.\n";
+    for feature in &blaster.features {
+        let _ = writeln!(
+            out,
+            "linuxblaster_feature_value{{feature=\"{:?}\",name=\"{}\"}} {}",
+            feature.id,
+            escape(feature.id.display_name()),
+            feature.value()
+        );
+    }
+
+    if blaster.supports(FeatureId::Output)
+        && let ValueKind::Preset(labels) =
+            blaster.model().value_kind(FeatureId::Output)
+    {
+        out += "# TYPE linuxblaster_output stateset\n";
+        out += "# HELP linuxblaster_output The selected Output.\n";
+        let selected = blaster.output_label();
+        for &label in labels {
+            let _ = writeln!(
+                out,
+                "linuxblaster_output{{linuxblaster_output=\"{}\"}} {}",
+                escape(label),
+                u8::from(selected == Some(label))
+            );
+        }
+    }
+
+    out += "# TYPE linuxblaster_ack_failures counter\n";
+    out += "# HELP linuxblaster_ack_failures Writes the device didn't acknowledge.\n";
+    let _ = writeln!(
+        out,
+        "linuxblaster_ack_failures_total {}",
+        stats.ack_failures.load(Ordering::Relaxed)
+    );
+
+    out += "# TYPE linuxblaster_reconnects counter\n";
+    out += "# HELP linuxblaster_reconnects Successful reconnects after the device went away.\n";
+    let _ = writeln!(
+        out,
+        "linuxblaster_reconnects_total {}",
+        stats.reconnects.load(Ordering::Relaxed)
+    );
+
+    out += "# TYPE linuxblaster_hid_round_trip_seconds histogram\n";
+    out += "# UNIT linuxblaster_hid_round_trip_seconds seconds\n";
+    out += "# HELP linuxblaster_hid_round_trip_seconds From sending a HID request to its response or ACK.\n";
+    let counts = stats.round_trip.cumulative();
+    let bounds = ROUND_TRIP_BUCKETS
+        .iter()
+        .map(|bound| bound.to_string())
+        .chain(["+Inf".to_string()]);
+    for (bound, count) in bounds.zip(&counts) {
+        let _ = writeln!(
+            out,
+            "linuxblaster_hid_round_trip_seconds_bucket{{le=\"{}\"}} {}",
+            bound, count
+        );
+    }
+    let _ = writeln!(
+        out,
+        "linuxblaster_hid_round_trip_seconds_sum {}",
+        stats.round_trip.sum().as_secs_f64()
+    );
+    let _ = writeln!(
+        out,
+        "linuxblaster_hid_round_trip_seconds_count {}",
+        counts.last().copied().unwrap_or(0)
+    );
+
+    out += "# EOF\n";
+    out
+}
+
+/// Serves `render()` at `http://<address>/metrics` on a background thread.
+///
+/// Meant for localhost, there is no authentication.
+pub fn serve(
+    blaster: SharedBlaster,
+    address: SocketAddr,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(address)?;
+    info!("Serving metrics at http://{}/metrics", address);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(error) = handle_request(stream, &blaster) {
+                        debug!("Failed to answer metrics request: {}", error);
+                    }
+                }
+                Err(error) => warn!("Failed to accept client: {}", error),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle_request(
+    stream: TcpStream,
+    blaster: &SharedBlaster,
+) -> Result<(), Box<dyn Error>> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // skip the headers
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            ("200 OK", CONTENT_TYPE, render(&blaster.lock().unwrap()))
+        }
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    };
+    write!(
+        &stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use std::fmt;
+
+use crate::features::{FeatureId, ValueKind};
+use crate::{INTERFACE, PRODUCT_ID, VENDOR_ID};
+
+/// Describes one member of the Sound Blaster family
+/// that speaks the same `0x5a` protocol as the G6.
+///
+/// Everything protocol related that differs between models lives here,
+/// so `features` only has to describe the G6 and look up the differences.
+#[derive(Debug)]
+pub struct DeviceModel {
+    pub name: &'static str,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// The HID interface that accepts the `0x5a` packets.
+    pub interface: i32,
+    /// Every feature the model has.
+    /// Everything else is rejected with an `UnsupportedFeatureError`.
+    pub features: &'static [FeatureId],
+    /// DSP addresses that differ from `FeatureId::dsp_address()`.
+    pub address_overrides: &'static [(FeatureId, (u8, u8))],
+    /// Value ranges that differ from `FeatureId::value_kind()`.
+    pub range_overrides: &'static [(FeatureId, ValueKind)],
+    /// Whether the ids and the feature map were confirmed on real hardware.
+    /// Unverified models are opened read-only, see `BlasterXG6::is_read_only()`.
+    pub verified: bool,
+}
+
+impl PartialEq for DeviceModel {
+    fn eq(&self, other: &Self) -> bool {
+        self.vendor_id == other.vendor_id && self.product_id == other.product_id
+    }
+}
+
+impl Eq for DeviceModel {}
+
+impl fmt::Display for DeviceModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// SBX features + the Equalizer, which every known model has.
+const SBX_AND_EQ: &[FeatureId] = &[
+    FeatureId::SbxMaster,
+    FeatureId::SurroundToggle,
+    FeatureId::SurroundLevel,
+    FeatureId::DialogPlusToggle,
+    FeatureId::DialogPlusLevel,
+    FeatureId::SmartVolToggle,
+    FeatureId::SmartVolLevel,
+    FeatureId::SmartVolMode,
+    FeatureId::CrystalizerToggle,
+    FeatureId::CrystalizerLevel,
+    FeatureId::BassToggle,
+    FeatureId::BassLevel,
+    FeatureId::EqToggle,
+    FeatureId::EqPreAmp,
+    FeatureId::Eq31Hz,
+    FeatureId::Eq62Hz,
+    FeatureId::Eq125Hz,
+    FeatureId::Eq250Hz,
+    FeatureId::Eq500Hz,
+    FeatureId::Eq1kHz,
+    FeatureId::Eq2kHz,
+    FeatureId::Eq4kHz,
+    FeatureId::Eq8kHz,
+    FeatureId::Eq16kHz,
+];
+
+impl DeviceModel {
+    pub const G6: DeviceModel = DeviceModel {
+        name: "Sound BlasterX G6",
+        vendor_id: VENDOR_ID,
+        product_id: PRODUCT_ID,
+        interface: INTERFACE,
+        features: FeatureId::ALL,
+        address_overrides: &[],
+        range_overrides: &[],
+        verified: true,
+    };
+
+    // The following models use the same 0x5a framing according to their
+    // owners, but none of them have been captured yet.
+    // The product ids are taken from `lsusb` reports,
+    // the feature maps from the options Sound Blaster Command offers.
+
+    pub const G5: DeviceModel = DeviceModel {
+        name: "Sound BlasterX G5",
+        vendor_id: VENDOR_ID,
+        product_id: 0x3249,
+        interface: INTERFACE,
+        // Scout Mode, no software output switching
+        features: &[
+            FeatureId::SbxMaster,
+            FeatureId::ScoutMode,
+            FeatureId::SurroundToggle,
+            FeatureId::SurroundLevel,
+            FeatureId::DialogPlusToggle,
+            FeatureId::DialogPlusLevel,
+            FeatureId::SmartVolToggle,
+            FeatureId::SmartVolLevel,
+            FeatureId::SmartVolMode,
+            FeatureId::CrystalizerToggle,
+            FeatureId::CrystalizerLevel,
+            FeatureId::BassToggle,
+            FeatureId::BassLevel,
+            FeatureId::EqToggle,
+            FeatureId::EqPreAmp,
+            FeatureId::Eq31Hz,
+            FeatureId::Eq62Hz,
+            FeatureId::Eq125Hz,
+            FeatureId::Eq250Hz,
+            FeatureId::Eq500Hz,
+            FeatureId::Eq1kHz,
+            FeatureId::Eq2kHz,
+            FeatureId::Eq4kHz,
+            FeatureId::Eq8kHz,
+            FeatureId::Eq16kHz,
+        ],
+        address_overrides: &[],
+        range_overrides: &[],
+        verified: false,
+    };
+
+    pub const G8: DeviceModel = DeviceModel {
+        name: "Sound Blaster G8",
+        vendor_id: VENDOR_ID,
+        product_id: 0x3291,
+        interface: INTERFACE,
+        features: FeatureId::ALL,
+        address_overrides: &[],
+        range_overrides: &[],
+        verified: false,
+    };
+
+    pub const KATANA: DeviceModel = DeviceModel {
+        name: "Sound BlasterX Katana",
+        vendor_id: VENDOR_ID,
+        product_id: 0x3247,
+        interface: INTERFACE,
+        features: SBX_AND_EQ,
+        address_overrides: &[],
+        range_overrides: &[],
+        verified: false,
+    };
+
+    /// Every model `BlasterXG6::enumerate()` looks for.
+    pub const ALL: &[DeviceModel] =
+        &[Self::G6, Self::G5, Self::G8, Self::KATANA];
+
+    pub fn from_ids(
+        vendor_id: u16,
+        product_id: u16,
+    ) -> Option<&'static DeviceModel> {
+        Self::ALL.iter().find(|model| {
+            model.vendor_id == vendor_id && model.product_id == product_id
+        })
+    }
+
+    pub fn supports(&self, id: FeatureId) -> bool {
+        self.features.contains(&id)
+    }
+
+    /// The `(family, feature_id)` pair of a DSP feature on this model.
+    /// Returns `None` for unsupported and non-DSP features.
+    pub fn dsp_address(&self, id: FeatureId) -> Option<(u8, u8)> {
+        if !self.supports(id) {
+            return None;
+        }
+        self.address_overrides
+            .iter()
+            .find(|(overridden, _)| *overridden == id)
+            .map(|&(_, address)| address)
+            .or_else(|| id.dsp_address())
+    }
+
+    /// Reverse lookup of `dsp_address()`.
+    pub fn feature_at(&self, address: (u8, u8)) -> Option<FeatureId> {
+        self.features
+            .iter()
+            .copied()
+            .find(|&id| self.dsp_address(id) == Some(address))
+    }
+
+    /// The value range of a feature on this model.
+    pub fn value_kind(&self, id: FeatureId) -> ValueKind {
+        self.range_overrides
+            .iter()
+            .find(|(overridden, _)| *overridden == id)
+            .map(|&(_, kind)| kind)
+            .unwrap_or_else(|| id.value_kind())
+    }
+}
@@ -0,0 +1,371 @@
+//! Bridge to an MQTT broker, with Home Assistant discovery,
+//! so every feature shows up as an entity and can be set from automations.
+//!
+//! Topics, `<node>` being the serial number of the device:
+//! - `<topic_prefix>/<node>/availability`: "online", or "offline"
+//!   while the device or `linuxblasterd` is gone
+//! - `<topic_prefix>/<node>/<FeatureId>/state`: the value (retained)
+//! - `<topic_prefix>/<node>/<FeatureId>/set`: sets the value
+//! - `<discovery_prefix>/<component>/<node>/<FeatureId>/config`: discovery
+//!
+//! Values are "ON" / "OFF" for Toggles, percent (0-100) for Percentages,
+//! the feature's unit (dB, cm) for Ranged features and the label
+//! (e.g. "Headphones") for Presets.
+
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::{debug, info, warn};
+
+use crate::dbus::SharedBlaster;
+use crate::{BlasterXG6, DeviceModel, FeatureChange, FeatureId, ValueKind};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+/// Room for discovery and the state of every feature at once.
+const CHANNEL_CAPACITY: usize = 256;
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Where the bridge connects to, the `mqtt` section of `config.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: String,
+    /// Home Assistant's discovery prefix.
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            topic_prefix: "linuxblaster".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+/// The Home Assistant entity type for a feature.
+pub fn component(kind: ValueKind) -> &'static str {
+    match kind {
+        ValueKind::Toggle => "switch",
+        ValueKind::Percentage | ValueKind::Ranged { .. } => "number",
+        ValueKind::Preset(_) => "select",
+    }
+}
+
+/// The state payload for `value`, `None` if it was never read.
+pub fn encode_state(kind: ValueKind, value: f32) -> Option<String> {
+    if value.is_nan() {
+        return None;
+    }
+    Some(match kind {
+        ValueKind::Toggle if value != 0.0 => "ON".to_string(),
+        ValueKind::Toggle => "OFF".to_string(),
+        ValueKind::Percentage => format!("{:.0}", value * 100.0),
+        ValueKind::Ranged { .. } => format!("{:.1}", value),
+        ValueKind::Preset(_) => kind.label(value)?.to_string(),
+    })
+}
+
+/// Reverse of `encode_state()`, numbers are clamped into range.
+pub fn decode_command(kind: ValueKind, payload: &str) -> Option<f32> {
+    let payload = payload.trim();
+    match kind {
+        ValueKind::Toggle if payload.eq_ignore_ascii_case("ON") => Some(1.0),
+        ValueKind::Toggle if payload.eq_ignore_ascii_case("OFF") => Some(0.0),
+        ValueKind::Toggle => None,
+        ValueKind::Percentage => {
+            let percent: f32 = payload.parse().ok()?;
+            Some((percent / 100.0).clamp(0.0, 1.0))
+        }
+        ValueKind::Ranged { min, max, .. } => {
+            Some(payload.parse::<f32>().ok()?.clamp(min, max))
+        }
+        ValueKind::Preset(_) => kind.value_of(payload),
+    }
+}
+
+/// Topic names for one device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topics {
+    pub node: String,
+    /// `<topic_prefix>/<node>`
+    pub base: String,
+    pub discovery_prefix: String,
+}
+
+impl Topics {
+    pub fn new(config: &MqttConfig, node: &str) -> Self {
+        Self {
+            node: node.to_string(),
+            base: format!("{}/{}", config.topic_prefix, node),
+            discovery_prefix: config.discovery_prefix.clone(),
+        }
+    }
+
+    /// The node id of `blaster`: its serial number, or the model
+    /// if there is none, reduced to what topics and entity ids allow.
+    pub fn node_of(blaster: &BlasterXG6) -> String {
+        let name = blaster
+            .descriptor()
+            .and_then(|descriptor| descriptor.serial.clone())
+            .unwrap_or_else(|| blaster.model().name.to_string());
+        name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+
+    pub fn availability(&self) -> String {
+        format!("{}/availability", self.base)
+    }
+
+    pub fn state(&self, id: FeatureId) -> String {
+        format!("{}/{:?}/state", self.base, id)
+    }
+
+    pub fn command(&self, id: FeatureId) -> String {
+        format!("{}/{:?}/set", self.base, id)
+    }
+
+    /// The feature a command topic is for.
+    pub fn parse_command(&self, topic: &str) -> Option<FeatureId> {
+        topic
+            .strip_prefix(&self.base)?
+            .strip_prefix('/')?
+            .strip_suffix("/set")?
+            .parse()
+            .ok()
+    }
+
+    /// Discovery topic and payload of a feature.
+    pub fn discovery(
+        &self,
+        model: &DeviceModel,
+        id: FeatureId,
+    ) -> (String, Value) {
+        let kind = model.value_kind(id);
+        let topic = format!(
+            "{}/{}/{}/{:?}/config",
+            self.discovery_prefix,
+            component(kind),
+            self.node,
+            id
+        );
+        let mut payload = json!({
+            "name": id.display_name(),
+            "unique_id": format!("linuxblaster_{}_{:?}", self.node, id),
+            "state_topic": self.state(id),
+            "command_topic": self.command(id),
+            "availability_topic": self.availability(),
+            "device": {
+                "identifiers": [format!("linuxblaster_{}", self.node)],
+                "name": model.name,
+                "manufacturer": "Creative",
+                "model": model.name,
+            },
+        });
+        match kind {
+            ValueKind::Toggle => {}
+            ValueKind::Percentage => {
+                payload["min"] = json!(0);
+                payload["max"] = json!(100);
+                payload["step"] = json!(1);
+                payload["unit_of_measurement"] = json!("%");
+            }
+            ValueKind::Ranged {
+                min,
+                max,
+                step,
+                unit,
+            } => {
+                payload["min"] = json!(min);
+                payload["max"] = json!(max);
+                payload["step"] = json!(step);
+                payload["unit_of_measurement"] = json!(unit);
+            }
+            ValueKind::Preset(labels) => payload["options"] = json!(labels),
+        }
+        (topic, payload)
+    }
+}
+
+/// Work the event loop hands off, so device access doesn't stall it.
+enum Job {
+    /// (Re)connected: discovery, availability and every state.
+    Announce,
+    Command(FeatureId, String),
+}
+
+/// Publishes every change of the device and applies commands,
+/// reconnecting to the broker as needed.
+#[derive(Clone)]
+pub struct Bridge {
+    client: Client,
+    topics: Topics,
+    model: &'static DeviceModel,
+}
+
+impl Bridge {
+    pub fn start(
+        blaster: SharedBlaster,
+        config: &MqttConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (topics, model) = {
+            let blaster = blaster.lock().unwrap();
+            (
+                Topics::new(config, &Topics::node_of(&blaster)),
+                blaster.model(),
+            )
+        };
+
+        let mut options = MqttOptions::new(
+            format!("linuxblaster_{}", topics.node),
+            &config.host,
+            config.port,
+        );
+        options.set_keep_alive(KEEP_ALIVE);
+        options.set_last_will(LastWill::new(
+            topics.availability(),
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            options.set_credentials(
+                username,
+                config.password.clone().unwrap_or_default(),
+            );
+        }
+        let (client, mut connection) = Client::new(options, CHANNEL_CAPACITY);
+        info!("Bridging to MQTT at {}:{}", config.host, config.port);
+
+        let (jobs, received) = mpsc::channel();
+        let subscriber = client.clone();
+        let commands = format!("{}/+/set", topics.base);
+        let parser = topics.clone();
+        std::thread::spawn(move || {
+            for event in connection.iter() {
+                match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("Connected to MQTT broker");
+                        if let Err(error) = subscriber
+                            .try_subscribe(&commands, QoS::AtLeastOnce)
+                        {
+                            warn!("Failed to subscribe to commands: {}", error);
+                        }
+                        let _ = jobs.send(Job::Announce);
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        let Some(id) = parser.parse_command(&publish.topic)
+                        else {
+                            continue;
+                        };
+                        let payload = String::from_utf8_lossy(&publish.payload)
+                            .into_owned();
+                        let _ = jobs.send(Job::Command(id, payload));
+                    }
+                    Ok(_) => {}
+                    Err(error) => {
+                        warn!("MQTT connection failed: {}", error);
+                        std::thread::sleep(RETRY_INTERVAL);
+                    }
+                }
+            }
+        });
+
+        let bridge = Self {
+            client,
+            topics,
+            model,
+        };
+        let worker = bridge.clone();
+        std::thread::spawn(move || worker.run(&blaster, received));
+        Ok(bridge)
+    }
+
+    fn run(&self, blaster: &SharedBlaster, jobs: Receiver<Job>) {
+        for job in jobs {
+            let blaster = blaster.lock().unwrap();
+            match job {
+                Job::Announce => self.announce(&blaster),
+                Job::Command(id, payload) => {
+                    if let Err(error) = self.apply(&blaster, id, &payload) {
+                        warn!(
+                            "MQTT command {:?} for {} failed: {}",
+                            payload, id, error
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn announce(&self, blaster: &BlasterXG6) {
+        for feature in &blaster.features {
+            let (topic, payload) =
+                self.topics.discovery(self.model, feature.id);
+            self.send(topic, payload.to_string());
+            self.publish_value(feature.id, feature.value());
+        }
+        self.set_available(true);
+    }
+
+    fn apply(
+        &self,
+        blaster: &BlasterXG6,
+        id: FeatureId,
+        payload: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        blaster.check_supported(id)?;
+        let kind = self.model.value_kind(id);
+        let value = decode_command(kind, payload)
+            .ok_or_else(|| format!("Invalid value for {}", id))?;
+        debug!("MQTT sets {} to {}", id, value);
+        match kind.label(value) {
+            Some(label) if id == FeatureId::Output => {
+                blaster.switch_output(Some(label))
+            }
+            _ => blaster.set_feature(id, Some(value)),
+        }
+    }
+
+    /// Publishes the new value, meant to be called for every change
+    /// from `BlasterXG6::subscribe()`.
+    pub fn publish(&self, change: &FeatureChange) {
+        self.publish_value(change.id, change.new);
+    }
+
+    /// Marks the device as (un)available, e.g. while it is unplugged.
+    pub fn set_available(&self, available: bool) {
+        let payload = if available { "online" } else { "offline" };
+        self.send(self.topics.availability(), payload.to_string());
+    }
+
+    fn publish_value(&self, id: FeatureId, value: f32) {
+        if let Some(state) = encode_state(self.model.value_kind(id), value) {
+            self.send(self.topics.state(id), state);
+        }
+    }
+
+    /// Retained, and dropped instead of blocking
+    /// while the broker is unreachable.
+    fn send(&self, topic: String, payload: String) {
+        if let Err(error) =
+            self.client
+                .try_publish(topic, QoS::AtLeastOnce, true, payload)
+        {
+            debug!("Dropped MQTT message: {}", error);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use zbus::zvariant::Value;
+
+use crate::{
+    BlasterXG6, CachedLibrary, ChangeSource, DeviceModel, FeatureChange,
+    FeatureId,
+};
+
+/// Id of the last notification, so the next one replaces it
+/// instead of stacking up, e.g. when a hotkey is pressed repeatedly.
+static LAST_ID: AtomicU32 = AtomicU32::new(0);
+
+const TIMEOUT_MS: i32 = 2000;
+const SUMMARY: &str = "Sound Blaster";
+
+/// Shows an on-screen notification through `org.freedesktop.Notifications`.
+pub fn notify(summary: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    debug!("Notification: {} {}", summary, body);
+    let connection = zbus::blocking::Connection::session()?;
+    let hints: HashMap<&str, Value> =
+        HashMap::from([("transient", Value::from(true))]);
+    let reply = connection.call_method(
+        Some("org.freedesktop.Notifications"),
+        "/org/freedesktop/Notifications",
+        Some("org.freedesktop.Notifications"),
+        "Notify",
+        &(
+            "Linuxblaster Control",
+            LAST_ID.load(Ordering::Relaxed),
+            "audio-card",
+            summary,
+            body,
+            Vec::<&str>::new(),
+            hints,
+            TIMEOUT_MS,
+        ),
+    )?;
+    let id: u32 = reply.body().deserialize()?;
+    LAST_ID.store(id, Ordering::Relaxed);
+    Ok(())
+}
+
+/// What state change notifications are grouped by,
+/// each with its own `Verbosity`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub enum NotificationGroup {
+    Output,
+    /// The active profile from the library, see `matches_profile()`.
+    Profile,
+    /// SBX, Scout Mode and the SBX effects.
+    Sbx,
+    Equalizer,
+}
+
+impl NotificationGroup {
+    pub const ALL: &[NotificationGroup] =
+        &[Self::Output, Self::Profile, Self::Sbx, Self::Equalizer];
+
+    /// The group changes of `id` are announced in.
+    pub fn of(id: FeatureId) -> Self {
+        match id {
+            FeatureId::Output => Self::Output,
+            FeatureId::EqToggle | FeatureId::EqPreAmp => Self::Equalizer,
+            id if FeatureId::EQ_BANDS.contains(&id) => Self::Equalizer,
+            _ => Self::Sbx,
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Output => "Output",
+            Self::Profile => "Profile",
+            Self::Sbx => "SBX",
+            Self::Equalizer => "Equalizer",
+        }
+    }
+}
+
+/// How much of a `NotificationGroup` is announced.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub enum Verbosity {
+    Off,
+    /// Only switches: the Output, the profile, SBX, Scout Mode
+    /// and the EQ toggle.
+    #[default]
+    Important,
+    /// Every change, including levels and EQ bands.
+    All,
+}
+
+impl Verbosity {
+    pub const ALL: &[Verbosity] = &[Self::Off, Self::Important, Self::All];
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Important => "Important",
+            Self::All => "All",
+        }
+    }
+
+    pub fn announces(&self, id: FeatureId) -> bool {
+        match self {
+            Self::Off => false,
+            Self::Important => matches!(
+                id,
+                FeatureId::Output
+                    | FeatureId::SbxMaster
+                    | FeatureId::ScoutMode
+                    | FeatureId::EqToggle
+            ),
+            Self::All => true,
+        }
+    }
+}
+
+/// Announces state changes as desktop notifications,
+/// whether they come from a hotkey, a rule or the device's buttons.
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    /// Groups that are left out use `Verbosity::default()`.
+    pub verbosity: BTreeMap<NotificationGroup, Verbosity>,
+    profile: Option<String>,
+    /// The profiles to find the active one in.
+    library: CachedLibrary,
+}
+
+impl Notifier {
+    pub fn new(
+        verbosity: BTreeMap<NotificationGroup, Verbosity>,
+        blaster: &BlasterXG6,
+    ) -> Self {
+        let mut notifier = Self {
+            verbosity,
+            profile: None,
+            library: CachedLibrary::default(),
+        };
+        notifier.profile = notifier.active_profile(blaster);
+        notifier
+    }
+
+    pub fn verbosity(&self, group: NotificationGroup) -> Verbosity {
+        self.verbosity.get(&group).copied().unwrap_or_default()
+    }
+
+    /// One line per announced change, e.g. "Output: Headphones".
+    ///
+    /// Re-reads (`ChangeSource::Refresh`) are left out,
+    /// e.g. the other Output's settings after switching it.
+    /// Values are formatted with the value ranges of `model`.
+    pub fn describe(
+        &self,
+        model: &DeviceModel,
+        changes: &[FeatureChange],
+    ) -> Vec<String> {
+        let mut lines = Vec::new();
+        for change in changes {
+            let id = change.id;
+            if change.source == ChangeSource::Refresh
+                || !self.verbosity(NotificationGroup::of(id)).announces(id)
+            {
+                continue;
+            }
+            let line = format!(
+                "{}: {}",
+                id.display_name(),
+                model.value_kind(id).format(change.new)
+            );
+            // a feature changing several times in one batch
+            lines.retain(|other: &String| {
+                !other.starts_with(&format!("{}:", id.display_name()))
+            });
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Shows a notification for `changes`, and for a change of the
+    /// active profile they caused, if any of it is announced.
+    pub fn update(&mut self, blaster: &BlasterXG6, changes: &[FeatureChange]) {
+        let mut lines = self.describe(blaster.model(), changes);
+
+        if !changes.is_empty()
+            && self.verbosity(NotificationGroup::Profile) != Verbosity::Off
+        {
+            let profile = self.active_profile(blaster);
+            if profile != self.profile
+                && let Some(name) = &profile
+            {
+                lines.insert(0, format!("Profile: {}", name));
+            }
+            self.profile = profile;
+        }
+
+        if lines.is_empty() {
+            return;
+        }
+        if let Err(error) = notify(SUMMARY, &lines.join("\n")) {
+            debug!("Failed to show notification: {}", error);
+        }
+    }
+
+    fn active_profile(&mut self, blaster: &BlasterXG6) -> Option<String> {
+        if self.verbosity(NotificationGroup::Profile) == Verbosity::Off {
+            return None;
+        }
+        self.library
+            .entries()
+            .iter()
+            .find(|entry| blaster.matches_profile(&entry.profile))
+            .map(|entry| entry.name().to_string())
+    }
+}
@@ -0,0 +1,355 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::info;
+
+use crate::features::{FeatureGroup, FeatureId};
+
+/// The version `Profile::to_json()` writes.
+///
+/// Bump this whenever the format changes in a way older versions
+/// can't read, and add a step to `migrate()` that upgrades the previous
+/// version. New optional fields (with a serde default) need no bump.
+pub const PROFILE_VERSION: u32 = 1;
+
+/// A saved device state, as stored in the preset library.
+///
+/// The G6 keeps a separate settings bank per Output,
+/// so a profile holds one bank per Output it describes.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "metadata": { "name": "...", "created": 1735689600, ... },
+///   "banks": [
+///     {
+///       "output": "Headphones",
+///       "values": [ { "id": "SurroundToggle", "value": 1.0 }, ... ]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// A profile with a `scope` only covers some features,
+/// e.g. just the Equalizer, and leaves everything else alone.
+/// Scoped profiles can be stacked as layers, see `Profile::stack()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub version: u32,
+    pub metadata: ProfileMetadata,
+    /// The features this profile covers, `None` for all of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Vec<ProfileScope>>,
+    pub banks: Vec<ProfileBank>,
+}
+
+/// The settings of one Output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileBank {
+    /// The Output these values belong to, e.g. "Headphones".
+    /// `None` applies to whichever Output is active,
+    /// e.g. for models without Output switching.
+    #[serde(default)]
+    pub output: Option<String>,
+    /// Never contains the Output itself.
+    pub values: Vec<ProfileValue>,
+}
+
+impl ProfileBank {
+    /// The saved value of a feature, if the bank has one.
+    pub fn value(&self, id: FeatureId) -> Option<f32> {
+        self.values
+            .iter()
+            .find(|saved| saved.id == id)
+            .map(|saved| saved.value)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileMetadata {
+    pub name: String,
+    /// Unix timestamp (seconds).
+    pub created: u64,
+    /// Firmware of the device the profile was saved from.
+    pub firmware: Option<String>,
+    /// `DeviceModel::name` of the device the profile was saved from.
+    pub model: Option<String>,
+    /// Output that was active when saving, e.g. "Headphones".
+    pub output: Option<String>,
+    /// Free-form labels for the `ProfileLibrary`.
+    pub tags: Vec<String>,
+    pub favourite: bool,
+}
+
+/// One entry of `Profile::scope`:
+/// a group like `"Equalizer"`, or a single feature like `"SmartVolMode"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProfileScope {
+    Group(FeatureGroup),
+    Feature(FeatureId),
+}
+
+impl ProfileScope {
+    pub fn contains(&self, id: FeatureId) -> bool {
+        match self {
+            Self::Group(group) => group.features().contains(&id),
+            Self::Feature(feature) => *feature == id,
+        }
+    }
+}
+
+impl fmt::Display for ProfileScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Group(group) => write!(f, "{}", group.display_name()),
+            Self::Feature(id) => write!(f, "{}", id.display_name()),
+        }
+    }
+}
+
+impl FromStr for ProfileScope {
+    type Err = Box<dyn Error>;
+
+    /// Parses the serialized name, e.g. "Equalizer" or "SmartVolMode".
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(Value::String(name.to_string()))
+            .map_err(|_| format!("Unknown feature or group: {}", name).into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ProfileValue {
+    pub id: FeatureId,
+    pub value: f32,
+}
+
+/// The profile was written by a newer version of this application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedProfileVersion(pub u32);
+
+impl fmt::Display for UnsupportedProfileVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Profile version {} is newer than the supported version {}",
+            self.0, PROFILE_VERSION
+        )
+    }
+}
+
+impl Error for UnsupportedProfileVersion {}
+
+impl Profile {
+    pub fn new(metadata: ProfileMetadata, banks: Vec<ProfileBank>) -> Self {
+        Self {
+            version: PROFILE_VERSION,
+            metadata,
+            scope: None,
+            banks,
+        }
+    }
+
+    /// Whether the profile covers `id`.
+    pub fn in_scope(&self, id: FeatureId) -> bool {
+        self.scope
+            .as_ref()
+            .is_none_or(|scope| scope.iter().any(|entry| entry.contains(id)))
+    }
+
+    /// Narrows the profile down to `scope`,
+    /// dropping the values of every other feature.
+    pub fn restrict(mut self, scope: &[ProfileScope]) -> Self {
+        let covered: Vec<FeatureId> = FeatureId::ALL
+            .iter()
+            .copied()
+            .filter(|&id| {
+                self.in_scope(id)
+                    && scope.iter().any(|entry| entry.contains(id))
+            })
+            .collect();
+
+        for bank in &mut self.banks {
+            bank.values.retain(|saved| covered.contains(&saved.id));
+        }
+        self.scope = Some(scope.to_vec());
+        self
+    }
+
+    /// Combines profiles into one, later layers override earlier ones,
+    /// e.g. a full "Music" profile with a "Late night" layer
+    /// that only sets `SmartVolMode` on top.
+    ///
+    /// Banks are merged by Output, a bank without an Output applies to
+    /// every Output of the layers below. The metadata is the top layer's.
+    /// The result is unscoped if any layer is.
+    pub fn stack(layers: &[Profile]) -> Option<Self> {
+        let top = layers.last()?;
+
+        let mut outputs: Vec<Option<&str>> = Vec::new();
+        for bank in layers.iter().flat_map(|layer| &layer.banks) {
+            let output = bank.output.as_deref();
+            if output.is_some() && !outputs.contains(&output) {
+                outputs.push(output);
+            }
+        }
+        if outputs.is_empty() {
+            outputs.push(None);
+        }
+
+        let banks = outputs
+            .into_iter()
+            .map(|output| {
+                let mut values: Vec<ProfileValue> = Vec::new();
+                for layer in layers {
+                    let Some(bank) = layer.bank_for(output) else {
+                        continue;
+                    };
+                    for &saved in &bank.values {
+                        if !layer.in_scope(saved.id) {
+                            continue;
+                        }
+                        values.retain(|value| value.id != saved.id);
+                        values.push(saved);
+                    }
+                }
+                ProfileBank {
+                    output: output.map(str::to_string),
+                    values,
+                }
+            })
+            .collect();
+
+        let mut scope = Some(Vec::new());
+        for layer in layers {
+            match (&mut scope, &layer.scope) {
+                (Some(scope), Some(layer_scope)) => {
+                    for &entry in layer_scope {
+                        if !scope.contains(&entry) {
+                            scope.push(entry);
+                        }
+                    }
+                }
+                _ => scope = None,
+            }
+        }
+
+        Some(Self {
+            version: PROFILE_VERSION,
+            metadata: top.metadata.clone(),
+            scope,
+            banks,
+        })
+    }
+
+    /// The bank saved for `output` ("Speakers", "Headphones").
+    pub fn bank(&self, output: &str) -> Option<&ProfileBank> {
+        self.banks
+            .iter()
+            .find(|bank| bank.output.as_deref() == Some(output))
+    }
+
+    /// The bank written to `output`:
+    /// the one saved for it, or else the one without an Output.
+    pub fn bank_for(&self, output: Option<&str>) -> Option<&ProfileBank> {
+        self.banks
+            .iter()
+            .find(|bank| bank.output.as_deref() == output)
+            .or_else(|| self.banks.iter().find(|bank| bank.output.is_none()))
+    }
+
+    /// Parses a profile of any known version,
+    /// migrating it to `PROFILE_VERSION` on the way.
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        let value: Value = serde_json::from_str(json)?;
+        let profile = serde_json::from_value(migrate(value)?)?;
+        Ok(profile)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, self.to_json()?)?;
+        info!("Saved Profile to {:?}", path);
+        Ok(())
+    }
+}
+
+/// Seconds since the Unix epoch, for `ProfileMetadata::created`.
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Upgrades a parsed profile step by step to `PROFILE_VERSION`.
+///
+/// Profiles without a `version` field are version 0:
+/// the serialized `BlasterXG6` that v2.1 saved.
+pub(crate) fn migrate(mut profile: Value) -> Result<Value, Box<dyn Error>> {
+    loop {
+        let version = match profile.get("version") {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or("Profile version is not a number")?,
+        };
+
+        profile = match version {
+            0 => migrate_v0(profile)?,
+            PROFILE_VERSION => return Ok(profile),
+            newer => return Err(Box::new(UnsupportedProfileVersion(newer))),
+        };
+    }
+}
+
+/// v0: `{ "features": [ { "id": ..., "value": ... }, ... ] }`
+///
+/// The values become the bank of the saved Output.
+/// Features that hadn't been read yet were saved as `null`, those are dropped.
+fn migrate_v0(profile: Value) -> Result<Value, Box<dyn Error>> {
+    let features = profile
+        .get("features")
+        .and_then(Value::as_array)
+        .ok_or("Unrecognized profile format")?;
+
+    let values: Vec<ProfileValue> = features
+        .iter()
+        .filter(|feature| feature.get("value").is_some_and(Value::is_number))
+        .map(|feature| serde_json::from_value(feature.clone()))
+        .collect::<Result<_, _>>()?;
+
+    let output = values
+        .iter()
+        .find(|saved| saved.id == FeatureId::Output)
+        .and_then(|saved| FeatureId::Output.value_kind().label(saved.value))
+        .map(str::to_string);
+
+    let bank = ProfileBank {
+        output,
+        values: values
+            .into_iter()
+            .filter(|saved| saved.id != FeatureId::Output)
+            .collect(),
+    };
+
+    Ok(json!({
+        "version": 1,
+        "metadata": ProfileMetadata::default(),
+        "banks": [bank],
+    }))
+}
@@ -0,0 +1,164 @@
+//! The backend of a `BlasterXG6` attached to `linuxblasterd`,
+//! see `BlasterXG6::attach()`.
+//!
+//! Reads and writes are forwarded through the daemon's socket,
+//! a second connection subscribes to its changes.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tracing::debug;
+
+use crate::socket::Client;
+use crate::{
+    DeviceModel, FeatureChange, FeatureId, Profile, ProfileBank,
+    ProfileMetadata, ProfileScope, ProfileValue,
+};
+
+pub(crate) struct Remote {
+    path: PathBuf,
+    client: Mutex<Client>,
+    changes: Receiver<FeatureChange>,
+    /// Cleared once the daemon closed the subscription.
+    alive: Arc<AtomicBool>,
+    firmware: Option<String>,
+}
+
+impl Remote {
+    /// Connects to the daemon serving at `path`,
+    /// returns the backend and the model of the device it owns.
+    pub(crate) fn connect(
+        path: &Path,
+    ) -> Result<(Self, &'static DeviceModel), Box<dyn Error>> {
+        let mut client = Client::connect_to(path)?;
+        let device = client.call("device", Value::Null)?;
+        let id = |key: &str| {
+            device[key]
+                .as_u64()
+                .and_then(|id| u16::try_from(id).ok())
+                .ok_or_else(|| format!("Missing {} in {}", key, device))
+        };
+        let model = DeviceModel::from_ids(id("vendor_id")?, id("product_id")?)
+            .ok_or_else(|| format!("Unknown device: {}", device))?;
+
+        let watcher = Client::connect_to(path)?;
+        let (sender, changes) = mpsc::channel();
+        let alive = Arc::new(AtomicBool::new(true));
+        let watching = alive.clone();
+        std::thread::spawn(move || {
+            let result = watcher.watch(|change| {
+                let _ = sender.send(change);
+            });
+            debug!("Daemon subscription ended: {:?}", result);
+            watching.store(false, Ordering::Relaxed);
+        });
+
+        let remote = Self {
+            path: path.to_path_buf(),
+            client: Mutex::new(client),
+            changes,
+            alive,
+            firmware: device["firmware"].as_str().map(str::to_string),
+        };
+        Ok((remote, model))
+    }
+
+    /// The socket of the daemon.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn is_connected(&self) -> bool {
+        self.alive.load(Ordering::Relaxed)
+    }
+
+    /// Changes the daemon reported since the last call.
+    pub(crate) fn changes(&self) -> Vec<FeatureChange> {
+        self.changes.try_iter().collect()
+    }
+
+    pub(crate) fn firmware(&self) -> Option<String> {
+        self.firmware.clone()
+    }
+
+    /// The daemon's cached value of every supported feature,
+    /// `NaN` for values it couldn't read.
+    pub(crate) fn state(
+        &self,
+    ) -> Result<Vec<(FeatureId, f32)>, Box<dyn Error>> {
+        let state = self.call("state", Value::Null)?;
+        let Value::Object(state) = state else {
+            return Err(format!("Unexpected state: {}", state).into());
+        };
+        state
+            .iter()
+            .map(|(name, value)| {
+                let value =
+                    value.as_f64().map_or(f32::NAN, |value| value as f32);
+                Ok((name.parse()?, value))
+            })
+            .collect()
+    }
+
+    pub(crate) fn set(
+        &self,
+        id: FeatureId,
+        value: f32,
+    ) -> Result<(), Box<dyn Error>> {
+        self.client.lock().unwrap().set(id, Some(value))
+    }
+
+    pub(crate) fn apply(
+        &self,
+        profile: &Profile,
+    ) -> Result<(), Box<dyn Error>> {
+        self.client.lock().unwrap().apply(profile)
+    }
+
+    pub(crate) fn reset(&self) -> Result<(), Box<dyn Error>> {
+        self.call("reset", Value::Null)?;
+        Ok(())
+    }
+
+    fn call(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, Box<dyn Error>> {
+        self.client.lock().unwrap().call(method, params)
+    }
+}
+
+/// The writes of a `Transaction` as a `Profile` scoped to them,
+/// so the daemon applies them in a transaction of its own.
+/// The Output is left out, the daemon restores it after applying.
+pub(crate) fn transaction_profile(writes: &[(FeatureId, f32)]) -> Profile {
+    let writes: Vec<(FeatureId, f32)> = writes
+        .iter()
+        .copied()
+        .filter(|(id, _)| *id != FeatureId::Output)
+        .collect();
+
+    let values = writes
+        .iter()
+        .map(|&(id, value)| ProfileValue { id, value })
+        .collect();
+    let mut profile = Profile::new(
+        ProfileMetadata::default(),
+        vec![ProfileBank {
+            output: None,
+            values,
+        }],
+    );
+    profile.scope = Some(
+        writes
+            .iter()
+            .map(|&(id, _)| ProfileScope::Feature(id))
+            .collect(),
+    );
+    profile
+}
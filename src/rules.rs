@@ -0,0 +1,354 @@
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::profile::{Profile, ProfileMetadata, ProfileScope, ProfileValue};
+use crate::{BlasterXG6, DEFAULT_BASE_PATH, FeatureId, ProfileLibrary, config};
+
+/// How often `linuxblasterd` and `--headless` evaluate the rules.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What makes a `Rule` match. Application names are compared
+/// case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "when", rename_all = "snake_case")]
+pub enum Trigger {
+    /// The application has an audio stream open, matched against
+    /// `application.name` and `application.process.binary`
+    /// of the PipeWire / PulseAudio client (e.g. "Spotify", "steam").
+    Stream { application: String },
+    /// A window of the application has focus, matched against the
+    /// window class or Wayland app id (Hyprland, Sway and X11).
+    Window { class: String },
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stream { application } => {
+                write!(f, "{} plays audio", application)
+            }
+            Self::Window { class } => write!(f, "{} is focused", class),
+        }
+    }
+}
+
+impl Trigger {
+    pub fn matches(&self, context: &Context) -> bool {
+        match self {
+            Self::Stream { application } => context
+                .streams
+                .iter()
+                .any(|stream| stream.eq_ignore_ascii_case(application)),
+            Self::Window { class } => context
+                .focused
+                .as_ref()
+                .is_some_and(|focused| focused.eq_ignore_ascii_case(class)),
+        }
+    }
+}
+
+/// Applies a profile while its `Trigger` matches.
+///
+/// ```json
+/// { "name": "Games", "when": "stream", "application": "steam",
+///   "profile": "Gaming", "scope": ["Sbx"] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(flatten)]
+    pub trigger: Trigger,
+    /// Library name or file path, see `ProfileLibrary::resolve()`.
+    pub profile: String,
+    /// Narrows the profile down further, see `Profile::restrict()`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Vec<ProfileScope>>,
+}
+
+impl Rule {
+    pub fn load_profile(&self) -> Result<Profile, Box<dyn Error>> {
+        ProfileLibrary::default()
+            .resolve_scoped(&self.profile, self.scope.as_deref())
+    }
+}
+
+/// The rules in `DEFAULT_BASE_PATH/rules.json`, next to `profiles/`.
+/// The first matching rule wins.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn path() -> PathBuf {
+        DEFAULT_BASE_PATH.join("rules.json")
+    }
+
+    /// The rules in `path()`, none if they can't be read.
+    pub fn load() -> Self {
+        config::load_json_or_default(&Self::path(), "rules")
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn Error>> {
+        config::load_json(path)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        config::save_json(self, path, "rules")
+    }
+
+    pub fn matching(&self, context: &Context) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.trigger.matches(context))
+    }
+
+    /// Detects only what the rules need, e.g. `pactl` isn't run
+    /// without a `Trigger::Stream` rule.
+    pub fn detect_context(&self) -> Context {
+        let needs = |stream: bool| {
+            self.rules.iter().any(|rule| {
+                matches!(rule.trigger, Trigger::Stream { .. }) == stream
+            })
+        };
+        Context {
+            streams: if needs(true) {
+                audio_streams()
+            } else {
+                Vec::new()
+            },
+            focused: if needs(false) { focused_window() } else { None },
+        }
+    }
+}
+
+/// What the desktop is doing right now.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Context {
+    /// Names and binaries of applications with an audio stream.
+    pub streams: Vec<String>,
+    /// Class or app id of the focused window.
+    pub focused: Option<String>,
+}
+
+/// Reads the playback streams through `pactl`,
+/// which works on PulseAudio and on PipeWire (pipewire-pulse).
+fn audio_streams() -> Vec<String> {
+    let Some(json) = run("pactl", &["-f", "json", "list", "sink-inputs"])
+    else {
+        return Vec::new();
+    };
+    let Ok(Value::Array(streams)) = serde_json::from_str::<Value>(&json) else {
+        warn!("Unexpected output from pactl");
+        return Vec::new();
+    };
+
+    let mut names = Vec::new();
+    for stream in &streams {
+        let properties = &stream["properties"];
+        for key in ["application.name", "application.process.binary"] {
+            if let Some(name) = properties[key].as_str() {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Asks the compositor (Hyprland, Sway) or X11 for the focused window.
+fn focused_window() -> Option<String> {
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        let json = run("hyprctl", &["activewindow", "-j"])?;
+        let window: Value = serde_json::from_str(&json).ok()?;
+        return window["class"].as_str().map(str::to_string);
+    }
+    if std::env::var_os("SWAYSOCK").is_some() {
+        let json = run("swaymsg", &["-t", "get_tree"])?;
+        return focused_node(&serde_json::from_str(&json).ok()?);
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        let class = run("xdotool", &["getactivewindow", "getwindowclassname"])?;
+        return Some(class.trim().to_string());
+    }
+    None
+}
+
+/// Walks Sway's tree to the focused node, its app id (Wayland)
+/// or window class (Xwayland).
+fn focused_node(node: &Value) -> Option<String> {
+    if node["focused"].as_bool() == Some(true) {
+        return node["app_id"]
+            .as_str()
+            .or_else(|| node["window_properties"]["class"].as_str())
+            .map(str::to_string);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[key].as_array())
+        .flatten()
+        .find_map(focused_node)
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    match Command::new(program).args(args).output() {
+        Ok(output) if output.status.success() => {
+            String::from_utf8(output.stdout).ok()
+        }
+        Ok(output) => {
+            debug!("{} failed: {}", program, output.status);
+            None
+        }
+        Err(error) => {
+            debug!("Failed to run {}: {}", program, error);
+            None
+        }
+    }
+}
+
+/// The rule currently applied.
+#[derive(Debug, Clone)]
+struct ActiveRule {
+    name: String,
+    /// The state before the rule was applied,
+    /// restricted to what the rule changes.
+    /// `None` if applying the rule failed.
+    revert: Option<Profile>,
+    /// The state right after applying, to tell which features
+    /// were changed while the rule was active.
+    applied: Vec<ProfileValue>,
+}
+
+/// Applies the matching rule, and reverts it once nothing matches anymore
+/// (e.g. the application exited) or another rule takes over.
+///
+/// Only the active Output's bank is captured and reverted. Features changed
+/// while a rule is active (by hand, a schedule or another frontend)
+/// keep their new value when it is reverted.
+#[derive(Debug, Default)]
+pub struct RuleEngine {
+    active: Option<ActiveRule>,
+}
+
+impl RuleEngine {
+    /// Name of the rule currently applied.
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_ref().map(|active| active.name.as_str())
+    }
+
+    /// Re-reads `RuleSet::path()`, so edits take effect without a restart,
+    /// and applies the matching rule.
+    pub fn update(
+        &mut self,
+        blaster: &BlasterXG6,
+    ) -> Result<(), Box<dyn Error>> {
+        let rules = RuleSet::load();
+        let context = rules.detect_context();
+        self.update_with(blaster, &rules, &context)
+    }
+
+    pub fn update_with(
+        &mut self,
+        blaster: &BlasterXG6,
+        rules: &RuleSet,
+        context: &Context,
+    ) -> Result<(), Box<dyn Error>> {
+        let matched = rules.matching(context);
+        if matched.map(|rule| rule.name.as_str()) == self.active() {
+            return Ok(());
+        }
+
+        self.revert(blaster)?;
+        let Some(rule) = matched else {
+            return Ok(());
+        };
+
+        info!("Rule {:?} matches, applying {:?}", rule.name, rule.profile);
+        // Remembered even if applying fails, so it isn't retried every check.
+        self.active = Some(ActiveRule {
+            name: rule.name.clone(),
+            revert: None,
+            applied: Vec::new(),
+        });
+        let profile = rule.load_profile()?;
+        let revert = Self::capture(blaster, &profile);
+        blaster.apply(&profile)?;
+        if let Some(active) = &mut self.active {
+            active.revert = Some(revert);
+            active.applied = blaster.current_bank().values;
+        }
+        Ok(())
+    }
+
+    /// Restores the state from before the active rule, if any.
+    pub fn revert(
+        &mut self,
+        blaster: &BlasterXG6,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(active) = self.active.take() else {
+            return Ok(());
+        };
+        info!("Rule {:?} no longer matches, reverting", active.name);
+        let Some(revert) = active.revert else {
+            return Ok(());
+        };
+        let revert = Self::untouched(blaster, revert, &active.applied);
+        if revert.banks.iter().all(|bank| bank.values.is_empty()) {
+            return Ok(());
+        }
+        blaster.apply(&revert)
+    }
+
+    /// The cached values of everything `profile` is about to change
+    /// on the active Output, scoped to exactly these features.
+    /// Reading the other Outputs would mean switching to them.
+    fn capture(blaster: &BlasterXG6, profile: &Profile) -> Profile {
+        let metadata = ProfileMetadata {
+            name: format!("Before {}", profile.metadata.name),
+            ..ProfileMetadata::default()
+        };
+        let changed = changed_features(profile);
+        Profile::new(metadata, vec![blaster.current_bank()]).restrict(&changed)
+    }
+
+    /// `revert` without the features whose cached value
+    /// differs from the one right after applying the rule.
+    pub(crate) fn untouched(
+        blaster: &BlasterXG6,
+        mut revert: Profile,
+        applied: &[ProfileValue],
+    ) -> Profile {
+        for bank in &mut revert.banks {
+            bank.values.retain(|saved| {
+                let current = blaster.feature(saved.id).value();
+                let unchanged = applied.iter().any(|value| {
+                    value.id == saved.id && value.value == current
+                });
+                if !unchanged {
+                    debug!("{} changed while the rule was active", saved.id);
+                }
+                unchanged
+            });
+        }
+        let changed = changed_features(&revert);
+        revert.restrict(&changed)
+    }
+}
+
+/// Every feature `profile` has a value for, as a scope.
+fn changed_features(profile: &Profile) -> Vec<ProfileScope> {
+    FeatureId::ALL
+        .iter()
+        .filter(|&&id| {
+            profile
+                .banks
+                .iter()
+                .any(|bank| bank.values.iter().any(|saved| saved.id == id))
+        })
+        .map(|&id| ProfileScope::Feature(id))
+        .collect()
+}
@@ -0,0 +1,364 @@
+//! Import and export of Sound Blaster Command (SBC) profiles.
+//!
+//! SBC profiles come as JSON or XML, and the layout differs between
+//! SBC versions. Instead of relying on one exact layout, every value in the
+//! file is flattened into a `(key path, value)` pair and the key is matched
+//! against the parameter names SBC uses (`Surround`, `Crystalizer`,
+//! `SmartVolume`, `31Hz`, ...).
+//! Everything that can't be matched (mixer levels, lighting, ...) is
+//! reported back instead of being silently dropped.
+//!
+//! The importer is experimental: it was written without real SBC exports to
+//! test against, and the 0-100 scaling of SBX levels is an assumption.
+//!
+//! There is no exporter: without real SBC profiles to test against, a file
+//! written here couldn't be guaranteed to load in SBC.
+
+use std::error::Error;
+use std::path::Path;
+
+use quick_xml::Reader;
+use quick_xml::escape::unescape;
+use quick_xml::events::Event;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::profile::{
+    self, Profile, ProfileBank, ProfileMetadata, ProfileValue,
+};
+use crate::{FeatureId, ValueKind};
+
+/// The result of importing an SBC profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SbcImport {
+    pub profile: Profile,
+    /// `key = value` of every entry that couldn't be mapped to a `FeatureId`.
+    pub unmapped: Vec<String>,
+}
+
+/// Imports a `.json` or `.xml` SBC profile.
+/// The format is detected from the content, not the extension.
+pub fn import(path: &Path) -> Result<SbcImport, Box<dyn Error>> {
+    warn!(
+        "SBC import is experimental and untested against real SBC profiles, check the imported values"
+    );
+    let content = std::fs::read_to_string(path)?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    if content.trim_start().starts_with('<') {
+        parse_xml(&content, &name)
+    } else {
+        parse_json(&content, &name)
+    }
+}
+
+pub fn parse_json(json: &str, name: &str) -> Result<SbcImport, Box<dyn Error>> {
+    let root: Value = serde_json::from_str(json)?;
+    let mut entries = Vec::new();
+    flatten_json(&root, String::new(), &mut entries);
+    Ok(map_entries(entries, name))
+}
+
+pub fn parse_xml(xml: &str, name: &str) -> Result<SbcImport, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut path: Vec<String> = Vec::new();
+    let mut entries = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let tag =
+                    String::from_utf8_lossy(element.local_name().as_ref())
+                        .into_owned();
+                path.push(tag);
+                xml_attributes(&element, &path, &mut entries)?;
+            }
+            Event::Empty(element) => {
+                let tag =
+                    String::from_utf8_lossy(element.local_name().as_ref())
+                        .into_owned();
+                path.push(tag);
+                xml_attributes(&element, &path, &mut entries)?;
+                path.pop();
+            }
+            Event::Text(text) => {
+                let text = unescape(&text.decode()?)?.into_owned();
+                if !text.is_empty() {
+                    entries.push((path.join("."), text));
+                }
+            }
+            Event::End(_) => {
+                path.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(map_entries(entries, name))
+}
+
+/// `<Param name="Surround" value="67"/>` becomes `Surround = 67`,
+/// any other attribute `<Surround Level="67"/>` becomes `Surround.Level = 67`.
+fn xml_attributes(
+    element: &quick_xml::events::BytesStart,
+    path: &[String],
+    entries: &mut Vec<(String, String)>,
+) -> Result<(), Box<dyn Error>> {
+    let mut attributes = Vec::new();
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8_lossy(attribute.key.local_name().as_ref())
+            .into_owned();
+        attributes.push((key, attribute.unescape_value()?.into_owned()));
+    }
+
+    let named = attributes.iter().find(|(key, _)| {
+        matches!(key.to_lowercase().as_str(), "name" | "id" | "key")
+    });
+    let value = attributes
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("value"));
+
+    match (named, value) {
+        (Some((_, name)), Some((_, value))) => {
+            let mut key = path[..path.len() - 1].to_vec();
+            key.push(name.clone());
+            entries.push((key.join("."), value.clone()));
+        }
+        _ => {
+            for (key, value) in attributes {
+                entries.push((format!("{}.{}", path.join("."), key), value));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn flatten_json(
+    value: &Value,
+    path: String,
+    entries: &mut Vec<(String, String)>,
+) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten_json(value, join(key), entries);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                flatten_json(value, join(&index.to_string()), entries);
+            }
+        }
+        Value::String(text) => entries.push((path, text.clone())),
+        Value::Null => {}
+        other => entries.push((path, other.to_string())),
+    }
+}
+
+fn map_entries(entries: Vec<(String, String)>, name: &str) -> SbcImport {
+    let mut values: Vec<ProfileValue> = Vec::new();
+    let mut unmapped = Vec::new();
+    let mut output = None;
+    let mut profile_name = None;
+
+    for (key, raw) in entries {
+        let normalized = normalize_key(&key);
+
+        // Only a top-level `Name` is the profile name, nested ones belong to
+        // bands, presets, devices, ...
+        let top_level = !key.contains('.');
+        if normalized == "profilename" || (normalized == "name" && top_level) {
+            profile_name.get_or_insert(raw);
+            continue;
+        }
+
+        let mapped = feature_for_key(&normalized)
+            .and_then(|id| parse_value(id, &raw).map(|value| (id, value)));
+        match mapped {
+            Some((FeatureId::Output, value)) => output = Some(value),
+            Some((id, value)) => {
+                match values.iter_mut().find(|saved| saved.id == id) {
+                    Some(saved) => saved.value = value,
+                    None => values.push(ProfileValue { id, value }),
+                }
+            }
+            None => unmapped.push(format!("{} = {}", key, raw)),
+        }
+    }
+
+    let output = output
+        .and_then(|value| FeatureId::Output.value_kind().label(value))
+        .map(str::to_string);
+
+    let metadata = ProfileMetadata {
+        name: profile_name.unwrap_or_else(|| name.to_string()),
+        created: profile::now(),
+        output: output.clone(),
+        ..Default::default()
+    };
+
+    SbcImport {
+        profile: Profile::new(metadata, vec![ProfileBank { output, values }]),
+        unmapped,
+    }
+}
+
+/// Keys that only describe which part of a parameter is meant.
+const GENERIC_KEYS: &[&str] = &[
+    "enabled", "enable", "state", "onoff", "level", "value", "slider",
+];
+
+/// The last segment of the key path, lowercase without separators.
+/// Generic segments (`Enabled`, `Level`, ...) are joined with their parent,
+/// so `SBX.Surround.Enabled` becomes `surroundenabled`.
+fn normalize_key(key: &str) -> String {
+    let segments: Vec<String> = key
+        .split('.')
+        .map(|segment| {
+            segment
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    match segments.as_slice() {
+        [.., parent, last] if GENERIC_KEYS.contains(&last.as_str()) => {
+            format!("{}{}", parent, last)
+        }
+        [.., last] => last.clone(),
+        [] => String::new(),
+    }
+}
+
+/// SBC parameter names, as produced by `normalize_key()`.
+const KEY_ALIASES: &[(&[&str], FeatureId)] = &[
+    (&["sbx", "sbxenabled", "sbxprostudio"], FeatureId::SbxMaster),
+    (
+        &["scoutmode", "scout", "scoutmodeenabled"],
+        FeatureId::ScoutMode,
+    ),
+    (
+        &["output", "outputdevice", "speakerconfig"],
+        FeatureId::Output,
+    ),
+    (
+        &["surroundenabled", "surroundstate"],
+        FeatureId::SurroundToggle,
+    ),
+    (
+        &["surround", "surroundlevel", "surroundvalue"],
+        FeatureId::SurroundLevel,
+    ),
+    (
+        &["surrounddistance", "speakerdistance"],
+        FeatureId::SurroundDistance,
+    ),
+    (
+        &["dialogplusenabled", "dialogenabled"],
+        FeatureId::DialogPlusToggle,
+    ),
+    (
+        &["dialogplus", "dialogpluslevel", "dialog"],
+        FeatureId::DialogPlusLevel,
+    ),
+    (
+        &["smartvolumeenabled", "smartvolenabled"],
+        FeatureId::SmartVolToggle,
+    ),
+    (
+        &["smartvolume", "smartvolumelevel", "smartvol"],
+        FeatureId::SmartVolLevel,
+    ),
+    (
+        &["smartvolumemode", "smartvolmode"],
+        FeatureId::SmartVolMode,
+    ),
+    (
+        &["crystalizerenabled", "crystallizerenabled"],
+        FeatureId::CrystalizerToggle,
+    ),
+    (
+        &["crystalizer", "crystalizerlevel", "crystallizer"],
+        FeatureId::CrystalizerLevel,
+    ),
+    (&["bassenabled", "bassboostenabled"], FeatureId::BassToggle),
+    (&["bass", "basslevel", "bassboost"], FeatureId::BassLevel),
+    (
+        &["equalizerenabled", "eqenabled", "equalizer"],
+        FeatureId::EqToggle,
+    ),
+    (&["preamp", "eqpreamp", "preampgain"], FeatureId::EqPreAmp),
+];
+
+/// Band names as SBC shows them, in `FeatureId::EQ_BANDS` order.
+const BAND_NAMES: [&str; 10] = [
+    "31Hz", "62Hz", "125Hz", "250Hz", "500Hz", "1kHz", "2kHz", "4kHz", "8kHz",
+    "16kHz",
+];
+
+fn feature_for_key(key: &str) -> Option<FeatureId> {
+    if let Some(&(_, id)) = KEY_ALIASES
+        .iter()
+        .find(|(aliases, _)| aliases.contains(&key))
+    {
+        return Some(id);
+    }
+
+    // "31hz", "eq31hz", "band31hz", "eq1000hz", ...
+    let band = key
+        .trim_start_matches("eq")
+        .trim_start_matches("band")
+        .replace("000hz", "khz");
+    BAND_NAMES
+        .iter()
+        .position(|name| name.to_lowercase() == band)
+        .map(|index| FeatureId::EQ_BANDS[index])
+}
+
+fn parse_value(id: FeatureId, raw: &str) -> Option<f32> {
+    let raw = raw.trim();
+    let number = raw.parse::<f32>().ok();
+    match id.value_kind() {
+        ValueKind::Toggle => match raw.to_lowercase().as_str() {
+            "true" | "on" | "yes" => Some(1.0),
+            "false" | "off" | "no" => Some(0.0),
+            _ => number.filter(|value| *value == 0.0 || *value == 1.0),
+        },
+        // SBC shows the SBX sliders as 0 - 100
+        ValueKind::Percentage => {
+            let value = number?;
+            (0.0..=100.0).contains(&value).then_some(value / 100.0)
+        }
+        ValueKind::Ranged { min, max, .. } => {
+            let value = number?;
+            (min..=max).contains(&value).then_some(value)
+        }
+        kind @ ValueKind::Preset(labels) => match number {
+            Some(index) => (index.fract() == 0.0
+                && (0.0..labels.len() as f32).contains(&index))
+            .then_some(index),
+            None => labels
+                .iter()
+                .find(|label| label.eq_ignore_ascii_case(raw))
+                .and_then(|label| kind.value_of(label)),
+        },
+    }
+}
@@ -0,0 +1,198 @@
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{
+    Datelike, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, Weekday,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::profile::{Profile, ProfileScope};
+use crate::{BlasterXG6, DEFAULT_BASE_PATH, ProfileLibrary, config};
+
+/// A time of day like "22:00", local time.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl FromStr for TimeOfDay {
+    type Err = chrono::ParseError;
+
+    fn from_str(time: &str) -> Result<Self, Self::Err> {
+        NaiveTime::parse_from_str(time, "%H:%M").map(Self)
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = chrono::ParseError;
+
+    fn try_from(time: String) -> Result<Self, Self::Error> {
+        time.parse()
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M"))
+    }
+}
+
+/// Applies a profile at a time of day, from then on until
+/// the next entry takes over, e.g. "Night" at 22:00 and "Default" at 7:00.
+///
+/// ```json
+/// { "name": "Night", "at": "22:00", "days": ["Mon", "Tue"],
+///   "profile": "Night", "scope": ["SmartVolMode", "EqBands"] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledProfile {
+    pub name: String,
+    pub at: TimeOfDay,
+    /// Only on these weekdays, every day if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+    /// Only on these dates ("2026-12-24"), any date if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dates: Vec<NaiveDate>,
+    /// Library name or file path, see `ProfileLibrary::resolve()`.
+    pub profile: String,
+    /// Narrows the profile down further, see `Profile::restrict()`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Vec<ProfileScope>>,
+}
+
+impl fmt::Display for ScheduledProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}", self.at)?;
+        if !self.days.is_empty() {
+            let days: Vec<String> =
+                self.days.iter().map(Weekday::to_string).collect();
+            write!(f, " on {}", days.join(", "))?;
+        }
+        if !self.dates.is_empty() {
+            let dates: Vec<String> =
+                self.dates.iter().map(NaiveDate::to_string).collect();
+            write!(f, " on {}", dates.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl ScheduledProfile {
+    pub fn runs_on(&self, date: NaiveDate) -> bool {
+        (self.days.is_empty() || self.days.contains(&date.weekday()))
+            && (self.dates.is_empty() || self.dates.contains(&date))
+    }
+
+    /// When the entry last ran up to `now`, looking back a week.
+    pub fn last_run(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        (0..=7)
+            .filter_map(|days_back| {
+                now.date().checked_sub_days(Days::new(days_back))
+            })
+            .filter(|&date| self.runs_on(date))
+            .map(|date| date.and_time(self.at.0))
+            .find(|&time| time <= now)
+    }
+
+    pub fn load_profile(&self) -> Result<Profile, Box<dyn Error>> {
+        ProfileLibrary::default()
+            .resolve_scoped(&self.profile, self.scope.as_deref())
+    }
+}
+
+/// The entries in `DEFAULT_BASE_PATH/schedule.json`, next to `rules.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub entries: Vec<ScheduledProfile>,
+}
+
+impl Schedule {
+    pub fn path() -> PathBuf {
+        DEFAULT_BASE_PATH.join("schedule.json")
+    }
+
+    /// See `RuleSet::load()`, no entries if the file can't be read.
+    pub fn load() -> Self {
+        config::load_json_or_default(&Self::path(), "schedule")
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn Error>> {
+        config::load_json(path)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        config::save_json(self, path, "schedule")
+    }
+
+    /// The entry in effect at `now`, the one that ran last,
+    /// and when it ran. On a tie the first entry wins.
+    pub fn current(
+        &self,
+        now: NaiveDateTime,
+    ) -> Option<(&ScheduledProfile, NaiveDateTime)> {
+        let mut current: Option<(&ScheduledProfile, NaiveDateTime)> = None;
+        for entry in &self.entries {
+            if let Some(time) = entry.last_run(now)
+                && current.is_none_or(|(_, latest)| time > latest)
+            {
+                current = Some((entry, time));
+            }
+        }
+        current
+    }
+}
+
+/// Applies the entry in effect, once per run: when it is due,
+/// or when the scheduler starts (e.g. the daemon starts at night).
+///
+/// Unlike rules, scheduled profiles are not reverted,
+/// the next entry takes over instead.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    /// When the entry applied last ran.
+    applied: Option<NaiveDateTime>,
+}
+
+impl Scheduler {
+    /// Re-reads `Schedule::path()`, so edits take effect without a restart,
+    /// and applies the entry in effect now.
+    pub fn update(
+        &mut self,
+        blaster: &BlasterXG6,
+    ) -> Result<(), Box<dyn Error>> {
+        self.update_at(blaster, &Schedule::load(), Local::now().naive_local())
+    }
+
+    pub fn update_at(
+        &mut self,
+        blaster: &BlasterXG6,
+        schedule: &Schedule,
+        now: NaiveDateTime,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some((entry, time)) = schedule.current(now) else {
+            return Ok(());
+        };
+        if self.applied == Some(time) {
+            return Ok(());
+        }
+
+        info!(
+            "Schedule {:?} is due, applying {:?}",
+            entry.name, entry.profile
+        );
+        // a failing entry waits for its next run
+        self.applied = Some(time);
+        blaster.apply(&entry.load_profile()?)
+    }
+}
@@ -0,0 +1,146 @@
+//! Everything that runs next to a frontend owning the device:
+//! rules, schedules, hotkeys, metrics and MQTT.
+//!
+//! `linuxblasterd`, `--headless` and the GUI all start these, so it doesn't
+//! matter which of them owns the device. Polling the device and checking
+//! the connection stays with the frontend, see `run_loop()` for the ones
+//! without a window.
+
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, warn};
+
+use crate::dbus::SharedBlaster;
+use crate::{
+    CONNECTION_CHECK_INTERVAL, Config, FeatureChange, Notifier, RuleEngine,
+    RuleSet, Scheduler, hotkeys, metrics, mqtt, notify, rules,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Starts everything `config` enables, for as long as the process runs.
+///
+/// Rules and schedules are checked every `rules::CHECK_INTERVAL`
+/// while the device is connected. Hotkeys run their `Action` and show
+/// the result as a notification. Changes and the connection state are
+/// published to MQTT.
+pub fn start(blaster: SharedBlaster, config: &Config) {
+    if let Some(address) = config.metrics_address
+        && let Err(error) = metrics::serve(blaster.clone(), address)
+    {
+        warn!("Not serving metrics at {}: {}", address, error);
+    }
+    let mqtt = config.mqtt.as_ref().and_then(|mqtt| {
+        mqtt::Bridge::start(blaster.clone(), mqtt)
+            .inspect_err(|error| warn!("Not bridging to MQTT: {}", error))
+            .ok()
+    });
+    let actions = hotkeys::listen(&config.hotkeys);
+
+    std::thread::spawn(move || {
+        let mut changes = blaster.lock().unwrap().subscribe();
+        let mut engine = RuleEngine::default();
+        let mut scheduler = Scheduler::default();
+        let mut connected = true;
+        let mut last_rules = Instant::now();
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let mut published = Vec::new();
+            let now_connected = {
+                let blaster = blaster.lock().unwrap();
+                loop {
+                    match changes.try_recv() {
+                        Ok(change) => published.push(change),
+                        Err(TryRecvError::Empty) => break,
+                        // the GUI switched to another device
+                        Err(TryRecvError::Disconnected) => {
+                            changes = blaster.subscribe();
+                            break;
+                        }
+                    }
+                }
+                blaster.link_stats().connected.load(Ordering::Relaxed)
+            };
+            if let Some(mqtt) = &mqtt {
+                if now_connected != connected {
+                    mqtt.set_available(now_connected);
+                }
+                for change in &published {
+                    mqtt.publish(change);
+                }
+            }
+            connected = now_connected;
+
+            if connected && last_rules.elapsed() >= rules::CHECK_INTERVAL {
+                // Detecting runs external tools, so not while holding the lock.
+                let rules = RuleSet::load();
+                let context = rules.detect_context();
+                let blaster = blaster.lock().unwrap();
+                if let Err(error) =
+                    engine.update_with(&blaster, &rules, &context)
+                {
+                    warn!("Failed to apply rule: {}", error);
+                }
+                if let Err(error) = scheduler.update(&blaster) {
+                    warn!("Failed to apply scheduled profile: {}", error);
+                }
+                last_rules = Instant::now();
+            }
+
+            for action in actions.try_iter() {
+                let result = action.run(&blaster.lock().unwrap());
+                let message = result.unwrap_or_else(|error| {
+                    warn!("{} failed: {}", action, error);
+                    format!("{} failed: {}", action, error)
+                });
+                if let Err(error) = notify::notify("Sound Blaster", &message) {
+                    debug!("Failed to show notification: {}", error);
+                }
+            }
+        }
+    });
+}
+
+/// Polls the device for notifications, checks the connection every
+/// `CONNECTION_CHECK_INTERVAL` and notifies about changes, forever.
+/// `forward` is called with every change as well,
+/// e.g. to emit it on D-Bus.
+///
+/// The main loop of `linuxblasterd` and `--headless`,
+/// the GUI polls from its frames instead.
+pub fn run_loop(
+    blaster: SharedBlaster,
+    config: &Config,
+    mut forward: impl FnMut(&FeatureChange),
+) -> ! {
+    let (changes, mut notifier) = {
+        let blaster = blaster.lock().unwrap();
+        let notifier = Notifier::new(config.notifications.clone(), &blaster);
+        (blaster.subscribe(), notifier)
+    };
+    let mut connected = true;
+    let mut last_check = Instant::now();
+    loop {
+        {
+            let mut blaster = blaster.lock().unwrap();
+            if connected {
+                blaster.poll_notifications();
+            }
+            if last_check.elapsed() >= CONNECTION_CHECK_INTERVAL {
+                connected = blaster.ensure_connected();
+                last_check = Instant::now();
+            }
+        }
+
+        let changes: Vec<FeatureChange> = changes.try_iter().collect();
+        if !changes.is_empty() {
+            notifier.update(&blaster.lock().unwrap(), &changes);
+        }
+        changes.iter().for_each(&mut forward);
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
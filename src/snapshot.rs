@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+
+use crate::profile::{Profile, ProfileBank, ProfileMetadata};
+use crate::{DEFAULT_BASE_PATH, DeviceDescriptor};
+
+/// Where the "last known good" state of a device is kept:
+/// `DEFAULT_BASE_PATH/state/<serial>.json`,
+/// or `<vid>-<pid>.json` for devices without a serial number.
+///
+/// The state is a regular `Profile` with one bank per Output,
+/// see `BlasterXG6::restore_last_state()`.
+pub fn path(descriptor: &DeviceDescriptor) -> PathBuf {
+    let name = match &descriptor.serial {
+        Some(serial) => serial.replace(['/', '\\', ':'], "_"),
+        None => {
+            format!(
+                "{:04x}-{:04x}",
+                descriptor.vendor_id, descriptor.product_id
+            )
+        }
+    };
+    DEFAULT_BASE_PATH
+        .join("state")
+        .join(format!("{}.json", name))
+}
+
+/// Replaces the bank of `bank.output` in `previous`,
+/// keeping the banks of every other Output.
+pub(crate) fn merge(
+    previous: Option<Profile>,
+    metadata: ProfileMetadata,
+    bank: ProfileBank,
+) -> Profile {
+    let mut banks = previous.map(|profile| profile.banks).unwrap_or_default();
+    banks.retain(|saved| saved.output != bank.output);
+    banks.push(bank);
+    Profile::new(metadata, banks)
+}
@@ -0,0 +1,505 @@
+//! JSON-RPC 2.0 over a Unix socket, for setups without a session bus
+//! (tiling WMs, containers). Serves the same operations as `dbus`.
+//!
+//! Every request and response is one line of JSON:
+//!
+//! ```text
+//! -> {"jsonrpc":"2.0","id":1,"method":"set","params":{"feature":"SurroundLevel","value":0.6}}
+//! <- {"jsonrpc":"2.0","id":1,"result":null}
+//! -> {"jsonrpc":"2.0","id":2,"method":"subscribe"}
+//! <- {"jsonrpc":"2.0","id":2,"result":true}
+//! <- {"jsonrpc":"2.0","method":"feature_changed","params":{"feature":"SurroundLevel","old":0.5,"new":0.6,"source":"Local"}}
+//! ```
+//!
+//! Values are in the feature's `ValueKind`, e.g. 0.0 - 1.0 for Percentages.
+//!
+//! Methods (params in parentheses):
+//! - `get` (feature), `set` (feature, value), `toggle` (feature)
+//! - `state`: every supported feature and its value
+//! - `device`: model name, vendor_id, product_id and firmware
+//! - `reset`: see `BlasterXG6::reset()`
+//! - `switch_output` (output, optional: next one if left out)
+//! - `apply_profile` (profile: library name or path, or a `Profile` object)
+//! - `diff_profile` (profile: `Profile` object): one line per change
+//! - `save_profile` (name), `list_profiles`
+//! - `subscribe`: `feature_changed` notifications from then on
+
+use std::error::Error;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use tracing::{debug, info, warn};
+
+use crate::dbus::SharedBlaster;
+use crate::{BlasterXG6, FeatureChange, FeatureId, Profile, ProfileLibrary};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The request was valid, but the device or library failed.
+const FAILED: i64 = -32000;
+
+/// How long a write to a client may block. Clients not reading
+/// their notifications are dropped instead of stalling `broadcast()`.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// `$XDG_RUNTIME_DIR/linuxblaster.sock`, or `linuxblaster-<uid>.sock`
+/// in the temp directory if `XDG_RUNTIME_DIR` is not set.
+pub fn path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) => {
+            PathBuf::from(runtime_dir).join("linuxblaster.sock")
+        }
+        None => std::env::temp_dir()
+            .join(format!("linuxblaster-{}.sock", current_uid())),
+    }
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: getuid() has no preconditions and can't fail
+    unsafe { libc::getuid() }
+}
+
+/// Fails unless `path` belongs to the current user,
+/// so nobody else can pose as the daemon or have its socket removed.
+fn check_owner(path: &Path) -> Result<(), Box<dyn Error>> {
+    let owner = std::fs::symlink_metadata(path)?.uid();
+    if owner != current_uid() {
+        return Err(format!(
+            "{:?} belongs to another user (uid {})",
+            path, owner
+        )
+        .into());
+    }
+    Ok(())
+}
+
+type Writer = Arc<Mutex<UnixStream>>;
+
+/// Serves a `SharedBlaster` on a Unix socket until dropped,
+/// which removes the socket file.
+pub struct Server {
+    path: PathBuf,
+    subscribers: Arc<Mutex<Vec<Writer>>>,
+}
+
+impl Server {
+    /// Serves `blaster` at `path()`.
+    pub fn serve(blaster: SharedBlaster) -> Result<Self, Box<dyn Error>> {
+        Self::serve_at(blaster, &path())
+    }
+
+    /// Fails if another process is already serving at `path`,
+    /// a stale socket file is replaced.
+    pub fn serve_at(
+        blaster: SharedBlaster,
+        path: &Path,
+    ) -> Result<Self, Box<dyn Error>> {
+        if path.exists() {
+            check_owner(path)?;
+            if UnixStream::connect(path).is_ok() {
+                return Err(format!("{:?} is already served", path).into());
+            }
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        info!("Serving JSON-RPC at {:?}", path);
+
+        let subscribers: Arc<Mutex<Vec<Writer>>> = Arc::default();
+        let accepted = subscribers.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let blaster = blaster.clone();
+                        let subscribers = accepted.clone();
+                        std::thread::spawn(move || {
+                            handle_connection(stream, &blaster, &subscribers)
+                        });
+                    }
+                    Err(error) => warn!("Failed to accept client: {}", error),
+                }
+            }
+        });
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            subscribers,
+        })
+    }
+
+    /// Sends a `feature_changed` notification to every subscribed client.
+    /// Clients that went away, or didn't take the notification within
+    /// `WRITE_TIMEOUT`, are disconnected: a line may be cut off.
+    pub fn broadcast(&self, change: &FeatureChange) {
+        let line = notification("feature_changed", change_to_json(change));
+        self.subscribers.lock().unwrap().retain(|writer| {
+            let Err(error) = send(writer, &line) else {
+                return true;
+            };
+            debug!("Dropping subscriber: {}", error);
+            let _ = writer.lock().unwrap().shutdown(Shutdown::Both);
+            false
+        });
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn handle_connection(
+    stream: UnixStream,
+    blaster: &SharedBlaster,
+    subscribers: &Mutex<Vec<Writer>>,
+) {
+    let writer = stream
+        .set_write_timeout(Some(WRITE_TIMEOUT))
+        .and_then(|()| stream.try_clone());
+    let writer: Writer = match writer {
+        Ok(writer) => Arc::new(Mutex::new(writer)),
+        Err(error) => {
+            warn!("Failed to set up client: {}", error);
+            return;
+        }
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        debug!("JSON-RPC: {}", line);
+
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => {
+                let id = request.get("id").cloned().unwrap_or(Value::Null);
+                let method = request["method"].as_str().unwrap_or_default();
+                let params = &request["params"];
+                let result = if method == "subscribe" {
+                    subscribers.lock().unwrap().push(writer.clone());
+                    Ok(Value::Bool(true))
+                } else {
+                    dispatch(&blaster.lock().unwrap(), method, params)
+                };
+                response(id, result)
+            }
+            Err(error) => {
+                response(Value::Null, Err((PARSE_ERROR, error.to_string())))
+            }
+        };
+        if send(&writer, &response).is_err() {
+            break;
+        }
+    }
+}
+
+type RpcResult = Result<Value, (i64, String)>;
+
+fn dispatch(blaster: &BlasterXG6, method: &str, params: &Value) -> RpcResult {
+    match method {
+        "get" => {
+            let id = feature_param(params)?;
+            let value = blaster.feature_value(id).map_err(failed)?;
+            Ok(json!(value))
+        }
+        "set" => {
+            check_connected(blaster)?;
+            let id = feature_param(params)?;
+            let value = params["value"]
+                .as_f64()
+                .ok_or_else(|| invalid("Missing value"))?;
+            blaster
+                .set_feature(id, Some(value as f32))
+                .map_err(failed)?;
+            Ok(Value::Null)
+        }
+        "toggle" => {
+            check_connected(blaster)?;
+            let id = feature_param(params)?;
+            blaster.set_feature(id, None).map_err(failed)?;
+            Ok(Value::Null)
+        }
+        "state" => {
+            let state: serde_json::Map<String, Value> = FeatureId::ALL
+                .iter()
+                .filter(|&&id| blaster.supports(id))
+                .map(|&id| {
+                    (format!("{:?}", id), json!(blaster.feature(id).value()))
+                })
+                .collect();
+            Ok(Value::Object(state))
+        }
+        "device" => {
+            let model = blaster.model();
+            Ok(json!({
+                "model": model.name,
+                "vendor_id": model.vendor_id,
+                "product_id": model.product_id,
+                "firmware": blaster.firmware_version(),
+            }))
+        }
+        "reset" => {
+            check_connected(blaster)?;
+            blaster.reset().map_err(failed)?;
+            Ok(Value::Null)
+        }
+        "switch_output" => {
+            check_connected(blaster)?;
+            let output = params["output"].as_str();
+            blaster.switch_output(output).map_err(failed)?;
+            Ok(Value::Null)
+        }
+        "apply_profile" => {
+            check_connected(blaster)?;
+            let profile = profile_param(params)?;
+            blaster.apply(&profile).map_err(failed)?;
+            Ok(Value::Null)
+        }
+        "diff_profile" => {
+            let profile = profile_param(params)?;
+            let diff = blaster.diff(&profile);
+            Ok(json!(
+                diff.changes
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            ))
+        }
+        "save_profile" => {
+            let name = params["name"]
+                .as_str()
+                .ok_or_else(|| invalid("Missing name"))?;
+            let profile = blaster.to_profile(name);
+            ProfileLibrary::default().save(&profile).map_err(failed)?;
+            Ok(Value::Null)
+        }
+        "list_profiles" => {
+            let entries = ProfileLibrary::default().list().map_err(failed)?;
+            Ok(json!(
+                entries
+                    .iter()
+                    .map(|entry| entry.name().to_string())
+                    .collect::<Vec<_>>()
+            ))
+        }
+        _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+    }
+}
+
+/// Writes are refused while the last connection check failed,
+/// instead of waiting for a device that isn't there.
+fn check_connected(blaster: &BlasterXG6) -> Result<(), (i64, String)> {
+    if blaster.link_stats().connected.load(Ordering::Relaxed) {
+        Ok(())
+    } else {
+        Err(failed(format!("The {} is disconnected", blaster.model())))
+    }
+}
+
+fn feature_param(params: &Value) -> Result<FeatureId, (i64, String)> {
+    params["feature"]
+        .as_str()
+        .ok_or_else(|| invalid("Missing feature"))?
+        .parse()
+        .map_err(|error: Box<dyn Error>| invalid(error))
+}
+
+/// A library name or path, or a serialized `Profile`.
+fn profile_param(params: &Value) -> Result<Profile, (i64, String)> {
+    match &params["profile"] {
+        Value::String(name) => {
+            ProfileLibrary::default().resolve(name).map_err(failed)
+        }
+        Value::Object(_) => {
+            Profile::from_json(&params["profile"].to_string()).map_err(invalid)
+        }
+        _ => Err(invalid("Missing profile")),
+    }
+}
+
+fn failed(error: impl ToString) -> (i64, String) {
+    (FAILED, error.to_string())
+}
+
+fn invalid(error: impl ToString) -> (i64, String) {
+    (INVALID_PARAMS, error.to_string())
+}
+
+fn response(id: Value, result: RpcResult) -> String {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    }
+    .to_string()
+}
+
+fn notification(method: &str, params: Value) -> String {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params }).to_string()
+}
+
+fn send(writer: &Mutex<UnixStream>, line: &str) -> std::io::Result<()> {
+    let mut writer = writer.lock().unwrap();
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\n")
+}
+
+/// `old` is `null` if the feature had no value yet,
+/// JSON has no NaN.
+pub(crate) fn change_to_json(change: &FeatureChange) -> Value {
+    let number = |value: f32| (!value.is_nan()).then_some(value);
+    json!({
+        "feature": change.id,
+        "old": number(change.old),
+        "new": number(change.new),
+        "source": change.source,
+    })
+}
+
+pub(crate) fn change_from_json(params: &Value) -> Option<FeatureChange> {
+    let number = |value: &Value| value.as_f64().map_or(f32::NAN, |v| v as f32);
+    Some(FeatureChange {
+        id: serde_json::from_value(params["feature"].clone()).ok()?,
+        old: number(&params["old"]),
+        new: number(&params["new"]),
+        source: serde_json::from_value(params["source"].clone()).ok()?,
+    })
+}
+
+/// Client side of `Server`, used by `blasterctl` and the GUI.
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+impl Client {
+    /// Connects to a server at `path()`, if there is one.
+    pub fn connect() -> Option<Self> {
+        Self::connect_to(&path()).ok()
+    }
+
+    /// Fails if `path` doesn't belong to the current user.
+    pub fn connect_to(path: &Path) -> Result<Self, Box<dyn Error>> {
+        check_owner(path)?;
+        let writer = UnixStream::connect(path)?;
+        Ok(Self {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+            next_id: 1,
+        })
+    }
+
+    /// Sends a request and waits for its response.
+    /// Notifications arriving in the meantime are dropped.
+    pub fn call(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, Box<dyn Error>> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        writeln!(self.writer, "{}", request)?;
+
+        loop {
+            let message = self.read()?;
+            if message["id"] != json!(id) {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                let message = error["message"].as_str().unwrap_or("Failed");
+                return Err(message.into());
+            }
+            return Ok(message["result"].clone());
+        }
+    }
+
+    fn read(&mut self) -> Result<Value, Box<dyn Error>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    pub fn get(&mut self, id: FeatureId) -> Result<f32, Box<dyn Error>> {
+        let value = self.call("get", json!({ "feature": id }))?;
+        Ok(value.as_f64().map_or(f32::NAN, |value| value as f32))
+    }
+
+    /// Pass `None` for value to toggle.
+    pub fn set(
+        &mut self,
+        id: FeatureId,
+        value: Option<f32>,
+    ) -> Result<(), Box<dyn Error>> {
+        match value {
+            Some(value) => {
+                self.call("set", json!({ "feature": id, "value": value }))?
+            }
+            None => self.call("toggle", json!({ "feature": id }))?,
+        };
+        Ok(())
+    }
+
+    pub fn switch_output(
+        &mut self,
+        output: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.call("switch_output", json!({ "output": output }))?;
+        Ok(())
+    }
+
+    pub fn apply(&mut self, profile: &Profile) -> Result<(), Box<dyn Error>> {
+        let profile: Value = serde_json::from_str(&profile.to_json()?)?;
+        self.call("apply_profile", json!({ "profile": profile }))?;
+        Ok(())
+    }
+
+    /// What applying `profile` would change, one line per feature.
+    pub fn diff(
+        &mut self,
+        profile: &Profile,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let profile: Value = serde_json::from_str(&profile.to_json()?)?;
+        let lines = self.call("diff_profile", json!({ "profile": profile }))?;
+        Ok(serde_json::from_value(lines)?)
+    }
+
+    /// Subscribes to changes and blocks, calling `on_change` for each,
+    /// until the server goes away.
+    pub fn watch(
+        mut self,
+        mut on_change: impl FnMut(FeatureChange),
+    ) -> Result<(), Box<dyn Error>> {
+        self.call("subscribe", Value::Null)?;
+        loop {
+            let message = self.read()?;
+            if message["method"] == "feature_changed"
+                && let Some(change) = change_from_json(&message["params"])
+            {
+                on_change(change);
+            }
+        }
+    }
+}
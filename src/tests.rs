@@ -1,6 +1,32 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
-    use crate::features::{self, FeatureId};
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use crate::connection::Connection;
+    use crate::dbus;
+    use crate::diff::{FeatureDiff, ProfileDiff};
+    use crate::eq::{EqFormat, GraphicEq};
+    use crate::features::{self, FeatureGroup, FeatureId};
+    use crate::hotkeys::{Action, Hotkey, KeyCombo, Modifier};
+    use crate::metrics::{self, Histogram, ROUND_TRIP_BUCKETS};
+    use crate::mqtt::{self, MqttConfig, Topics};
+    use crate::notify::{NotificationGroup, Notifier, Verbosity};
+    use crate::profile::{self, PROFILE_VERSION, UnsupportedProfileVersion};
+    use crate::rules::{self, RuleEngine};
+    use crate::sbc;
+    use crate::schedule::{Schedule, ScheduledProfile};
+    use crate::snapshot;
+    use crate::socket;
+    use crate::transaction;
+    use crate::{
+        BlasterXG6, CachedLibrary, ChangeSource, Config, DeviceDescriptor,
+        DeviceModel, DeviceSelector, FeatureChange, MismatchError, Profile,
+        ProfileBank, ProfileLibrary, ProfileMetadata, ProfileScope,
+        ProfileValue, ReadOnlyError, Rule, RuleSet, Trigger,
+        UnsupportedFeatureError, ValueKind,
+    };
 
     #[test]
     fn all_feature_ids_are_registered() {
@@ -131,4 +157,1735 @@ mod tests {
     fn eq_all_constant_has_eleven_entries() {
         assert_eq!(FeatureId::EQ_ALL.len(), 11);
     }
+
+    #[test]
+    fn single_status_request_matches_known_packet() {
+        // 5a 11 03 01 96 07 (read CrystalizerToggle)
+        let packet = features::status_request_packet(&[(0x96, 0x07)]);
+        assert_eq!(packet[0], 0x00, "report id must be prepended");
+        assert_eq!(&packet[1..7], &[0x5a, 0x11, 0x03, 0x01, 0x96, 0x07]);
+        assert!(packet[7..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn batched_status_request_lists_every_address() {
+        let addresses = [(0x96, 0x00), (0x96, 0x01), (0x96, 0x17)];
+        let packet = features::status_request_packet(&addresses);
+        assert_eq!(
+            &packet[1..11],
+            &[0x5a, 0x11, 0x07, 0x03, 0x96, 0x00, 0x96, 0x01, 0x96, 0x17]
+        );
+    }
+
+    #[test]
+    fn parses_single_status_response() {
+        let mut packet = [0u8; 64];
+        packet[..11].copy_from_slice(&[
+            0x5a, 0x11, 0x08, 0x01, 0x00, 0x96, 0x07, 0x00, 0x00, 0x80, 0x3f,
+        ]);
+        assert_eq!(
+            features::parse_status_response(&packet),
+            Some(vec![(0x96, 0x07, 1.0)])
+        );
+    }
+
+    #[test]
+    fn parses_multi_feature_status_response() {
+        let values = [0.5f32, -3.0, 80.0];
+        let ids = [0x08u8, 0x0b, 0x17];
+
+        let mut packet = [0u8; 64];
+        packet[..5].copy_from_slice(&[0x5a, 0x11, 0x14, 0x03, 0x00]);
+        for (index, (&id, value)) in ids.iter().zip(values).enumerate() {
+            let offset = 5 + index * 6;
+            packet[offset] = 0x96;
+            packet[offset + 1] = id;
+            packet[offset + 2..offset + 6]
+                .copy_from_slice(&value.to_le_bytes());
+        }
+
+        assert_eq!(
+            features::parse_status_response(&packet),
+            Some(vec![
+                (0x96, 0x08, 0.5),
+                (0x96, 0x0b, -3.0),
+                (0x96, 0x17, 80.0)
+            ])
+        );
+    }
+
+    #[test]
+    fn non_status_packets_are_not_parsed() {
+        let mut packet = [0u8; 64];
+        packet[..4].copy_from_slice(&[0x5a, 0x02, 0x0a, 0x12]);
+        assert_eq!(features::parse_status_response(&packet), None);
+    }
+
+    fn position(plan: &[(FeatureId, f32)], id: FeatureId) -> usize {
+        plan.iter()
+            .position(|&(planned, _)| planned == id)
+            .unwrap_or_else(|| panic!("{:?} is not part of the plan", id))
+    }
+
+    #[test]
+    fn transaction_enables_dependencies_once_before_values() {
+        let writes = [(FeatureId::Eq31Hz, 2.0), (FeatureId::Eq62Hz, -1.0)];
+        let plan = transaction::plan(&DeviceModel::G6, &writes, |_| 0.0);
+
+        assert_eq!(
+            plan,
+            vec![
+                (FeatureId::SbxMaster, 1.0),
+                (FeatureId::EqToggle, 1.0),
+                (FeatureId::Eq31Hz, 2.0),
+                (FeatureId::Eq62Hz, -1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn transaction_writes_toggle_before_slider() {
+        let writes = [
+            (FeatureId::SurroundLevel, 0.5),
+            (FeatureId::SurroundToggle, 1.0),
+        ];
+        let plan = transaction::plan(&DeviceModel::G6, &writes, |id| {
+            if id == FeatureId::SbxMaster { 1.0 } else { 0.0 }
+        });
+
+        assert!(
+            position(&plan, FeatureId::SurroundToggle)
+                < position(&plan, FeatureId::SurroundLevel)
+        );
+    }
+
+    #[test]
+    fn transaction_disables_toggles_after_their_sliders() {
+        // the slider can only be written while its toggle is ON,
+        // so the toggle is enabled first and switched OFF at the very end
+        let writes = [
+            (FeatureId::BassToggle, 0.0),
+            (FeatureId::BassLevel, 0.7),
+            (FeatureId::SbxMaster, 0.0),
+        ];
+        let plan = transaction::plan(&DeviceModel::G6, &writes, |_| 0.0);
+
+        assert_eq!(
+            plan,
+            vec![
+                (FeatureId::SbxMaster, 1.0),
+                (FeatureId::BassToggle, 1.0),
+                (FeatureId::BassLevel, 0.7),
+                (FeatureId::BassToggle, 0.0),
+                (FeatureId::SbxMaster, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn transaction_skips_unchanged_values() {
+        let writes = [(FeatureId::Eq31Hz, 2.0), (FeatureId::Eq62Hz, -1.0)];
+        let plan =
+            transaction::plan(&DeviceModel::G6, &writes, |id| match id {
+                FeatureId::Eq31Hz => 2.0,
+                FeatureId::Eq62Hz => 0.0,
+                _ => 1.0,
+            });
+
+        assert_eq!(plan, vec![(FeatureId::Eq62Hz, -1.0)]);
+    }
+
+    #[test]
+    fn transaction_writes_scout_mode_last() {
+        let writes = [
+            (FeatureId::ScoutMode, 1.0),
+            (FeatureId::CrystalizerToggle, 1.0),
+        ];
+        let plan = transaction::plan(&DeviceModel::G6, &writes, |_| 0.0);
+
+        assert_eq!(plan.last(), Some(&(FeatureId::ScoutMode, 1.0)));
+    }
+
+    #[test]
+    fn toggles_and_presets_must_match_exactly() {
+        assert!(ValueKind::Toggle.matches(1.0, 1.0));
+        assert!(!ValueKind::Toggle.matches(1.0, 0.0));
+        assert!(!FeatureId::SmartVolMode.value_kind().matches(2.0, 1.0));
+    }
+
+    #[test]
+    fn continuous_values_match_within_tolerance() {
+        assert!(ValueKind::Percentage.matches(0.5, 0.502));
+        assert!(!ValueKind::Percentage.matches(0.5, 0.52));
+
+        let eq = FeatureId::Eq1kHz.value_kind();
+        assert!(eq.matches(2.1, 2.1000001));
+        // clamped by the device
+        assert!(!eq.matches(14.0, 12.0));
+    }
+
+    #[test]
+    fn mismatch_error_reports_requested_and_actual_values() {
+        let error = MismatchError {
+            id: FeatureId::EqPreAmp,
+            requested: 8.0,
+            actual: 6.0,
+            attempts: 3,
+        };
+        assert_eq!(
+            error.to_string(),
+            "EQ Pre-Amp: requested 8, device reports 6 (after 3 attempts)"
+        );
+    }
+
+    #[test]
+    fn dsp_addresses_round_trip() {
+        for &id in FeatureId::ALL {
+            if let Some(address) = id.dsp_address() {
+                assert_eq!(FeatureId::from_dsp_address(address), Some(id));
+            }
+        }
+        assert_eq!(FeatureId::from_dsp_address((0x96, 0x70)), None);
+    }
+
+    #[test]
+    fn decodes_status_push_notification() {
+        let mut packet = [0u8; 64];
+        packet[..11].copy_from_slice(&[
+            0x5a, 0x11, 0x08, 0x01, 0x00, 0x96, 0x18, 0x00, 0x00, 0x80, 0x3f,
+        ]);
+        assert_eq!(
+            features::decode_notification(&DeviceModel::G6, &packet),
+            vec![(FeatureId::BassToggle, 1.0)]
+        );
+    }
+
+    #[test]
+    fn decodes_global_profile_notification() {
+        // Scout ON + EQ ON (SBX auto-disabled)
+        let mut packet = [0u8; 64];
+        packet[..7]
+            .copy_from_slice(&[0x5a, 0x26, 0x0b, 0x08, 0xff, 0xff, 0x06]);
+        assert_eq!(
+            features::decode_notification(&DeviceModel::G6, &packet),
+            vec![
+                (FeatureId::SbxMaster, 0.0),
+                (FeatureId::ScoutMode, 1.0),
+                (FeatureId::EqToggle, 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_output_notification() {
+        let mut packet = [0u8; 64];
+        packet[..5].copy_from_slice(&[0x5a, 0x2c, 0x05, 0x01, 0x04]);
+        assert_eq!(
+            features::decode_notification(&DeviceModel::G6, &packet),
+            vec![(FeatureId::Output, 1.0)]
+        );
+    }
+
+    #[test]
+    fn acks_are_not_notifications() {
+        let mut packet = [0u8; 64];
+        packet[..4].copy_from_slice(&[0x5a, 0x02, 0x0a, 0x12]);
+        assert!(
+            features::decode_notification(&DeviceModel::G6, &packet).is_empty()
+        );
+    }
+
+    fn descriptor(serial: Option<&str>, path: &str) -> DeviceDescriptor {
+        DeviceDescriptor {
+            serial: serial.map(str::to_string),
+            path: path.to_string(),
+            product: Some("Sound Blaster X G6".to_string()),
+            vendor_id: crate::VENDOR_ID,
+            product_id: crate::PRODUCT_ID,
+        }
+    }
+
+    #[test]
+    fn device_selector_parses_serials_and_paths() {
+        assert_eq!(
+            "0123ABCD".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Serial("0123ABCD".to_string())
+        );
+        assert_eq!(
+            "1-2.3:1.4".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Path("1-2.3:1.4".to_string())
+        );
+        assert_eq!(
+            "/dev/hidraw3".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Path("/dev/hidraw3".to_string())
+        );
+    }
+
+    #[test]
+    fn device_selector_matches_descriptors() {
+        let desk = descriptor(Some("DESK"), "1-2:1.4");
+        let headset = descriptor(None, "3-1.4:1.4");
+
+        assert!(DeviceSelector::First.matches(&desk));
+        assert!(DeviceSelector::Serial("DESK".to_string()).matches(&desk));
+        assert!(!DeviceSelector::Serial("DESK".to_string()).matches(&headset));
+        assert!(
+            DeviceSelector::Path("3-1.4:1.4".to_string()).matches(&headset)
+        );
+
+        // prefers the serial, since the path may change after a reset
+        assert_eq!(
+            DeviceSelector::for_descriptor(&desk),
+            DeviceSelector::Serial("DESK".to_string())
+        );
+        assert_eq!(
+            DeviceSelector::for_descriptor(&headset),
+            DeviceSelector::Path("3-1.4:1.4".to_string())
+        );
+    }
+
+    #[test]
+    fn usb_port_from_libusb_path() {
+        assert_eq!(descriptor(None, "3-1.4:1.4").usb_port(), Some("3-1.4"));
+        assert_eq!(descriptor(None, "/dev/hidraw3").usb_port(), None);
+    }
+
+    #[test]
+    fn g6_model_matches_feature_table() {
+        let model = DeviceModel::G6;
+        for &id in FeatureId::ALL {
+            assert!(model.supports(id));
+            assert_eq!(model.dsp_address(id), id.dsp_address());
+        }
+        assert_eq!(
+            DeviceModel::from_ids(crate::VENDOR_ID, crate::PRODUCT_ID),
+            Some(&DeviceModel::G6)
+        );
+        assert_eq!(descriptor(None, "1-2:1.4").model(), Some(&DeviceModel::G6));
+    }
+
+    #[test]
+    fn known_models_have_unique_ids() {
+        for (index, model) in DeviceModel::ALL.iter().enumerate() {
+            assert!(
+                !DeviceModel::ALL[index + 1..].contains(model),
+                "{} shares its ids with another model",
+                model
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_features_have_no_address() {
+        let model = DeviceModel::KATANA;
+        assert!(!model.supports(FeatureId::ScoutMode));
+        assert_eq!(model.dsp_address(FeatureId::ScoutMode), None);
+        assert!(
+            features::features_for(&model)
+                .iter()
+                .all(|feature| model.supports(feature.id))
+        );
+
+        // Scout Mode is dropped from the global profile notification
+        let mut packet = [0u8; 64];
+        packet[..7]
+            .copy_from_slice(&[0x5a, 0x26, 0x0b, 0x08, 0xff, 0xff, 0x03]);
+        assert_eq!(
+            features::decode_notification(&model, &packet),
+            vec![(FeatureId::SbxMaster, 1.0), (FeatureId::EqToggle, 0.0)]
+        );
+
+        let error = UnsupportedFeatureError {
+            id: FeatureId::ScoutMode,
+            model: model.name,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Scout Mode is not supported by the Sound BlasterX Katana"
+        );
+    }
+
+    #[test]
+    fn migrates_unversioned_profiles() {
+        // what `save_profile()` wrote before profiles had a version
+        let legacy = r#"{
+        "features": [
+            { "id": "SurroundToggle", "value": 1.0 },
+            { "id": "SurroundLevel", "value": 0.67 },
+            { "id": "Eq31Hz", "value": null },
+            { "id": "Output", "value": 1.0 }
+        ]
+    }"#;
+
+        let profile = Profile::from_json(legacy).unwrap();
+        assert_eq!(profile.version, PROFILE_VERSION);
+        assert_eq!(profile.metadata, ProfileMetadata::default());
+        assert_eq!(profile.banks.len(), 1);
+        assert_eq!(profile.banks[0].output.as_deref(), Some("Headphones"));
+        assert_eq!(
+            profile.banks[0].values,
+            vec![
+                ProfileValue {
+                    id: FeatureId::SurroundToggle,
+                    value: 1.0
+                },
+                ProfileValue {
+                    id: FeatureId::SurroundLevel,
+                    value: 0.67
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn profile_round_trip() {
+        let profile = Profile::new(
+            ProfileMetadata {
+                name: "Movies".to_string(),
+                created: 1_735_689_600,
+                firmware: Some("2.1.250903.1324".to_string()),
+                model: Some(DeviceModel::G6.name.to_string()),
+                output: Some("Headphones".to_string()),
+                tags: vec!["Night".to_string()],
+                favourite: true,
+            },
+            vec![
+                ProfileBank {
+                    output: Some("Speakers".to_string()),
+                    values: vec![ProfileValue {
+                        id: FeatureId::EqPreAmp,
+                        value: -3.5,
+                    }],
+                },
+                ProfileBank {
+                    output: Some("Headphones".to_string()),
+                    values: vec![ProfileValue {
+                        id: FeatureId::Eq31Hz,
+                        value: 2.0,
+                    }],
+                },
+            ],
+        );
+
+        let json = profile.to_json().unwrap();
+        assert_eq!(Profile::from_json(&json).unwrap(), profile);
+
+        let speakers = profile.bank("Speakers").unwrap();
+        assert_eq!(speakers.value(FeatureId::EqPreAmp), Some(-3.5));
+        assert_eq!(speakers.value(FeatureId::Eq31Hz), None);
+        assert!(profile.bank("Line Out").is_none());
+    }
+
+    #[test]
+    fn output_labels() {
+        let output = FeatureId::Output.value_kind();
+        assert_eq!(output.label(1.0), Some("Headphones"));
+        assert_eq!(output.label(2.0), None);
+        assert_eq!(output.value_of("Speakers"), Some(0.0));
+        assert_eq!(ValueKind::Toggle.label(1.0), None);
+    }
+
+    #[test]
+    fn rejects_unknown_profiles() {
+        let newer = format!(
+            r#"{{ "version": {}, "metadata": {{}}, "banks": [] }}"#,
+            PROFILE_VERSION + 1
+        );
+        let error = profile::migrate(serde_json::from_str(&newer).unwrap())
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<UnsupportedProfileVersion>(),
+            Some(&UnsupportedProfileVersion(PROFILE_VERSION + 1))
+        );
+
+        assert!(Profile::from_json(r#"{ "presets": [] }"#).is_err());
+    }
+
+    #[test]
+    fn parses_firmware_response() {
+        let mut packet = [0u8; 64];
+        let version = b"2.1.250903.1324";
+        packet[..3].copy_from_slice(&[0x5a, 0x07, version.len() as u8]);
+        packet[3..3 + version.len()].copy_from_slice(version);
+        assert_eq!(
+            features::parse_firmware_response(&packet).as_deref(),
+            Some("2.1.250903.1324")
+        );
+
+        packet[1] = 0x02;
+        assert_eq!(features::parse_firmware_response(&packet), None);
+    }
+
+    #[test]
+    fn profile_plan_reaches_exact_state() {
+        // device: Surround ON at 50%, EQ ON with a boosted 31Hz band
+        // profile: Surround OFF but slider saved at 80%, EQ OFF and flat
+        let current = |id: FeatureId| match id {
+            FeatureId::SbxMaster | FeatureId::SurroundToggle => 1.0,
+            FeatureId::SurroundLevel => 0.5,
+            FeatureId::EqToggle => 1.0,
+            FeatureId::Eq31Hz => 6.0,
+            _ => 0.0,
+        };
+        let profile = [
+            (FeatureId::SbxMaster, 1.0),
+            (FeatureId::SurroundToggle, 0.0),
+            (FeatureId::SurroundLevel, 0.8),
+            (FeatureId::EqToggle, 0.0),
+            (FeatureId::Eq31Hz, 0.0),
+            (FeatureId::Eq62Hz, 0.0),
+        ];
+
+        assert_eq!(
+            transaction::plan(&DeviceModel::G6, &profile, current),
+            vec![
+                (FeatureId::SurroundLevel, 0.8),
+                (FeatureId::Eq31Hz, 0.0),
+                (FeatureId::SurroundToggle, 0.0),
+                (FeatureId::EqToggle, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_sbc_json() {
+        let json = r#"{
+        "ProfileName": "Gaming",
+        "Output": "Headphones",
+        "SBX": {
+            "Enabled": true,
+            "Surround": { "Enabled": true, "Level": 67 },
+            "Crystalizer": { "Enabled": false, "Level": 50 }
+        },
+        "Equalizer": {
+            "Enabled": true,
+            "PreAmp": -2.5,
+            "Bands": { "31Hz": 4.0, "1kHz": -1.0 }
+        },
+        "Mixer": { "Line-In": 80 }
+    }"#;
+
+        let import = sbc::parse_json(json, "fallback").unwrap();
+        let bank = &import.profile.banks[0];
+
+        assert_eq!(import.profile.metadata.name, "Gaming");
+        assert_eq!(bank.output.as_deref(), Some("Headphones"));
+        assert_eq!(bank.value(FeatureId::SbxMaster), Some(1.0));
+        assert_eq!(bank.value(FeatureId::SurroundToggle), Some(1.0));
+        assert_eq!(bank.value(FeatureId::SurroundLevel), Some(0.67));
+        assert_eq!(bank.value(FeatureId::CrystalizerToggle), Some(0.0));
+        assert_eq!(bank.value(FeatureId::CrystalizerLevel), Some(0.5));
+        assert_eq!(bank.value(FeatureId::EqPreAmp), Some(-2.5));
+        assert_eq!(bank.value(FeatureId::Eq31Hz), Some(4.0));
+        assert_eq!(bank.value(FeatureId::Eq1kHz), Some(-1.0));
+        assert_eq!(import.unmapped, vec!["Mixer.Line-In = 80".to_string()]);
+    }
+
+    #[test]
+    fn imports_sbc_xml() {
+        let xml = r#"<?xml version="1.0"?>
+        <Profile>
+            <Param name="SmartVolume" value="40"/>
+            <Param name="SmartVolumeMode" value="Night"/>
+            <Equalizer Enabled="1">
+                <Band name="16kHz" value="3"/>
+            </Equalizer>
+            <Lighting Color="FF0000"/>
+        </Profile>"#;
+
+        let import = sbc::parse_xml(xml, "Night").unwrap();
+        let bank = &import.profile.banks[0];
+
+        assert_eq!(import.profile.metadata.name, "Night");
+        assert_eq!(bank.output, None);
+        assert_eq!(bank.value(FeatureId::SmartVolLevel), Some(0.4));
+        assert_eq!(bank.value(FeatureId::SmartVolMode), Some(2.0));
+        assert_eq!(bank.value(FeatureId::EqToggle), Some(1.0));
+        assert_eq!(bank.value(FeatureId::Eq16kHz), Some(3.0));
+        assert_eq!(
+            import.unmapped,
+            vec!["Profile.Lighting.Color = FF0000".to_string()]
+        );
+    }
+
+    #[test]
+    fn sbc_values_are_checked() {
+        let json = r#"{
+        "Surround": { "Enabled": 1.0, "Level": 1 },
+        "Crystalizer": { "Enabled": "0", "Level": 100 },
+        "DialogPlus": { "Enabled": 2, "Level": 0 },
+        "SmartVolumeMode": 7,
+        "Bass": { "Level": 101 }
+    }"#;
+
+        let import = sbc::parse_json(json, "").unwrap();
+        let bank = &import.profile.banks[0];
+
+        assert_eq!(bank.value(FeatureId::SurroundToggle), Some(1.0));
+        assert_eq!(bank.value(FeatureId::SurroundLevel), Some(0.01));
+        assert_eq!(bank.value(FeatureId::CrystalizerToggle), Some(0.0));
+        assert_eq!(bank.value(FeatureId::CrystalizerLevel), Some(1.0));
+        assert_eq!(bank.value(FeatureId::DialogPlusLevel), Some(0.0));
+        assert_eq!(
+            import.unmapped,
+            vec![
+                "Bass.Level = 101".to_string(),
+                "DialogPlus.Enabled = 2".to_string(),
+                "SmartVolumeMode = 7".to_string(),
+            ]
+        );
+    }
+
+    fn sample_eq() -> GraphicEq {
+        GraphicEq {
+            preamp: -4.5,
+            bands: [5.0, 3.5, 1.0, 0.0, -1.5, -2.0, 0.5, 2.5, -3.0, 1.5],
+        }
+    }
+
+    #[test]
+    fn eq_export_round_trip() {
+        let eq = sample_eq();
+        for &format in EqFormat::ALL {
+            let (exported, import) = match format {
+                EqFormat::EqualizerApo => {
+                    let apo = eq.to_apo();
+                    (apo.clone(), GraphicEq::from_apo(&apo))
+                }
+                EqFormat::EasyEffects => {
+                    let preset = eq.to_easyeffects();
+                    (preset.clone(), GraphicEq::from_easyeffects(&preset))
+                }
+                EqFormat::PipeWire => {
+                    let config = eq.to_pipewire();
+                    (config.clone(), GraphicEq::from_pipewire(&config))
+                }
+            };
+            let import = import.unwrap();
+            assert_eq!(import.eq, eq, "{}:\n{}", format, exported);
+            assert!(
+                import.skipped.is_empty(),
+                "{}: {:?}",
+                format,
+                import.skipped
+            );
+        }
+    }
+
+    #[test]
+    fn imports_apo_filters_onto_nearest_band() {
+        let config = "\
+        Preamp: -3 dB
+        Filter 1: ON PK Fc 33 Hz Gain 2.0 dB Q 1.41
+        Filter 2: ON PK Fc 1100 Hz Gain -1.5 dB Q 2.00
+        Filter 3: ON LSC Fc 105 Hz Gain 4.0 dB
+        Filter 4: OFF PK Fc 500 Hz Gain 6.0 dB Q 1.41
+        Filter 5: ON PK Fc 26000 Hz Gain 1.0 dB Q 1.41
+    ";
+
+        let import = GraphicEq::from_apo(config).unwrap();
+        let mut bands = [0.0; 10];
+        bands[0] = 2.0;
+        bands[5] = -1.5;
+        assert_eq!(
+            import.eq,
+            GraphicEq {
+                preamp: -3.0,
+                bands
+            }
+        );
+        assert_eq!(
+            import.skipped,
+            vec![
+                "Filter 3: ON LSC Fc 105 Hz Gain 4.0 dB".to_string(),
+                "Filter 5: ON PK Fc 26000 Hz Gain 1.0 dB Q 1.41".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_graphic_eq_curves() {
+        let curve = "GraphicEQ: 20 1.0; 31 4.0; 40 3.0; 62 2.0; 16000 -1.0";
+        let import = GraphicEq::from_apo(curve).unwrap();
+        assert_eq!(import.eq.bands[0], 4.0);
+        assert_eq!(import.eq.bands[1], 2.0);
+        assert_eq!(import.eq.bands[9], -1.0);
+        assert_eq!(import.eq.bands[4], 0.0);
+    }
+
+    #[test]
+    fn eq_writes_are_clamped() {
+        let eq = GraphicEq {
+            preamp: -9.0,
+            bands: [14.0; 10],
+        };
+        let writes = eq.writes();
+        assert_eq!(writes[0], (FeatureId::EqPreAmp, -6.0));
+        assert_eq!(writes[1], (FeatureId::Eq31Hz, 12.0));
+        assert_eq!(writes.len(), FeatureId::EQ_ALL.len());
+    }
+
+    fn named_profile(name: &str) -> Profile {
+        Profile::new(
+            ProfileMetadata {
+                name: name.to_string(),
+                ..Default::default()
+            },
+            vec![ProfileBank {
+                output: None,
+                values: vec![ProfileValue {
+                    id: FeatureId::BassToggle,
+                    value: 1.0,
+                }],
+            }],
+        )
+    }
+
+    #[test]
+    fn profile_library_manages_profiles() {
+        let root = std::env::temp_dir()
+            .join(format!("linuxblaster-library-{}", std::process::id()));
+        let library = ProfileLibrary::new(&root);
+        assert!(library.list().unwrap().is_empty());
+
+        library.save(&named_profile("music")).unwrap();
+        library.save(&named_profile("Games")).unwrap();
+        assert!(library.save(&named_profile(" ")).is_err());
+
+        let names = |library: &ProfileLibrary| -> Vec<String> {
+            library
+                .list()
+                .unwrap()
+                .iter()
+                .map(|entry| entry.name().to_string())
+                .collect()
+        };
+        assert_eq!(names(&library), ["Games", "music"]);
+
+        library.set_favourite("music", true).unwrap();
+        assert_eq!(names(&library), ["music", "Games"]);
+
+        library.rename("Games", "Movies").unwrap();
+        assert!(library.duplicate("Movies", "music").is_err());
+        library.duplicate("music", "Music Copy").unwrap();
+        assert!(!library.load("Music Copy").unwrap().metadata.favourite);
+        assert_eq!(names(&library), ["music", "Movies", "Music Copy"]);
+
+        let tags = [" night ", "", "night", "bass"].map(str::to_string);
+        library.set_tags("Movies", &tags).unwrap();
+        assert_eq!(
+            library.load("Movies").unwrap().metadata.tags,
+            ["night", "bass"]
+        );
+
+        library.delete("Music Copy").unwrap();
+        assert_eq!(names(&library), ["music", "Movies"]);
+
+        // copied in by hand, named differently from its file
+        named_profile("Late night")
+            .save(&root.join("night.json"))
+            .unwrap();
+        let entries = library.list().unwrap();
+        let copied = entries.iter().find(|entry| entry.name() == "night");
+        assert_eq!(copied.map(|entry| entry.label()), Some("Late night"));
+        library.set_favourite("night", true).unwrap();
+        assert_eq!(names(&library), ["night", "music", "Movies"]);
+        library.delete("night").unwrap();
+        assert_eq!(names(&library), ["music", "Movies"]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn bank(output: Option<&str>, values: &[(FeatureId, f32)]) -> ProfileBank {
+        ProfileBank {
+            output: output.map(str::to_string),
+            values: values
+                .iter()
+                .map(|&(id, value)| ProfileValue { id, value })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn diffs_profiles_by_output() {
+        let from = Profile::new(
+            ProfileMetadata::default(),
+            vec![
+                bank(
+                    Some("Speakers"),
+                    &[
+                        (FeatureId::SurroundLevel, 0.5),
+                        (FeatureId::Eq31Hz, 0.0),
+                    ],
+                ),
+                bank(Some("Headphones"), &[(FeatureId::BassToggle, 1.0)]),
+            ],
+        );
+        // a bank without Output stands in for both
+        let to = Profile::new(
+            ProfileMetadata::default(),
+            vec![bank(
+                None,
+                &[
+                    (FeatureId::SurroundLevel, 0.502),
+                    (FeatureId::BassToggle, 1.0),
+                ],
+            )],
+        );
+
+        let diff = ProfileDiff::between(&DeviceModel::G6, &from, &to);
+        assert_eq!(
+            diff.changes,
+            vec![
+                FeatureDiff {
+                    output: Some("Speakers".to_string()),
+                    id: FeatureId::BassToggle,
+                    from: None,
+                    to: Some(1.0),
+                    kind: DeviceModel::G6.value_kind(FeatureId::BassToggle),
+                },
+                FeatureDiff {
+                    output: Some("Speakers".to_string()),
+                    id: FeatureId::Eq31Hz,
+                    from: Some(0.0),
+                    to: None,
+                    kind: DeviceModel::G6.value_kind(FeatureId::Eq31Hz),
+                },
+                FeatureDiff {
+                    output: Some("Headphones".to_string()),
+                    id: FeatureId::SurroundLevel,
+                    from: None,
+                    to: Some(0.502),
+                    kind: DeviceModel::G6.value_kind(FeatureId::SurroundLevel),
+                },
+            ]
+        );
+        assert!(ProfileDiff::between(&DeviceModel::G6, &to, &to).is_empty());
+    }
+
+    #[test]
+    fn renders_values_by_kind() {
+        let change = FeatureDiff {
+            output: Some("Headphones".to_string()),
+            id: FeatureId::SurroundLevel,
+            from: Some(0.5),
+            to: Some(0.8),
+            kind: DeviceModel::G6.value_kind(FeatureId::SurroundLevel),
+        };
+        assert_eq!(
+            change.to_string(),
+            "[Headphones] Surround Slider: 50% -> 80%"
+        );
+
+        assert_eq!(FeatureId::EqToggle.value_kind().format(1.0), "On");
+        assert_eq!(FeatureId::Eq1kHz.value_kind().format(-2.5), "-2.5 dB");
+        assert_eq!(FeatureId::Output.value_kind().format(1.0), "Headphones");
+        assert_eq!(FeatureId::BassLevel.value_kind().format(f32::NAN), "?");
+        assert_eq!(
+            FeatureId::SurroundDistance.value_kind().format(120.0),
+            "120 cm"
+        );
+    }
+
+    #[test]
+    fn parses_profile_scopes() {
+        let json = r#"{
+        "version": 1,
+        "metadata": {},
+        "scope": ["Equalizer", "SmartVolMode"],
+        "banks": []
+    }"#;
+        let profile = Profile::from_json(json).unwrap();
+        assert_eq!(
+            profile.scope,
+            Some(vec![
+                ProfileScope::Group(FeatureGroup::Equalizer),
+                ProfileScope::Feature(FeatureId::SmartVolMode),
+            ])
+        );
+        assert!(profile.in_scope(FeatureId::Eq16kHz));
+        assert!(profile.in_scope(FeatureId::SmartVolMode));
+        assert!(!profile.in_scope(FeatureId::SmartVolLevel));
+
+        assert_eq!(
+            "SbxToggles".parse::<ProfileScope>().unwrap(),
+            ProfileScope::Group(FeatureGroup::SbxToggles)
+        );
+        assert!("Loudness".parse::<ProfileScope>().is_err());
+    }
+
+    #[test]
+    fn restricts_profiles_to_scope() {
+        let profile = Profile::new(
+            ProfileMetadata::default(),
+            vec![bank(
+                None,
+                &[(FeatureId::BassToggle, 1.0), (FeatureId::EqPreAmp, -2.0)],
+            )],
+        );
+        let eq_only =
+            profile.restrict(&[ProfileScope::Group(FeatureGroup::Equalizer)]);
+        assert_eq!(eq_only.banks[0].values.len(), 1);
+        assert_eq!(eq_only.banks[0].value(FeatureId::EqPreAmp), Some(-2.0));
+
+        // restricting never widens the scope again
+        let none = eq_only.restrict(&[ProfileScope::Group(FeatureGroup::Sbx)]);
+        assert!(none.banks[0].values.is_empty());
+    }
+
+    #[test]
+    fn stacks_profile_layers() {
+        let music = Profile::new(
+            ProfileMetadata::default(),
+            vec![
+                bank(
+                    Some("Speakers"),
+                    &[
+                        (FeatureId::SmartVolMode, 0.0),
+                        (FeatureId::BassLevel, 0.4),
+                    ],
+                ),
+                bank(Some("Headphones"), &[(FeatureId::SmartVolMode, 0.0)]),
+            ],
+        );
+        let mut late_night = Profile::new(
+            ProfileMetadata {
+                name: "Late night".to_string(),
+                ..Default::default()
+            },
+            vec![bank(
+                None,
+                &[(FeatureId::SmartVolMode, 1.0), (FeatureId::BassLevel, 1.0)],
+            )],
+        );
+        late_night.scope =
+            Some(vec![ProfileScope::Feature(FeatureId::SmartVolMode)]);
+
+        let stacked = Profile::stack(&[music.clone(), late_night]).unwrap();
+        assert_eq!(stacked.metadata.name, "Late night");
+        assert_eq!(stacked.scope, None);
+
+        let speakers = stacked.bank("Speakers").unwrap();
+        assert_eq!(speakers.value(FeatureId::SmartVolMode), Some(1.0));
+        // outside the overlay's scope
+        assert_eq!(speakers.value(FeatureId::BassLevel), Some(0.4));
+        let headphones = stacked.bank("Headphones").unwrap();
+        assert_eq!(headphones.value(FeatureId::SmartVolMode), Some(1.0));
+
+        assert_eq!(
+            Profile::stack(std::slice::from_ref(&music)).unwrap().banks,
+            music.banks
+        );
+        assert!(Profile::stack(&[]).is_none());
+    }
+
+    #[test]
+    fn last_state_keeps_other_outputs() {
+        let previous = Profile::new(
+            ProfileMetadata::default(),
+            vec![
+                bank(Some("Speakers"), &[(FeatureId::BassToggle, 1.0)]),
+                bank(Some("Headphones"), &[(FeatureId::BassToggle, 0.0)]),
+            ],
+        );
+        let metadata = ProfileMetadata {
+            output: Some("Headphones".to_string()),
+            ..Default::default()
+        };
+
+        let merged = snapshot::merge(
+            Some(previous),
+            metadata,
+            bank(Some("Headphones"), &[(FeatureId::BassToggle, 1.0)]),
+        );
+        assert_eq!(merged.banks.len(), 2);
+        assert_eq!(
+            merged
+                .bank("Speakers")
+                .unwrap()
+                .value(FeatureId::BassToggle),
+            Some(1.0)
+        );
+        assert_eq!(
+            merged
+                .bank("Headphones")
+                .unwrap()
+                .value(FeatureId::BassToggle),
+            Some(1.0)
+        );
+        assert_eq!(merged.metadata.output.as_deref(), Some("Headphones"));
+
+        let first =
+            snapshot::merge(None, ProfileMetadata::default(), bank(None, &[]));
+        assert_eq!(first.banks.len(), 1);
+    }
+
+    #[test]
+    fn last_state_is_stored_per_device() {
+        let with_serial =
+            snapshot::path(&descriptor(Some("ABC123"), "1-2:1.4"));
+        assert_eq!(with_serial.file_name().unwrap(), "ABC123.json");
+
+        let without_serial = snapshot::path(&descriptor(None, "1-2:1.4"));
+        assert_eq!(without_serial.file_name().unwrap(), "041e-3256.json");
+    }
+
+    #[test]
+    fn config_defaults_fill_missing_fields() {
+        let path = std::env::temp_dir()
+            .join(format!("linuxblaster-config-{}.json", std::process::id()));
+        assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+
+        std::fs::write(&path, "{}").unwrap();
+        assert!(!Config::load_from(&path).unwrap().restore_last_state);
+
+        let config = Config {
+            restore_last_state: true,
+            ..Default::default()
+        };
+        config.save_to(&path).unwrap();
+        assert_eq!(Config::load_from(&path).unwrap(), config);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn feature_ids_parse_from_their_names() {
+        for &id in FeatureId::ALL {
+            let name = format!("{:?}", id);
+            assert_eq!(name.parse::<FeatureId>().unwrap(), id);
+        }
+        assert!("Loudness".parse::<FeatureId>().is_err());
+    }
+
+    #[test]
+    fn dbus_interface_name_matches_constant() {
+        use zbus::object_server::Interface;
+        assert_eq!(dbus::BlasterInterface::name().as_str(), dbus::INTERFACE);
+    }
+
+    #[test]
+    fn socket_notifications_round_trip() {
+        let change = FeatureChange {
+            id: FeatureId::SurroundLevel,
+            old: f32::NAN,
+            new: 60.0,
+            source: ChangeSource::Device,
+        };
+        let json = socket::change_to_json(&change);
+        assert_eq!(json["feature"], "SurroundLevel");
+        assert!(json["old"].is_null());
+
+        let parsed = socket::change_from_json(&json).unwrap();
+        assert_eq!(parsed.id, change.id);
+        assert!(parsed.old.is_nan());
+        assert_eq!(parsed.new, 60.0);
+        assert_eq!(parsed.source, ChangeSource::Device);
+    }
+
+    #[test]
+    fn rules_parse_from_flat_json() {
+        let json = r#"{ "rules": [
+        { "name": "Games", "when": "stream", "application": "steam",
+          "profile": "Gaming", "scope": ["Sbx"] },
+        { "name": "Music", "when": "window", "class": "Spotify",
+          "profile": "AutoEq" }
+    ] }"#;
+        let rules: RuleSet = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            rules.rules[0].trigger,
+            Trigger::Stream {
+                application: "steam".to_string()
+            }
+        );
+        assert_eq!(
+            rules.rules[0].scope,
+            Some(vec![ProfileScope::Group(FeatureGroup::Sbx)])
+        );
+        assert_eq!(rules.rules[1].scope, None);
+
+        let saved = serde_json::to_string(&rules).unwrap();
+        assert_eq!(serde_json::from_str::<RuleSet>(&saved).unwrap(), rules);
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rule = |name: &str, trigger: Trigger| Rule {
+            name: name.to_string(),
+            trigger,
+            profile: name.to_string(),
+            scope: None,
+        };
+        let rules = RuleSet {
+            rules: vec![
+                rule(
+                    "Games",
+                    Trigger::Stream {
+                        application: "steam".to_string(),
+                    },
+                ),
+                rule(
+                    "Music",
+                    Trigger::Window {
+                        class: "spotify".to_string(),
+                    },
+                ),
+            ],
+        };
+
+        let mut context = rules::Context::default();
+        assert!(rules.matching(&context).is_none());
+
+        context.focused = Some("Spotify".to_string());
+        assert_eq!(rules.matching(&context).unwrap().name, "Music");
+
+        context.streams = vec!["Steam".to_string()];
+        assert_eq!(rules.matching(&context).unwrap().name, "Games");
+    }
+
+    #[test]
+    fn decodes_jack_notifications() {
+        let mut packet = [0u8; 64];
+        packet[..5].copy_from_slice(&[0x5a, 0x6e, 0x02, 0x01, 0x00]);
+        assert_eq!(features::decode_jack(&packet), Some(false));
+
+        packet[4] = 0x01;
+        assert_eq!(features::decode_jack(&packet), Some(true));
+
+        // the Output response is no jack notification
+        packet[..5].copy_from_slice(&[0x5a, 0x2c, 0x05, 0x01, 0x04]);
+        assert_eq!(features::decode_jack(&packet), None);
+    }
+
+    #[test]
+    fn hotkeys_parse_from_config() {
+        let hotkey: Hotkey = serde_json::from_str(
+        r#"{ "action": "toggle", "feature": "ScoutMode", "trigger": "CTRL+ALT+S" }"#,
+    )
+    .unwrap();
+        assert_eq!(
+            hotkey.action,
+            Action::Toggle {
+                feature: FeatureId::ScoutMode
+            }
+        );
+        assert_eq!(hotkey.action.id(), "toggle-ScoutMode");
+
+        let hotkey: Hotkey = serde_json::from_str(
+            r#"{ "action": "next_profile", "trigger": "XF86AudioNext" }"#,
+        )
+        .unwrap();
+        assert_eq!(hotkey.action, Action::NextProfile);
+    }
+
+    #[test]
+    fn hotkey_triggers_map_to_key_codes() {
+        let combo = KeyCombo::parse("CTRL+ALT+S").unwrap();
+        assert_eq!(combo.modifiers, vec![Modifier::Ctrl, Modifier::Alt]);
+        assert_eq!(combo.key, 31);
+
+        assert_eq!(KeyCombo::parse("LOGO+F12").unwrap().key, 88);
+        assert_eq!(KeyCombo::parse("XF86AudioPlay").unwrap().key, 164);
+        assert_eq!(KeyCombo::parse("0").unwrap().key, 11);
+        assert!(KeyCombo::parse("HYPER+S").is_none());
+        assert!(KeyCombo::parse("CTRL+Unknown").is_none());
+    }
+
+    #[test]
+    fn notifications_follow_group_verbosity() {
+        assert_eq!(
+            NotificationGroup::of(FeatureId::Output),
+            NotificationGroup::Output
+        );
+        assert_eq!(
+            NotificationGroup::of(FeatureId::ScoutMode),
+            NotificationGroup::Sbx
+        );
+        assert_eq!(
+            NotificationGroup::of(FeatureId::CrystalizerLevel),
+            NotificationGroup::Sbx
+        );
+        assert_eq!(
+            NotificationGroup::of(FeatureId::EQ_BANDS[0]),
+            NotificationGroup::Equalizer
+        );
+
+        let change = |id, new, source| FeatureChange {
+            id,
+            old: 0.0,
+            new,
+            source,
+        };
+        let changes = [
+            change(FeatureId::Output, 1.0, ChangeSource::Device),
+            change(FeatureId::SbxMaster, 1.0, ChangeSource::Local),
+            change(FeatureId::SbxMaster, 0.0, ChangeSource::Local),
+            change(FeatureId::CrystalizerLevel, 0.4, ChangeSource::Local),
+            change(FeatureId::ScoutMode, 1.0, ChangeSource::Refresh),
+        ];
+
+        let mut notifier = Notifier::default();
+        assert_eq!(
+            notifier.describe(&DeviceModel::G6, &changes),
+            vec!["Output: Headphones", "SBX: Off"]
+        );
+
+        notifier.verbosity = [
+            (NotificationGroup::Output, Verbosity::Off),
+            (NotificationGroup::Sbx, Verbosity::All),
+        ]
+        .into();
+        assert_eq!(
+            notifier.describe(&DeviceModel::G6, &changes),
+            vec!["SBX: Off", "Crystalizer Slider: 40%"]
+        );
+    }
+
+    #[test]
+    fn schedule_picks_the_entry_that_ran_last() {
+        let schedule: Schedule = serde_json::from_str(
+            r#"{ "entries": [
+            { "name": "Night", "at": "22:00", "profile": "Night",
+              "scope": ["SmartVolMode"] },
+            { "name": "Morning", "at": "07:30", "profile": "Default" },
+            { "name": "Weekend", "at": "10:00", "days": ["Sat", "Sun"],
+              "profile": "Music" },
+            { "name": "Christmas", "at": "18:00", "dates": ["2026-12-24"],
+              "profile": "Party" }
+        ] }"#,
+        )
+        .unwrap();
+        let current = |time: &str| {
+            let now =
+                chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M")
+                    .unwrap();
+            schedule.current(now).map(|(entry, _)| entry.name.as_str())
+        };
+
+        // Friday
+        assert_eq!(current("2026-10-16 06:00"), Some("Night"));
+        assert_eq!(current("2026-10-16 07:30"), Some("Morning"));
+        assert_eq!(current("2026-10-16 23:00"), Some("Night"));
+        // Saturday
+        assert_eq!(current("2026-10-17 09:00"), Some("Morning"));
+        assert_eq!(current("2026-10-17 12:00"), Some("Weekend"));
+        // Thursday
+        assert_eq!(current("2026-12-24 19:00"), Some("Christmas"));
+        assert_eq!(current("2026-12-24 22:00"), Some("Night"));
+
+        assert_eq!(schedule.entries[2].to_string(), "at 10:00 on Sat, Sun");
+        let json = serde_json::to_value(&schedule.entries[0]).unwrap();
+        assert_eq!(json["at"], "22:00");
+        assert!(
+            serde_json::from_str::<ScheduledProfile>(
+                r#"{ "name": "Bad", "at": "25:00", "profile": "Night" }"#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn round_trip_histogram_is_cumulative() {
+        let histogram = Histogram::default();
+        for millis in [1, 3, 3, 40, 5000] {
+            histogram.observe(std::time::Duration::from_millis(millis));
+        }
+
+        let counts = histogram.cumulative();
+        assert_eq!(counts.len(), ROUND_TRIP_BUCKETS.len() + 1);
+        // le 0.001, 0.0025, 0.005, ... 0.05
+        assert_eq!(&counts[..6], &[1, 1, 3, 3, 3, 4]);
+        assert_eq!(counts[ROUND_TRIP_BUCKETS.len() - 1], 4);
+        assert_eq!(counts.last(), Some(&5));
+        assert_eq!(histogram.sum().as_millis(), 5047);
+    }
+
+    #[test]
+    fn mqtt_payloads_round_trip() {
+        let toggle = FeatureId::SbxMaster.value_kind();
+        assert_eq!(mqtt::encode_state(toggle, 1.0).as_deref(), Some("ON"));
+        assert_eq!(mqtt::decode_command(toggle, "off"), Some(0.0));
+        assert_eq!(mqtt::decode_command(toggle, "toggle"), None);
+
+        let percentage = FeatureId::SurroundLevel.value_kind();
+        assert_eq!(mqtt::encode_state(percentage, 0.6).as_deref(), Some("60"));
+        assert_eq!(mqtt::decode_command(percentage, "60"), Some(0.6));
+        assert_eq!(mqtt::decode_command(percentage, "150"), Some(1.0));
+
+        let ranged = FeatureId::Eq1kHz.value_kind();
+        assert_eq!(mqtt::encode_state(ranged, -2.5).as_deref(), Some("-2.5"));
+        assert_eq!(mqtt::decode_command(ranged, "-20"), Some(-12.0));
+
+        let output = FeatureId::Output.value_kind();
+        assert_eq!(
+            mqtt::encode_state(output, 1.0).as_deref(),
+            Some("Headphones")
+        );
+        assert_eq!(mqtt::decode_command(output, "Speakers"), Some(0.0));
+        assert_eq!(mqtt::encode_state(output, f32::NAN), None);
+    }
+
+    #[test]
+    fn mqtt_discovery_matches_value_kinds() {
+        let topics = Topics::new(&MqttConfig::default(), "ABC123");
+        assert_eq!(
+            topics.command(FeatureId::Output),
+            "linuxblaster/ABC123/Output/set"
+        );
+        assert_eq!(
+            topics.parse_command("linuxblaster/ABC123/ScoutMode/set"),
+            Some(FeatureId::ScoutMode)
+        );
+        assert_eq!(
+            topics.parse_command("linuxblaster/OTHER/ScoutMode/set"),
+            None
+        );
+
+        let model = &DeviceModel::G6;
+        let (topic, payload) = topics.discovery(model, FeatureId::SbxMaster);
+        assert_eq!(topic, "homeassistant/switch/ABC123/SbxMaster/config");
+        assert_eq!(
+            payload["state_topic"],
+            "linuxblaster/ABC123/SbxMaster/state"
+        );
+        assert_eq!(
+            payload["availability_topic"],
+            "linuxblaster/ABC123/availability"
+        );
+
+        let (topic, payload) =
+            topics.discovery(model, FeatureId::SurroundLevel);
+        assert_eq!(topic, "homeassistant/number/ABC123/SurroundLevel/config");
+        assert_eq!(payload["max"], 100);
+
+        let (_, payload) = topics.discovery(model, FeatureId::Eq1kHz);
+        assert_eq!(payload["step"], 0.5);
+        assert_eq!(payload["unit_of_measurement"], "dB");
+        let (_, payload) = topics.discovery(model, FeatureId::SurroundDistance);
+        assert_eq!(payload["max"], 300.0);
+        assert_eq!(payload["step"], 1.0);
+        assert_eq!(payload["unit_of_measurement"], "cm");
+
+        let (topic, payload) = topics.discovery(model, FeatureId::Output);
+        assert_eq!(topic, "homeassistant/select/ABC123/Output/config");
+        assert_eq!(
+            payload["options"],
+            serde_json::json!(["Speakers", "Headphones"])
+        );
+    }
+
+    /// A `BlasterXG6` without a device, so anything it sends fails.
+    fn detached(model: &'static DeviceModel) -> BlasterXG6 {
+        BlasterXG6 {
+            features: features::features_for(model),
+            write_retries: 0,
+            subscribers: Mutex::new(Vec::new()),
+            headphones: Mutex::new(None),
+            unsaved_since: Mutex::new(None),
+            reconnected_at: Mutex::new(None),
+            output_changed_at: Mutex::new(None),
+            read_only: false,
+            remote: None,
+            connection: Connection::detached(model),
+        }
+    }
+
+    #[test]
+    fn transactions_work_without_output() {
+        let blaster = detached(&DeviceModel::G5);
+        blaster.feature(FeatureId::SbxMaster).set_cached(1.0);
+        blaster.feature(FeatureId::EqPreAmp).set_cached(-2.0);
+
+        // already matches, so nothing is written
+        let mut transaction = blaster.transaction();
+        transaction
+            .set(FeatureId::SbxMaster, 1.0)
+            .set(FeatureId::EqPreAmp, -2.0);
+        transaction.commit().unwrap();
+
+        let mut transaction = blaster.transaction();
+        transaction.set(FeatureId::Output, 1.0);
+        let error = transaction.commit().unwrap_err();
+        assert!(error.downcast_ref::<UnsupportedFeatureError>().is_some());
+    }
+
+    #[test]
+    fn diffs_only_the_cached_output() {
+        let blaster = detached(&DeviceModel::G6);
+        blaster.feature(FeatureId::Output).set_cached(0.0);
+        blaster.feature(FeatureId::BassToggle).set_cached(0.0);
+
+        let profile = Profile::new(
+            ProfileMetadata::default(),
+            vec![
+                bank(Some("Speakers"), &[(FeatureId::BassToggle, 1.0)]),
+                bank(Some("Headphones"), &[(FeatureId::BassToggle, 1.0)]),
+            ],
+        );
+
+        // the Headphones bank isn't read, its current value is unknown
+        let diff = blaster.diff(&profile);
+        assert_eq!(
+            diff.to_string(),
+            "[Speakers] Bass: Off -> On\n[Headphones] Bass: ? -> On\n"
+        );
+    }
+
+    #[test]
+    fn device_changes_right_after_reconnect_are_not_saved() {
+        let blaster = detached(&DeviceModel::G6);
+        let set_sbx = |source, value| {
+            blaster.observe(source, || {
+                blaster.feature(FeatureId::SbxMaster).set_cached(value)
+            })
+        };
+        let unsaved = || blaster.unsaved_since.lock().unwrap().is_some();
+
+        *blaster.reconnected_at.lock().unwrap() = Some(Instant::now());
+        set_sbx(ChangeSource::Device, 1.0);
+        set_sbx(ChangeSource::Refresh, 0.0);
+        assert!(!unsaved());
+
+        set_sbx(ChangeSource::Local, 1.0);
+        assert!(unsaved());
+        blaster.save_last_state();
+        assert!(!unsaved());
+    }
+
+    #[test]
+    fn rejects_values_outside_of_the_value_kind() {
+        assert!(ValueKind::Percentage.contains(0.6));
+        assert!(!ValueKind::Percentage.contains(60.0));
+        assert!(FeatureId::SmartVolMode.value_kind().contains(2.0));
+        assert!(!FeatureId::SmartVolMode.value_kind().contains(1.5));
+        assert!(!FeatureId::Eq1kHz.value_kind().contains(12.5));
+
+        // rejected before anything is written
+        let blaster = detached(&DeviceModel::G6);
+        let error = blaster
+            .set_feature(FeatureId::SurroundLevel, Some(60.0))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "60 is out of range for Surround Slider, expected 0.0 - 1.0"
+        );
+    }
+
+    #[test]
+    fn attaches_to_a_served_device() {
+        let path = std::env::temp_dir()
+            .join(format!("linuxblaster-attach-{}.sock", std::process::id()));
+        let served = detached(&DeviceModel::G5);
+        served.feature(FeatureId::SbxMaster).set_cached(1.0);
+        served.feature(FeatureId::SurroundLevel).set_cached(0.4);
+        let served = Arc::new(Mutex::new(served));
+        let server = socket::Server::serve_at(served.clone(), &path).unwrap();
+
+        let attached = BlasterXG6::attach_at(&path).unwrap();
+        assert!(attached.is_attached());
+        assert_eq!(attached.model(), &DeviceModel::G5);
+        assert_eq!(attached.feature(FeatureId::SbxMaster).value(), 1.0);
+        assert_eq!(attached.feature(FeatureId::SurroundLevel).value(), 0.4);
+
+        // changes of the daemon are followed
+        let changes = attached.subscribe();
+        let change = FeatureChange {
+            id: FeatureId::SurroundLevel,
+            old: 0.4,
+            new: 0.7,
+            source: ChangeSource::Device,
+        };
+        let deadline = Instant::now() + std::time::Duration::from_secs(2);
+        while attached.feature(FeatureId::SurroundLevel).value() != 0.7 {
+            assert!(Instant::now() < deadline, "change never arrived");
+            server.broadcast(&change);
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            attached.poll_notifications();
+        }
+        assert_eq!(changes.try_recv().unwrap().new, 0.7);
+
+        drop(server);
+    }
+
+    #[test]
+    fn serves_clients_and_drops_stalled_subscribers() {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+        use std::time::Duration;
+
+        let path = std::env::temp_dir()
+            .join(format!("linuxblaster-serve-{}.sock", std::process::id()));
+        let served = detached(&DeviceModel::G6);
+        served.feature(FeatureId::SurroundLevel).set_cached(0.4);
+        let served = Arc::new(Mutex::new(served));
+        let server = socket::Server::serve_at(served, &path).unwrap();
+
+        let mut client = socket::Client::connect_to(&path).unwrap();
+        let state = client.call("state", serde_json::Value::Null).unwrap();
+        assert_eq!(state["SurroundLevel"].as_f64(), Some(0.4f32 as f64));
+        let error = client
+            .set(FeatureId::SurroundLevel, Some(40.0))
+            .unwrap_err();
+        assert!(error.to_string().contains("out of range"), "{}", error);
+
+        // subscribes, but never reads its notifications
+        let mut stalled = UnixStream::connect(&path).unwrap();
+        writeln!(
+            stalled,
+            r#"{{"jsonrpc":"2.0","id":1,"method":"subscribe"}}"#
+        )
+        .unwrap();
+        let watcher = socket::Client::connect_to(&path).unwrap();
+        let (sender, received) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = watcher.watch(|change| {
+                let _ = sender.send(change);
+            });
+        });
+        std::thread::sleep(Duration::from_millis(100));
+
+        let change = FeatureChange {
+            id: FeatureId::SurroundLevel,
+            old: 0.4,
+            new: 0.7,
+            source: ChangeSource::Device,
+        };
+        let started = Instant::now();
+        // far more than a socket buffers
+        for _ in 0..20_000 {
+            server.broadcast(&change);
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // the stalled client was disconnected, the other one still follows
+        stalled
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let mut rest = Vec::new();
+        stalled.read_to_end(&mut rest).unwrap();
+        let change = received.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(change.new, 0.7);
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn reverting_a_rule_keeps_later_changes() {
+        let blaster = detached(&DeviceModel::G6);
+        let revert = Profile::new(
+            ProfileMetadata::default(),
+            vec![bank(
+                Some("Speakers"),
+                &[(FeatureId::SbxMaster, 0.0), (FeatureId::SurroundLevel, 0.3)],
+            )],
+        );
+        // the rule set both, then a schedule changed SurroundLevel
+        let applied = vec![
+            ProfileValue {
+                id: FeatureId::SbxMaster,
+                value: 1.0,
+            },
+            ProfileValue {
+                id: FeatureId::SurroundLevel,
+                value: 0.8,
+            },
+        ];
+        blaster.feature(FeatureId::SbxMaster).set_cached(1.0);
+        blaster.feature(FeatureId::SurroundLevel).set_cached(0.5);
+
+        let revert = RuleEngine::untouched(&blaster, revert, &applied);
+        assert_eq!(
+            revert.banks[0].values,
+            vec![ProfileValue {
+                id: FeatureId::SbxMaster,
+                value: 0.0,
+            }]
+        );
+        assert_eq!(
+            revert.scope,
+            Some(vec![ProfileScope::Feature(FeatureId::SbxMaster)])
+        );
+    }
+
+    #[test]
+    fn cached_library_follows_file_changes() {
+        let root = std::env::temp_dir()
+            .join(format!("linuxblaster-cached-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let library = ProfileLibrary::new(&root);
+        let mut cached = CachedLibrary::new(library.clone());
+        assert!(cached.entries().is_empty());
+
+        let profile = |name: &str| {
+            Profile::new(
+                ProfileMetadata {
+                    name: name.to_string(),
+                    ..ProfileMetadata::default()
+                },
+                Vec::new(),
+            )
+        };
+        library.save(&profile("music")).unwrap();
+        library.save(&profile("movies")).unwrap();
+        assert_eq!(cached.entries().len(), 2);
+
+        library.set_favourite("music", true).unwrap();
+        assert!(cached.entries()[0].profile.metadata.favourite);
+
+        library.delete("movies").unwrap();
+        assert_eq!(cached.entries().len(), 1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a G6 and an MQTT broker at localhost:1883"]
+    fn mqtt_commands_reach_the_device() {
+        use rumqttc::{Client, MqttOptions, QoS};
+        use std::time::Duration;
+
+        let blaster = BlasterXG6::open(&DeviceSelector::First).unwrap();
+        let id = FeatureId::SurroundLevel;
+        let before = blaster.feature_value(id).unwrap();
+        let target = if before > 0.5 { 0.25 } else { 0.75 };
+        let config = MqttConfig::default();
+        let topics = Topics::new(&config, &Topics::node_of(&blaster));
+        let blaster = Arc::new(Mutex::new(blaster));
+        let _bridge = mqtt::Bridge::start(blaster.clone(), &config).unwrap();
+
+        let (client, mut connection) = Client::new(
+            MqttOptions::new("linuxblaster_test", &config.host, config.port),
+            16,
+        );
+        std::thread::spawn(move || for _ in connection.iter() {});
+
+        // commands aren't retained, so repeat until the bridge subscribed
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let payload = mqtt::encode_state(id.value_kind(), target).unwrap();
+        while (blaster.lock().unwrap().feature(id).value() - target).abs()
+            > 0.01
+        {
+            assert!(Instant::now() < deadline, "command never applied");
+            client
+                .publish(
+                    topics.command(id),
+                    QoS::AtLeastOnce,
+                    false,
+                    payload.clone(),
+                )
+                .unwrap();
+            std::thread::sleep(Duration::from_millis(500));
+        }
+
+        blaster
+            .lock()
+            .unwrap()
+            .set_feature(id, Some(before))
+            .unwrap();
+    }
+
+    #[test]
+    fn unverified_models_are_read_only() {
+        let mut blaster = detached(&DeviceModel::G8);
+        blaster.read_only = true;
+
+        let error = blaster
+            .set_feature(FeatureId::SbxMaster, Some(1.0))
+            .unwrap_err();
+        assert!(error.downcast_ref::<ReadOnlyError>().is_some());
+        let error = blaster
+            .transaction()
+            .set(FeatureId::SurroundLevel, 0.5)
+            .commit()
+            .unwrap_err();
+        assert!(error.downcast_ref::<ReadOnlyError>().is_some());
+        assert!(blaster.reset().is_err());
+    }
+
+    #[test]
+    fn sbc_nested_names_are_not_the_profile_name() {
+        let json = r#"{
+        "Equalizer": { "Preset": { "Name": "Flat" } },
+        "Name": "Movie",
+        "Device": { "Name": "Sound BlasterX G6" }
+    }"#;
+
+        let import = sbc::parse_json(json, "fallback").unwrap();
+
+        assert_eq!(import.profile.metadata.name, "Movie");
+        assert_eq!(
+            import.unmapped,
+            vec![
+                "Device.Name = Sound BlasterX G6".to_string(),
+                "Equalizer.Preset.Name = Flat".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn deferred_packets_reach_poll_notifications() {
+        let blaster = detached(&DeviceModel::G6);
+
+        // a global profile push that arrived while waiting for a ping
+        let mut packet = [0u8; 64];
+        packet[..7]
+            .copy_from_slice(&[0x5a, 0x26, 0x0b, 0x08, 0xff, 0xff, 0x05]);
+        blaster.connection.defer(packet);
+        blaster.poll_notifications();
+
+        assert_eq!(blaster.feature(FeatureId::SbxMaster).value(), 1.0);
+        assert_eq!(blaster.feature(FeatureId::ScoutMode).value(), 0.0);
+        assert_eq!(blaster.feature(FeatureId::EqToggle).value(), 1.0);
+        assert!(blaster.connection.take_deferred().is_none());
+    }
+
+    #[test]
+    fn write_failures_are_returned() {
+        let blaster = detached(&DeviceModel::G6);
+
+        assert!(
+            blaster
+                .set_feature(FeatureId::SbxMaster, Some(1.0))
+                .is_err()
+        );
+        assert!(
+            blaster
+                .transaction()
+                .set(FeatureId::EqPreAmp, 3.0)
+                .commit()
+                .is_err()
+        );
+        assert!(blaster.reset().is_err());
+        // the cache is left alone
+        assert!(blaster.feature(FeatureId::SbxMaster).value().is_nan());
+    }
+
+    #[test]
+    fn renders_openmetrics_exposition() {
+        let blaster = detached(&DeviceModel::G6);
+        blaster.feature(FeatureId::Output).set_cached(1.0);
+        blaster.feature(FeatureId::SbxMaster).set_cached(1.0);
+
+        let exposition = metrics::render(&blaster);
+        let lines: Vec<&str> = exposition.lines().collect();
+
+        for line in [
+            "linuxblaster_device_info{model=\"Sound BlasterX G6\",serial=\"\"} 1",
+            "linuxblaster_connected 1",
+            "linuxblaster_feature_value{feature=\"SbxMaster\",name=\"SBX\"} 1",
+            "linuxblaster_output{linuxblaster_output=\"Speakers\"} 0",
+            "linuxblaster_output{linuxblaster_output=\"Headphones\"} 1",
+            "linuxblaster_ack_failures_total 0",
+            "linuxblaster_reconnects_total 0",
+            "linuxblaster_hid_round_trip_seconds_count 0",
+        ] {
+            assert!(lines.contains(&line), "missing {:?}", line);
+        }
+        // never read
+        assert!(lines.contains(
+            &"linuxblaster_feature_value{feature=\"BassLevel\",name=\"Bass Slider\"} NaN"
+        ));
+        assert!(lines.contains(&"# TYPE linuxblaster_output stateset"));
+        assert_eq!(lines.last(), Some(&"# EOF"));
+    }
 }
@@ -0,0 +1,349 @@
+use std::error::Error;
+
+use hidapi::HidResult;
+use tracing::{debug, error, info, warn};
+
+use crate::remote;
+use crate::{
+    BlasterXG6, ChangeSource, DeviceModel, Feature, FeatureId, MismatchError,
+    TransactionError, ValueKind,
+};
+
+/// Collects multiple writes and applies them in one go.
+///
+/// Compared to calling `BlasterXG6::set_feature()` for every value,
+/// a transaction:
+/// - resolves dependencies once for all queued writes,
+/// - writes in a safe order (toggles before the sliders they gate,
+///   toggles that end up OFF after everything else),
+/// - skips writes whose value already matches the cached state,
+/// - re-reads and verifies every touched feature once at the end,
+/// - retries writes that could not be verified (`write_retries`),
+/// - restores the previous values if anything still doesn't match
+///   and returns a `TransactionError`.
+///
+/// ```ignore
+/// let mut transaction = blaster.transaction();
+/// transaction
+///     .set(FeatureId::EqPreAmp, -3.0)
+///     .set(FeatureId::Eq31Hz, 2.5);
+/// transaction.commit()?;
+/// ```
+pub struct Transaction<'a> {
+    blaster: &'a BlasterXG6,
+    writes: Vec<(FeatureId, f32)>,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(blaster: &'a BlasterXG6) -> Self {
+        Self {
+            blaster,
+            writes: Vec::new(),
+        }
+    }
+
+    /// Queues a write.
+    /// Queuing the same feature twice replaces the earlier value.
+    pub fn set(&mut self, id: FeatureId, value: f32) -> &mut Self {
+        match self.writes.iter_mut().find(|(queued, _)| *queued == id) {
+            Some(write) => write.1 = value,
+            None => self.writes.push((id, value)),
+        }
+        self
+    }
+
+    /// The queued writes, in the order they were queued.
+    pub fn writes(&self) -> &[(FeatureId, f32)] {
+        &self.writes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Writes everything to the device and verifies the result.
+    ///
+    /// If the Output is part of the transaction, it is switched first
+    /// (and the state re-read), so all other writes land in the settings
+    /// profile of the new Output.
+    ///
+    /// On failure, every written feature is restored to its previous value
+    /// (including the Output) and a `TransactionError` listing every
+    /// mismatch is returned.
+    ///
+    /// Nothing is written if any queued feature is unsupported by the model,
+    /// an `UnsupportedFeatureError` is returned instead.
+    pub fn commit(&self) -> Result<(), Box<dyn Error>> {
+        if self.writes.is_empty() {
+            return Ok(());
+        }
+
+        for &(id, _) in &self.writes {
+            self.blaster.check_supported(id)?;
+        }
+        self.blaster.check_writable()?;
+
+        if self.blaster.is_attached() {
+            return self.commit_remote();
+        }
+
+        self.blaster
+            .observe(ChangeSource::Local, || self.commit_observed())
+    }
+
+    /// The daemon owns the device, so it runs the transaction:
+    /// the Output is switched first, the rest is sent as a scoped profile.
+    fn commit_remote(&self) -> Result<(), Box<dyn Error>> {
+        let blaster = self.blaster;
+        if let Some(&(_, output)) =
+            self.writes.iter().find(|(id, _)| *id == FeatureId::Output)
+        {
+            blaster.set_feature(FeatureId::Output, Some(output))?;
+        }
+        if self.writes.iter().any(|(id, _)| *id != FeatureId::Output) {
+            blaster.apply(&remote::transaction_profile(&self.writes))?;
+        }
+        Ok(())
+    }
+
+    fn commit_observed(&self) -> Result<(), Box<dyn Error>> {
+        let blaster = self.blaster;
+
+        // models without software output switching have no Output
+        let previous_output = blaster
+            .supports(FeatureId::Output)
+            .then(|| blaster.feature(FeatureId::Output).value());
+        let output_write = self
+            .writes
+            .iter()
+            .find(|(id, _)| *id == FeatureId::Output)
+            .filter(|(_, value)| Some(*value) != previous_output);
+
+        if let Some(&(_, output)) = output_write {
+            debug!("Transaction: switching Output first");
+            blaster
+                .feature(FeatureId::Output)
+                .write_to_device(&blaster.connection, output)?;
+            blaster.refresh_state();
+        }
+
+        let writes: Vec<(FeatureId, f32)> = self
+            .writes
+            .iter()
+            .copied()
+            .filter(|(id, _)| *id != FeatureId::Output)
+            .collect();
+
+        let snapshot: Vec<(FeatureId, f32)> = blaster
+            .features
+            .iter()
+            .map(|feature| (feature.id, feature.value()))
+            .collect();
+        let current = |id: FeatureId| blaster.feature(id).value();
+
+        let planned = plan(blaster.model(), &writes, current);
+        debug!("Transaction plan: {:?}", planned);
+
+        self.execute(&planned)?;
+
+        let mut mismatches = self.mismatches(&writes, 1);
+        for attempt in 1..=blaster.write_retries {
+            if mismatches.is_empty() {
+                break;
+            }
+            warn!(
+                "Transaction verification failed for {} features, retry {}/{}",
+                mismatches.len(),
+                attempt,
+                blaster.write_retries
+            );
+            let retry: Vec<(FeatureId, f32)> = mismatches
+                .iter()
+                .map(|mismatch| (mismatch.id, mismatch.requested))
+                .collect();
+            self.execute(&plan(blaster.model(), &retry, current))?;
+            mismatches = self.mismatches(&writes, attempt + 1);
+        }
+
+        if mismatches.is_empty() {
+            info!("Transaction committed ({} writes)", planned.len());
+            return Ok(());
+        }
+
+        let error = TransactionError { mismatches };
+        error!("{}", error);
+
+        let touched: Vec<FeatureId> =
+            planned.iter().map(|&(id, _)| id).collect();
+        let restore: Vec<(FeatureId, f32)> = snapshot
+            .into_iter()
+            .filter(|(id, value)| touched.contains(id) && !value.is_nan())
+            .collect();
+        self.execute(&plan_restore(blaster.model(), &restore))?;
+
+        if output_write.is_some()
+            && let Some(previous_output) = previous_output
+        {
+            blaster
+                .feature(FeatureId::Output)
+                .write_to_device(&blaster.connection, previous_output)?;
+            blaster.refresh_state();
+        }
+
+        Err(Box::new(error))
+    }
+
+    /// Compares the cached (freshly read) values against the requested ones.
+    fn mismatches(
+        &self,
+        writes: &[(FeatureId, f32)],
+        attempts: usize,
+    ) -> Vec<MismatchError> {
+        writes
+            .iter()
+            .filter_map(|&(id, requested)| {
+                let actual = self.blaster.feature(id).value();
+                let value_kind = self.blaster.model().value_kind(id);
+                (!value_kind.matches(requested, actual)).then_some(
+                    MismatchError {
+                        id,
+                        requested,
+                        actual,
+                        attempts,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Writes the planned values in order and re-reads everything
+    /// that could have been affected, once.
+    /// Stops at the first write that can't be sent.
+    fn execute(&self, plan: &[(FeatureId, f32)]) -> HidResult<()> {
+        let blaster = self.blaster;
+
+        for &(id, value) in plan {
+            debug!("Transaction write: {} = {}", id, value);
+            blaster
+                .feature(id)
+                .write_to_device(&blaster.connection, value)?;
+        }
+
+        let mut affected: Vec<FeatureId> = Vec::new();
+        for &(id, _) in plan {
+            for &affected_id in std::iter::once(&id).chain(id.dependents()) {
+                if !affected.contains(&affected_id) {
+                    affected.push(affected_id);
+                }
+            }
+        }
+        // SBX and Scout Mode switch each other off
+        if affected.contains(&FeatureId::ScoutMode)
+            && !affected.contains(&FeatureId::SbxMaster)
+        {
+            affected.push(FeatureId::SbxMaster);
+        }
+
+        let features: Vec<&Feature> = affected
+            .iter()
+            .filter(|&&id| blaster.supports(id))
+            .map(|&id| blaster.feature(id))
+            .collect();
+        blaster.read_features_from_device(&features);
+        Ok(())
+    }
+}
+
+/// Orders the writes of a transaction:
+/// 1. dependencies of the changed values that are currently OFF,
+/// 2. toggles switched ON (outer toggles first),
+/// 3. all other values,
+/// 4. toggles that end up OFF (inner toggles first),
+///    including dependencies from step 1 that are written as OFF,
+/// 5. Scout Mode, since enabling it disables SBX.
+///
+/// Writes that already match the current value are dropped.
+pub(crate) fn plan(
+    model: &DeviceModel,
+    writes: &[(FeatureId, f32)],
+    current: impl Fn(FeatureId) -> f32,
+) -> Vec<(FeatureId, f32)> {
+    let is_toggle =
+        |id: FeatureId| matches!(model.value_kind(id), ValueKind::Toggle);
+    let changed: Vec<(FeatureId, f32)> = writes
+        .iter()
+        .copied()
+        .filter(|&(id, value)| current(id) != value)
+        .collect();
+
+    let mut enable: Vec<(FeatureId, f32)> = Vec::new();
+    for &(id, _) in &changed {
+        for &dependency in id.dependencies() {
+            if current(dependency) != 1.0
+                && !enable.iter().any(|&(queued, _)| queued == dependency)
+            {
+                enable.push((dependency, 1.0));
+            }
+        }
+    }
+    enable.sort_by_key(|(id, _)| id.dependencies().len());
+    let enabled =
+        |id: FeatureId| enable.iter().any(|&(queued, _)| queued == id);
+
+    let mut toggles_on: Vec<(FeatureId, f32)> = changed
+        .iter()
+        .copied()
+        .filter(|&(id, value)| {
+            is_toggle(id)
+                && id != FeatureId::ScoutMode
+                && value != 0.0
+                && !enabled(id)
+        })
+        .collect();
+    toggles_on.sort_by_key(|(id, _)| id.dependencies().len());
+
+    let values: Vec<(FeatureId, f32)> = changed
+        .iter()
+        .copied()
+        .filter(|&(id, _)| !is_toggle(id))
+        .collect();
+
+    let mut toggles_off: Vec<(FeatureId, f32)> = writes
+        .iter()
+        .copied()
+        .filter(|&(id, value)| {
+            is_toggle(id)
+                && id != FeatureId::ScoutMode
+                && value == 0.0
+                && (current(id) != value || enabled(id))
+        })
+        .collect();
+    toggles_off
+        .sort_by_key(|(id, _)| std::cmp::Reverse(id.dependencies().len()));
+
+    let scout: Vec<(FeatureId, f32)> = changed
+        .iter()
+        .copied()
+        .filter(|&(id, _)| id == FeatureId::ScoutMode)
+        .collect();
+
+    [enable, toggles_on, values, toggles_off, scout].concat()
+}
+
+/// Orders a rollback.
+/// Unlike `plan()`, nothing is dropped and only dependencies that are part
+/// of the rollback themselves are touched.
+fn plan_restore(
+    model: &DeviceModel,
+    restore: &[(FeatureId, f32)],
+) -> Vec<(FeatureId, f32)> {
+    plan(model, restore, |id| {
+        if restore.iter().any(|&(queued, _)| queued == id) {
+            // differs from everything, so nothing gets dropped
+            f32::NAN
+        } else {
+            // counts as enabled, so no dependency gets added
+            1.0
+        }
+    })
+}
@@ -0,0 +1,344 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, ViewportCommand};
+use ksni::blocking::{Handle, TrayMethods};
+use ksni::menu::{CheckmarkItem, RadioGroup, RadioItem, StandardItem, SubMenu};
+use ksni::{MenuItem, ToolTip};
+use linuxblaster_control::dbus::SharedBlaster;
+use linuxblaster_control::{
+    BlasterXG6, CONNECTION_CHECK_INTERVAL, CachedLibrary, FeatureId,
+    LibraryEntry, ProfileLibrary, ValueKind,
+};
+use tracing::{info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Set while the window is hidden in the tray,
+/// or minimized where it can't be hidden (see `hide()`).
+pub static HIDDEN: AtomicBool = AtomicBool::new(false);
+/// Set by "Quit", so closing the window exits instead of hiding it.
+pub static QUITTING: AtomicBool = AtomicBool::new(false);
+
+/// What the tray shows, read from the device whenever it changes,
+/// so building the menu doesn't wait for the device.
+#[derive(Debug, Clone, Default)]
+struct TrayState {
+    outputs: Vec<&'static str>,
+    output: Option<usize>,
+    sbx: bool,
+    scout: bool,
+    /// Library name and label of every profile.
+    profiles: Vec<(String, String)>,
+    active_profile: Option<usize>,
+}
+
+impl TrayState {
+    fn read(blaster: &BlasterXG6, entries: &[LibraryEntry]) -> Self {
+        let is_on =
+            |id| blaster.feature_value(id).is_ok_and(|value| value != 0.0);
+        let outputs = match blaster.model().value_kind(FeatureId::Output) {
+            ValueKind::Preset(labels)
+                if blaster.supports(FeatureId::Output) =>
+            {
+                labels.to_vec()
+            }
+            _ => Vec::new(),
+        };
+        let output = blaster.output_label().and_then(|label| {
+            outputs.iter().position(|&output| output == label)
+        });
+
+        Self {
+            outputs,
+            output,
+            sbx: is_on(FeatureId::SbxMaster),
+            scout: is_on(FeatureId::ScoutMode),
+            active_profile: entries
+                .iter()
+                .position(|entry| blaster.matches_profile(&entry.profile)),
+            profiles: entries
+                .iter()
+                .map(|entry| {
+                    (entry.name().to_string(), entry.label().to_string())
+                })
+                .collect(),
+        }
+    }
+}
+
+/// StatusNotifierItem for the GUI: Output switching, SBX and Scout Mode
+/// toggles and profile selection, without opening the window.
+pub struct BlasterTray {
+    blaster: SharedBlaster,
+    ctx: egui::Context,
+    library: CachedLibrary,
+    state: TrayState,
+}
+
+impl BlasterTray {
+    fn refresh(&mut self) {
+        let blaster = self.blaster.lock().unwrap();
+        self.state = TrayState::read(&blaster, self.library.entries());
+    }
+
+    /// Runs `action` on the device, the menu is refreshed
+    /// through the change subscription afterwards.
+    fn with_blaster(
+        &self,
+        action: impl FnOnce(&BlasterXG6) -> Result<(), Box<dyn std::error::Error>>,
+    ) {
+        if let Err(error) = action(&self.blaster.lock().unwrap()) {
+            warn!("Tray action failed: {}", error);
+        }
+        self.ctx.request_repaint();
+    }
+}
+
+/// Shows the window again after `hide()`.
+pub fn show(ctx: &egui::Context) {
+    HIDDEN.store(false, Ordering::Relaxed);
+    if wayland() {
+        ctx.send_viewport_cmd(ViewportCommand::Minimized(false));
+    } else {
+        ctx.send_viewport_cmd(ViewportCommand::Visible(true));
+    }
+    ctx.send_viewport_cmd(ViewportCommand::Focus);
+    ctx.request_repaint();
+}
+
+/// Hides the window, or minimizes it on Wayland,
+/// where winit can't hide windows.
+pub fn hide(ctx: &egui::Context) {
+    HIDDEN.store(true, Ordering::Relaxed);
+    if wayland() {
+        ctx.send_viewport_cmd(ViewportCommand::Minimized(true));
+    } else {
+        ctx.send_viewport_cmd(ViewportCommand::Visible(false));
+    }
+}
+
+/// Whether winit runs on Wayland, which it prefers over X11.
+fn wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var_os("WAYLAND_SOCKET").is_some()
+}
+
+impl ksni::Tray for BlasterTray {
+    fn id(&self) -> String {
+        "linuxblaster_control".to_string()
+    }
+
+    fn title(&self) -> String {
+        "Sound Blaster X G6 Control".to_string()
+    }
+
+    fn icon_name(&self) -> String {
+        match self.state.output.map(|index| self.state.outputs[index]) {
+            Some("Headphones") => "audio-headphones",
+            Some(_) => "audio-speakers",
+            None => "audio-card",
+        }
+        .to_string()
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let on_off = |on| if on { "On" } else { "Off" };
+        let mut description = Vec::new();
+        if let Some(index) = self.state.output {
+            description.push(self.state.outputs[index].to_string());
+        }
+        description.push(format!("SBX {}", on_off(self.state.sbx)));
+        description.push(format!("Scout Mode {}", on_off(self.state.scout)));
+        if let Some(index) = self.state.active_profile {
+            description
+                .push(format!("Profile: {}", self.state.profiles[index].1));
+        }
+        ToolTip {
+            title: self.title(),
+            description: description.join("\n"),
+            ..Default::default()
+        }
+    }
+
+    /// Left click toggles the window.
+    fn activate(&mut self, _x: i32, _y: i32) {
+        if HIDDEN.load(Ordering::Relaxed) {
+            show(&self.ctx);
+        } else {
+            hide(&self.ctx);
+        }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let mut menu: Vec<MenuItem<Self>> = vec![
+            StandardItem {
+                label: "Show Window".to_string(),
+                activate: Box::new(|tray: &mut Self| show(&tray.ctx)),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+        ];
+
+        if !self.state.outputs.is_empty() {
+            menu.push(
+                RadioGroup {
+                    selected: self.state.output.unwrap_or(usize::MAX),
+                    select: Box::new(|tray: &mut Self, index| {
+                        let output = tray.state.outputs[index];
+                        tray.with_blaster(|blaster| {
+                            blaster.switch_output(Some(output))
+                        });
+                    }),
+                    options: self
+                        .state
+                        .outputs
+                        .iter()
+                        .map(|&label| RadioItem {
+                            label: label.to_string(),
+                            ..Default::default()
+                        })
+                        .collect(),
+                }
+                .into(),
+            );
+            menu.push(MenuItem::Separator);
+        }
+
+        for (id, checked) in [
+            (FeatureId::SbxMaster, self.state.sbx),
+            (FeatureId::ScoutMode, self.state.scout),
+        ] {
+            menu.push(
+                CheckmarkItem {
+                    label: id.display_name().to_string(),
+                    checked,
+                    activate: Box::new(move |tray: &mut Self| {
+                        tray.with_blaster(|blaster| {
+                            blaster.set_feature(id, None)
+                        });
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        menu.push(
+            SubMenu {
+                label: "Profiles".to_string(),
+                enabled: !self.state.profiles.is_empty(),
+                submenu: self
+                    .state
+                    .profiles
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (name, label))| {
+                        let name = name.clone();
+                        CheckmarkItem {
+                            label: label.clone(),
+                            checked: self.state.active_profile == Some(index),
+                            activate: Box::new(move |tray: &mut Self| {
+                                tray.with_blaster(|blaster| {
+                                    let profile = ProfileLibrary::default()
+                                        .load(&name)?;
+                                    blaster.apply(&profile)
+                                });
+                            }),
+                            ..Default::default()
+                        }
+                        .into()
+                    })
+                    .collect(),
+                ..Default::default()
+            }
+            .into(),
+        );
+
+        menu.push(MenuItem::Separator);
+        menu.push(
+            StandardItem {
+                label: "Quit".to_string(),
+                icon_name: "application-exit".to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    QUITTING.store(true, Ordering::Relaxed);
+                    // a hidden window may not process the close otherwise
+                    show(&tray.ctx);
+                    tray.ctx.send_viewport_cmd(ViewportCommand::Close);
+                }),
+                ..Default::default()
+            }
+            .into(),
+        );
+        menu
+    }
+}
+
+/// Shows the tray icon, or returns `None` if there is no
+/// StatusNotifierItem host (e.g. GNOME without the AppIndicator extension).
+///
+/// The icon follows the device through the change subscription.
+/// While the window is hidden, the tray also polls the device
+/// and checks the connection, which the window does otherwise.
+pub fn spawn(
+    blaster: SharedBlaster,
+    ctx: egui::Context,
+) -> Option<Handle<BlasterTray>> {
+    let mut library = CachedLibrary::default();
+    let state = TrayState::read(&blaster.lock().unwrap(), library.entries());
+    let tray = BlasterTray {
+        blaster: blaster.clone(),
+        ctx,
+        library,
+        state,
+    };
+    let handle = match tray.spawn() {
+        Ok(handle) => handle,
+        Err(error) => {
+            info!("No system tray: {}", error);
+            return None;
+        }
+    };
+
+    let updates = handle.clone();
+    std::thread::spawn(move || {
+        let mut changes = blaster.lock().unwrap().subscribe();
+        let mut connected = true;
+        let mut last_check = Instant::now();
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let mut changed = false;
+            {
+                let mut blaster = blaster.lock().unwrap();
+                if HIDDEN.load(Ordering::Relaxed) {
+                    if connected {
+                        blaster.poll_notifications();
+                    }
+                    if last_check.elapsed() >= CONNECTION_CHECK_INTERVAL {
+                        connected = blaster.ensure_connected();
+                        last_check = Instant::now();
+                    }
+                }
+                loop {
+                    match changes.try_recv() {
+                        Ok(_) => changed = true,
+                        Err(TryRecvError::Empty) => break,
+                        // the GUI switched to another device
+                        Err(TryRecvError::Disconnected) => {
+                            changes = blaster.subscribe();
+                            changed = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            if changed && updates.update(BlasterTray::refresh).is_none() {
+                break;
+            }
+        }
+    });
+    Some(handle)
+}